@@ -0,0 +1,435 @@
+use std::cmp::Reverse;
+
+use anyhow::{ensure, Result};
+use riichi::algo::shanten::calc_all;
+use riichi::convlog::generate::Board;
+use riichi::tile::Tile;
+use riichi::tu8;
+use serde::Serialize;
+
+use crate::fuuro::split_melds;
+use crate::result::AgariResult;
+use crate::tiles_vec_to_string;
+
+// A suited number with three (one chance) or four (kabe, no chance) copies visible.
+#[derive(Serialize)]
+pub struct WallBlock {
+    pub tile: String,
+    pub visible: u8,
+}
+
+// A tile whose every ryanmen wait goes through a block.
+#[derive(Serialize)]
+pub struct SaferTile {
+    pub tile: String,
+    // "no_chance" when every ryanmen wait needs a kabe tile, "one_chance" when some only need a one chance one
+    pub chance: &'static str,
+    pub walls: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct WallAnalysis {
+    pub blocks: Vec<WallBlock>,
+    pub safer: Vec<SaferTile>,
+}
+
+fn tile_string(kind: usize) -> String {
+    tiles_vec_to_string(&[Tile::new_unchecked(kind as u8)])
+}
+
+// The ryanmen shapes that wait on number `n` (1-9) of a suit, as the two numbers of each shape.
+pub(crate) fn ryanmen_shapes(n: usize) -> Vec<[usize; 2]> {
+    let mut shapes = vec![];
+    if n <= 6 {
+        shapes.push([n + 1, n + 2]);
+    }
+    if n >= 4 {
+        shapes.push([n - 2, n - 1]);
+    }
+    shapes
+}
+
+// Kabe and one chance blocks per suit from the tiles the player can see, and the tiles they make safer
+// against ryanmen waits. Genbutsu and suji are not taken into account here.
+pub(crate) fn wall_analysis(visible: &[u8; 34]) -> WallAnalysis {
+    let mut blocks = vec![];
+    let mut safer = vec![];
+    for suit in 0..3 {
+        let kind = |n: usize| suit * 9 + n - 1;
+        for n in 1..=9 {
+            if visible[kind(n)] >= 3 {
+                blocks.push(WallBlock {
+                    tile: tile_string(kind(n)),
+                    visible: visible[kind(n)],
+                });
+            }
+        }
+        for n in 1..=9 {
+            if visible[kind(n)] >= 4 {
+                // no copies left to be waited on
+                continue;
+            }
+            let mut walls = vec![];
+            let mut no_chance = true;
+            let blocked = ryanmen_shapes(n).iter().all(|shape| {
+                let Some(wall) = shape.iter().copied().max_by_key(|m| visible[kind(*m)]) else {
+                    return false;
+                };
+                match visible[kind(wall)] {
+                    4 => {}
+                    3 => no_chance = false,
+                    _ => return false,
+                }
+                let wall = tile_string(kind(wall));
+                if !walls.contains(&wall) {
+                    walls.push(wall);
+                }
+                true
+            });
+            if blocked {
+                safer.push(SaferTile {
+                    tile: tile_string(kind(n)),
+                    chance: if no_chance { "no_chance" } else { "one_chance" },
+                    walls,
+                });
+            }
+        }
+    }
+    WallAnalysis { blocks, safer }
+}
+
+#[derive(Serialize)]
+pub struct TileDanger {
+    pub tile: String,
+    // 0 (safe) to 100
+    pub danger: u8,
+    // what lowered or raised the score, e.g. "genbutsu" or "suji"
+    pub reasons: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+pub struct OpponentDanger {
+    // relative to the focused player like RawBoard.kawa
+    pub seat: u8,
+    pub riichi: bool,
+    pub tiles: Vec<TileDanger>,
+}
+
+fn genbutsu_of(board: &Board, seat: usize) -> [bool; 34] {
+    let mut genbutsu = [false; 34];
+    for sutehai in &board.kawa[seat] {
+        genbutsu[sutehai.pai.deaka().as_usize()] = true;
+    }
+    genbutsu
+}
+
+// Tiles the other seats discarded after `seat`'s riichi without it winning. Every seat discards about once
+// per turn, so those after the riichi in turn order passed it.
+fn passed_after_riichi(board: &Board, seat: usize) -> [bool; 34] {
+    let mut passed = [false; 34];
+    if let Some(riichi_index) = board.kawa[seat].iter().position(|sutehai| sutehai.riichi) {
+        for (other, other_kawa) in board.kawa.iter().enumerate() {
+            let first_after = if other > seat { riichi_index } else { riichi_index + 1 };
+            for sutehai in other_kawa.iter().skip(first_after) {
+                passed[sutehai.pai.deaka().as_usize()] = true;
+            }
+        }
+    }
+    passed
+}
+
+// "suji" when both ryanmen waits on a suited `kind` are cut by genbutsu 3 away, "half_suji" when one is,
+// "none" otherwise and "honor" for honors.
+fn suji(genbutsu: &[bool; 34], kind: usize) -> &'static str {
+    if kind >= 27 {
+        return "honor";
+    }
+    let n = kind % 9 + 1;
+    let sides: Vec<bool> = [n.checked_sub(3).filter(|m| *m >= 1), Some(n + 3).filter(|m| *m <= 9)]
+        .into_iter()
+        .flatten()
+        .map(|m| genbutsu[kind - n + m])
+        .collect();
+    if sides.iter().all(|side| *side) {
+        "suji"
+    } else if sides.iter().any(|side| *side) {
+        "half_suji"
+    } else {
+        "none"
+    }
+}
+
+// rough danger of a tile of `kind` against a tenpai hand, before anything reads the kawa
+fn base_danger(kind: usize, visible: u8) -> f32 {
+    if kind >= 27 {
+        return match visible {
+            0 | 1 => 35.0,
+            2 => 20.0,
+            _ => 5.0,
+        };
+    }
+    match kind % 9 + 1 {
+        1 | 9 => 40.0,
+        2 | 8 => 55.0,
+        3 | 7 => 65.0,
+        _ => 80.0,
+    }
+}
+
+// For each opponent, a 0-100 danger score per tile kind combining genbutsu, tiles passed after their
+// riichi, suji, kabe and one chance blocks, the riichi tile's neighbours and whether they can be tenpai
+// at all. It is a heuristic for coloring a hand, not a read of the actual waits. `seats` holds the
+// generator's seat of each seat of the board, see RawBoard::seat_map.
+pub(crate) fn danger_analysis(board: &Board, visible: &[u8; 34], seats: &[usize]) -> Vec<OpponentDanger> {
+    let walls = wall_analysis(visible);
+    let wall_chance = |kind: usize| {
+        let tile = tile_string(kind);
+        walls.safer.iter().find(|safer| safer.tile == tile).map(|safer| safer.chance)
+    };
+
+    (1..seats.len())
+        .map(|board_seat| {
+            let seat = seats[board_seat];
+            let kawa = &board.kawa[seat];
+            let genbutsu = genbutsu_of(board, seat);
+            let riichi_index = kawa.iter().position(|sutehai| sutehai.riichi);
+            let passed = passed_after_riichi(board, seat);
+            let riichi_tile = riichi_index.map(|i| kawa[i].pai.deaka().as_usize());
+            let calls = board.fuuro[seat].tiles.iter().filter(|fuurohai| fuurohai.sideways).count();
+
+            let tiles = (0..34)
+                .map(|kind| {
+                    let mut reasons = vec![];
+                    let mut danger = base_danger(kind, visible[kind]);
+                    if visible[kind] >= 4 {
+                        danger = 0.0;
+                        reasons.push("all_visible");
+                    } else if genbutsu[kind] {
+                        danger = 0.0;
+                        reasons.push("genbutsu");
+                    } else {
+                        if passed[kind] {
+                            danger = danger.min(5.0);
+                            reasons.push("passed_after_riichi");
+                        }
+                        if kind < 27 {
+                            match suji(&genbutsu, kind) {
+                                "suji" => {
+                                    danger *= 0.4;
+                                    reasons.push("suji");
+                                }
+                                "half_suji" => {
+                                    danger *= 0.75;
+                                    reasons.push("half_suji");
+                                }
+                                _ => {}
+                            }
+                            match wall_chance(kind) {
+                                Some("no_chance") => {
+                                    danger *= 0.3;
+                                    reasons.push("no_chance");
+                                }
+                                Some(_) => {
+                                    danger *= 0.6;
+                                    reasons.push("one_chance");
+                                }
+                                None => {}
+                            }
+                            if let Some(riichi_tile) = riichi_tile.filter(|tile| *tile < 27 && tile / 9 == kind / 9) {
+                                if riichi_tile != kind && riichi_tile.abs_diff(kind) <= 2 {
+                                    danger *= 1.2;
+                                    reasons.push("near_riichi_tile");
+                                }
+                            }
+                        }
+                        if riichi_index.is_none() {
+                            danger *= if calls > 0 { 0.7 } else { 0.4 };
+                            reasons.push(if calls > 0 { "open_hand" } else { "no_riichi" });
+                        }
+                    }
+                    TileDanger {
+                        tile: tile_string(kind),
+                        danger: danger.round().clamp(0.0, 100.0) as u8,
+                        reasons,
+                    }
+                })
+                .collect();
+            OpponentDanger {
+                seat: board_seat as u8,
+                riichi: riichi_index.is_some(),
+                tiles,
+            }
+        })
+        .collect()
+}
+
+// A tile that lowers the shanten, with the copies the player cannot see.
+#[derive(Serialize, Clone)]
+pub struct AcceptedTile {
+    pub tile: String,
+    pub remaining: u8,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Ukeire {
+    // -1 for a complete hand
+    pub shanten: i8,
+    pub accepted: Vec<AcceptedTile>,
+    pub remaining: u8,
+}
+
+#[derive(Serialize)]
+pub struct DiscardUkeire {
+    pub tile: String,
+    #[serde(flatten)]
+    pub ukeire: Ukeire,
+}
+
+#[derive(Serialize)]
+pub struct HandAnalysis {
+    #[serde(flatten)]
+    pub ukeire: Ukeire,
+    // with a tile to discard, what each distinct one leaves, best first
+    pub discards: Vec<DiscardUkeire>,
+}
+
+fn ukeire(hand: &mut [u8; 34], len_div3: u8, visible: &[u8; 34]) -> Ukeire {
+    let shanten = calc_all(hand, len_div3);
+    let mut accepted = vec![];
+    for kind in 0..34 {
+        if hand[kind] == 4 {
+            continue;
+        }
+        hand[kind] += 1;
+        if calc_all(hand, len_div3) < shanten {
+            accepted.push(AcceptedTile {
+                tile: tile_string(kind),
+                remaining: 4u8.saturating_sub(visible[kind]),
+            });
+        }
+        hand[kind] -= 1;
+    }
+    Ukeire {
+        shanten,
+        remaining: accepted.iter().map(|tile| tile.remaining).sum(),
+        accepted,
+    }
+}
+
+// Shanten of the player's closed hand and the tiles that improve it, counting what is left against every
+// tile they can see. A hand holding its draw is scored by its best discard.
+pub(crate) fn hand_analysis(board: &Board, visible: &[u8; 34]) -> HandAnalysis {
+    let mut hand = [0; 34];
+    for tile in &board.tehai {
+        hand[tile.deaka().as_usize()] += 1;
+    }
+    let len_div3 = (board.tehai.len() / 3) as u8;
+    if board.tehai.len() % 3 != 2 {
+        return HandAnalysis {
+            ukeire: ukeire(&mut hand, len_div3, visible),
+            discards: vec![],
+        };
+    }
+
+    let mut discards = vec![];
+    for kind in 0..34 {
+        if hand[kind] == 0 {
+            continue;
+        }
+        hand[kind] -= 1;
+        discards.push(DiscardUkeire {
+            tile: tile_string(kind),
+            ukeire: ukeire(&mut hand, len_div3, visible),
+        });
+        hand[kind] += 1;
+    }
+    discards.sort_by_key(|discard| (discard.ukeire.shanten, Reverse(discard.ukeire.remaining)));
+    // the waits of the best discard, and -1 when the hand is already complete
+    let mut ukeire = discards[0].ukeire.clone();
+    ukeire.shanten = calc_all(&hand, len_div3);
+    HandAnalysis { ukeire, discards }
+}
+
+#[derive(Serialize)]
+pub struct WaitScore {
+    pub tile: String,
+    // of the four, those the player cannot see
+    pub remaining: u8,
+    // None when the hand has no yaku that way or every copy is in sight
+    pub tsumo: Option<AgariResult>,
+    // a ron on the kamicha's discard
+    pub ron: Option<AgariResult>,
+}
+
+#[derive(Serialize)]
+pub struct HandTileSafety {
+    pub tile: String,
+    // in the opponent's own kawa
+    pub genbutsu: bool,
+    // discarded by another seat after the riichi
+    pub passed: bool,
+    // "suji", "half_suji", "none" or "honor"
+    pub suji: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct RiichiSafety {
+    // relative to the focused player like RawBoard.kawa
+    pub seat: u8,
+    pub tiles: Vec<HandTileSafety>,
+}
+
+// Genbutsu and suji of each distinct tile of the player's hand against every opponent in riichi.
+pub(crate) fn riichi_safety(board: &Board, seats: &[usize]) -> Vec<RiichiSafety> {
+    let mut kinds: Vec<usize> = board.tehai.iter().map(|tile| tile.deaka().as_usize()).collect();
+    kinds.sort_unstable();
+    kinds.dedup();
+    (1..seats.len())
+        .filter(|board_seat| board.kawa[seats[*board_seat]].iter().any(|sutehai| sutehai.riichi))
+        .map(|board_seat| {
+            let genbutsu = genbutsu_of(board, seats[board_seat]);
+            let passed = passed_after_riichi(board, seats[board_seat]);
+            RiichiSafety {
+                seat: board_seat as u8,
+                tiles: kinds
+                    .iter()
+                    .map(|kind| HandTileSafety {
+                        tile: tile_string(*kind),
+                        genbutsu: genbutsu[*kind],
+                        passed: passed[*kind],
+                        suji: suji(&genbutsu, *kind),
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+pub struct TileCounts {
+    // draws left before the kyoku ends in a ryuukyoku
+    pub live_wall: u8,
+    // copies of each tile kind the player cannot see, 1m to 7z
+    pub unseen: Vec<u8>,
+}
+
+// The 70 tiles between the haipai and the dead wall, 55 with three players, lose one to every draw: one
+// before each discard still in a kawa, one more for each kan's or kita's replacement tile, and the player's
+// own draw when they hold it. The kita are set aside face up, so their N are seen too.
+pub(crate) fn tile_counts(board: &Board, visible: &[u8; 34], players: usize, kita: u8) -> Result<TileCounts> {
+    let mut kans = 0;
+    for fuuro in &board.fuuro {
+        kans += split_melds(&fuuro.tiles)?.iter().filter(|meld| meld.kind.is_kan()).count();
+    }
+    let discards: usize = board.kawa.iter().map(Vec::len).sum();
+    let drawn = usize::from(board.tehai.len() % 3 == 2);
+    let draws = discards + kans + drawn + kita as usize;
+    let wall = if players == 3 { 55 } else { 70 };
+    ensure!(draws <= wall, "the board has {draws} draws, more than the wall holds");
+    let mut visible = *visible;
+    visible[tu8!(N) as usize] += kita;
+    Ok(TileCounts {
+        live_wall: (wall - draws) as u8,
+        unseen: visible.iter().map(|count| 4u8.saturating_sub(*count)).collect(),
+    })
+}
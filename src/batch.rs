@@ -0,0 +1,18 @@
+use anyhow::Result;
+use rayon::prelude::*;
+use riichi::mjai::Event;
+use serde_json::Value;
+
+use crate::{generate, tenhou_log_events, GenerateOptions, GenerateResult, RawBoard};
+
+// Batches for servers working through thousands of positions, spread over every core with rayon. Results
+// are in the order of the input and each stands alone, so one bad board does not fail the others.
+
+pub fn generate_batch(boards: Vec<RawBoard>, options: &GenerateOptions) -> Vec<Result<GenerateResult>> {
+    boards.into_par_iter().map(|raw_board| generate(raw_board, options)).collect()
+}
+
+// the mjai events of each tenhou/6 json log
+pub fn tenhou_log_events_batch(logs: &[Value]) -> Vec<Result<Vec<Event>>> {
+    logs.par_iter().map(tenhou_log_events).collect()
+}
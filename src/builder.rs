@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use riichi::convlog::generate::Board;
+
+use crate::RawBoard;
+
+#[derive(Clone, Copy)]
+pub enum Wind {
+    East,
+    South,
+    West,
+    North,
+}
+
+impl Wind {
+    fn notation(self) -> char {
+        match self {
+            Wind::East => 'E',
+            Wind::South => 'S',
+            Wind::West => 'W',
+            Wind::North => 'N',
+        }
+    }
+}
+
+// A RawBoard put together in Rust, e.g.
+// `BoardBuilder::new().kyoku(Wind::South, 3).honba(1).tehai("123m456p789s1122z").kawa(0, "1z.2z").build()`.
+// Tiles are in the RawBoard notation and seats relative to the player; nothing is checked before build.
+#[derive(Clone)]
+pub struct BoardBuilder {
+    raw: RawBoard,
+}
+
+impl BoardBuilder {
+    // East 1, the player East, no honba or kyotaku and everyone at 25000.
+    pub fn new() -> Self {
+        BoardBuilder {
+            raw: RawBoard {
+                kyoku: "E1".to_owned(),
+                jikaze: "E".to_owned(),
+                kyotaku: "0".to_owned(),
+                honba: "0".to_owned(),
+                scores: vec!["25000".to_owned(); 4],
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn kyoku(mut self, bakaze: Wind, kyoku: u8) -> Self {
+        self.raw.kyoku = format!("{}{kyoku}", bakaze.notation());
+        self
+    }
+
+    pub fn jikaze(mut self, jikaze: Wind) -> Self {
+        self.raw.jikaze = jikaze.notation().to_string();
+        self
+    }
+
+    pub fn honba(mut self, honba: u8) -> Self {
+        self.raw.honba = honba.to_string();
+        self
+    }
+
+    pub fn kyotaku(mut self, kyotaku: u8) -> Self {
+        self.raw.kyotaku = kyotaku.to_string();
+        self
+    }
+
+    pub fn scores(mut self, scores: [i32; 4]) -> Self {
+        self.raw.scores = scores.iter().map(|score| score.to_string()).collect();
+        self
+    }
+
+    pub fn dora(mut self, indicators: &str) -> Self {
+        self.raw.dora = indicators.to_owned();
+        self
+    }
+
+    pub fn tehai(mut self, tehai: &str) -> Self {
+        self.raw.tehai = tehai.to_owned();
+        self
+    }
+
+    pub fn tsumo(mut self, tile: &str) -> Self {
+        self.raw.tsumo = tile.to_owned();
+        self
+    }
+
+    pub fn kawa(mut self, seat: usize, kawa: &str) -> Self {
+        set_seat(&mut self.raw.kawa, seat, kawa);
+        self
+    }
+
+    pub fn fuuro(mut self, seat: usize, fuuro: &str) -> Self {
+        set_seat(&mut self.raw.fuuro, seat, fuuro);
+        self
+    }
+
+    // the seats whose riichi sticks are already in kyotaku, see RawBoard.deposits
+    pub fn deposits(mut self, seats: &[usize]) -> Self {
+        self.raw.deposits = seats.iter().map(|seat| seat.to_string()).collect();
+        self
+    }
+
+    // the board for generate and the other functions that take a RawBoard
+    pub fn raw_board(self) -> RawBoard {
+        self.raw
+    }
+
+    pub fn build(self) -> Result<Board> {
+        self.raw.try_into().context("parse error")
+    }
+}
+
+impl Default for BoardBuilder {
+    fn default() -> Self {
+        BoardBuilder::new()
+    }
+}
+
+fn set_seat(seats: &mut Vec<String>, seat: usize, value: &str) {
+    if seats.len() <= seat {
+        seats.resize(seat + 1, String::new());
+    }
+    seats[seat] = value.to_owned();
+}
@@ -0,0 +1,66 @@
+use std::cell::RefCell;
+
+use riichi::mjai::Event;
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// Recently converted tenhou logs by the hash of their json, most recent last. Off until a capacity is set.
+struct ConversionCache {
+    capacity: usize,
+    entries: Vec<(u64, Vec<Event>)>,
+}
+
+thread_local! {
+    static CONVERSIONS: RefCell<ConversionCache> = const {
+        RefCell::new(ConversionCache {
+            capacity: 0,
+            entries: vec![],
+        })
+    };
+}
+
+pub(crate) fn set_capacity(capacity: usize) {
+    CONVERSIONS.with_borrow_mut(|cache| {
+        cache.capacity = capacity;
+        let excess = cache.entries.len().saturating_sub(capacity);
+        cache.entries.drain(..excess);
+    });
+}
+
+pub(crate) fn enabled() -> bool {
+    CONVERSIONS.with_borrow(|cache| cache.capacity > 0)
+}
+
+pub(crate) fn get(hash: u64) -> Option<Vec<Event>> {
+    CONVERSIONS.with_borrow_mut(|cache| {
+        let i = cache.entries.iter().position(|(entry_hash, _)| *entry_hash == hash)?;
+        let entry = cache.entries.remove(i);
+        let events = entry.1.clone();
+        cache.entries.push(entry);
+        Some(events)
+    })
+}
+
+pub(crate) fn insert(hash: u64, events: &[Event]) {
+    CONVERSIONS.with_borrow_mut(|cache| {
+        if cache.capacity == 0 {
+            return;
+        }
+        if cache.entries.len() >= cache.capacity {
+            cache.entries.remove(0);
+        }
+        cache.entries.push((hash, events.to_vec()));
+    });
+}
+
+pub(crate) fn clear() {
+    // a fresh list, so the memory of the old entries is given back
+    CONVERSIONS.with_borrow_mut(|cache| cache.entries = vec![]);
+}
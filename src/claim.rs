@@ -0,0 +1,52 @@
+use anyhow::{ensure, Result};
+use riichi::convlog::generate::{Board, Sutehai};
+use riichi::mjai::Event;
+
+use crate::{filler_tile, visible_counts};
+
+// A claim snapshot is a board right after the opponent at relative seat `discarder` discarded the last tile
+// of their kawa, with the player holding 13 tiles and deciding whether to call it. The generator only ends
+// on the player's own draw, so the board is completed to that draw first and the log cut back afterwards.
+pub(crate) fn prepare_claim(board: &mut Board, discarder: usize) -> Result<()> {
+    ensure!(
+        board.tehai.len() % 3 == 1,
+        "a claim snapshot needs the tehai before the draw, it has {} tiles",
+        board.tehai.len()
+    );
+    ensure!(
+        !board.kawa[discarder].is_empty(),
+        "kawa[{discarder}] is empty, there is no discard to claim"
+    );
+    let mut counts = visible_counts(board);
+    // seats after the discarder discard something that is cut off again, and the player draws
+    for kawa in &mut board.kawa[discarder + 1..] {
+        kawa.push(Sutehai {
+            pai: filler_tile(&mut counts)?,
+            tedashi: true,
+            riichi: false,
+        });
+    }
+    board.tehai.push(filler_tile(&mut counts)?);
+    Ok(())
+}
+
+// Ends the kyoku right after the last discard of the absolute seat `discarder`, keeping the closing events.
+pub(crate) fn truncate_at_claim(events: &mut Vec<Event>, discarder: u8) -> Result<()> {
+    let tail_start = events.len()
+        - events
+            .iter()
+            .rev()
+            .take_while(|event| matches!(event, Event::EndKyoku | Event::EndGame))
+            .count();
+    let tail = events.split_off(tail_start);
+    let discard = events
+        .iter()
+        .rposition(|event| matches!(event, Event::Dahai { actor, .. } if *actor == discarder));
+    ensure!(
+        discard.is_some(),
+        "the generated log has no discard by seat {discarder} to claim"
+    );
+    events.truncate(discard.unwrap_or_default() + 1);
+    events.extend(tail);
+    Ok(())
+}
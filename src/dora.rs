@@ -0,0 +1,84 @@
+use anyhow::{ensure, Result};
+use riichi::convlog::generate::Board;
+use riichi::tile::Tile;
+
+use crate::fuuro::{meld_to_string, split_melds};
+use crate::pairi::is_pairi;
+use crate::{filler_tile, tiles_vec_to_string, visible_counts, RawBoard};
+
+fn kan_count(board: &Board) -> Result<usize> {
+    let mut kans = 0;
+    for fuuro in &board.fuuro {
+        kans += split_melds(&fuuro.tiles)?.iter().filter(|meld| meld.kind.is_kan()).count();
+    }
+    Ok(kans)
+}
+
+// Every kan reveals one more indicator after the first.
+pub(crate) fn check_dora_count(board: &Board) -> Result<()> {
+    let kans = kan_count(board)?;
+    ensure!(
+        board.dora_indicators.len() == kans + 1,
+        "{} dora indicators given but {kans} kans are on the table, which reveal {}",
+        board.dora_indicators.len(),
+        kans + 1
+    );
+    Ok(())
+}
+
+fn tile_string(tile: Tile) -> String {
+    tiles_vec_to_string(&[tile])
+}
+
+// Fixes `raw_board` so its dora indicators match its kans, `board` being the parse of it, and says what changed.
+// Extra indicators are trimmed from the end, or with `inject_kans` explained by ankan of tiles nobody can see,
+// dealt to the opponents in turn. Missing indicators are unseen tiles.
+pub(crate) fn reconcile_dora(raw_board: &mut RawBoard, board: &Board, inject_kans: bool) -> Result<Vec<String>> {
+    let mut report = vec![];
+    let mut indicators = board.dora_indicators.clone();
+    let mut kans = kan_count(board)?;
+    let mut counts = visible_counts(board);
+
+    if inject_kans {
+        let players = raw_board.players()? as usize;
+        let (seats, _) = raw_board.seat_map()?;
+        raw_board.fuuro.resize(players, String::new());
+        let mut seat = 1;
+        let mut rewritten = [false; 4];
+        while indicators.len() > kans + 1 && kans < 4 {
+            // the board does not say whether the game plays red fives, which would make a kan of four plain
+            // ones impossible, and three player mahjong has no 2m to 8m
+            let kind = (0..34)
+                .rev()
+                .find(|kind| counts[*kind] == 0 && ![4, 13, 22].contains(kind) && (players == 4 || !(1..=7).contains(kind)));
+            let Some(kind) = kind else {
+                break;
+            };
+            counts[kind] = 4;
+            let tile = tile_string(Tile::new_unchecked(kind as u8));
+            // the hand analyzer's format is rewritten from the parse first, which has the generator's seats
+            if !rewritten[seat] && is_pairi(&raw_board.fuuro[seat]) {
+                raw_board.fuuro[seat] = meld_to_string(&board.fuuro[seats[seat]].tiles);
+                rewritten[seat] = true;
+            }
+            raw_board.fuuro[seat].push_str(&tile.repeat(4));
+            report.push(format!("added an ankan of {tile} to fuuro[{seat}]"));
+            kans += 1;
+            seat = seat % (players - 1) + 1;
+        }
+    }
+    while indicators.len() > kans + 1 {
+        if let Some(tile) = indicators.pop() {
+            report.push(format!("removed dora indicator {}", tile_string(tile)));
+        }
+    }
+    while indicators.len() < kans + 1 {
+        let tile = filler_tile(&mut counts)?;
+        report.push(format!("added dora indicator {}", tile_string(tile)));
+        indicators.push(tile);
+    }
+    if !report.is_empty() {
+        raw_board.dora = indicators.iter().map(|tile| tile_string(*tile)).collect();
+    }
+    Ok(report)
+}
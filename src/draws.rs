@@ -0,0 +1,126 @@
+use anyhow::{bail, ensure, Context, Result};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+
+use crate::tiles_vec_to_string;
+use crate::ura::used_counts;
+
+// Whether every discard and call of `seat` takes a tile it holds at the time.
+fn replays(events: &[Event], seat: u8) -> bool {
+    let mut hand: Vec<Tile> = vec![];
+    let take = |hand: &mut Vec<Tile>, tile: Tile| match hand.iter().position(|held| *held == tile) {
+        Some(i) => {
+            hand.swap_remove(i);
+            true
+        }
+        None => false,
+    };
+    for event in events {
+        let taken = match event {
+            Event::StartKyoku { tehais, .. } => {
+                hand = tehais[seat as usize].to_vec();
+                true
+            }
+            Event::Tsumo { actor, pai } if *actor == seat => {
+                hand.push(*pai);
+                true
+            }
+            Event::Dahai { actor, pai, .. } | Event::Kakan { actor, pai, .. } if *actor == seat => take(&mut hand, *pai),
+            Event::Chi { actor, consumed, .. } | Event::Pon { actor, consumed, .. } if *actor == seat => {
+                consumed.iter().all(|tile| take(&mut hand, *tile))
+            }
+            Event::Daiminkan { actor, consumed, .. } if *actor == seat => consumed.iter().all(|tile| take(&mut hand, *tile)),
+            Event::Ankan { actor, consumed } if *actor == seat => consumed.iter().all(|tile| take(&mut hand, *tile)),
+            _ => true,
+        };
+        if !taken {
+            return false;
+        }
+    }
+    true
+}
+
+// Makes the draw at `index` of `seat` be `tile`. The generator's tile trades places with a copy of `tile` in the
+// haipai, so the hand holds the same tiles from that draw on; an opponent's tile nobody sees can also be
+// swapped for an unseen one, which changes only its hidden hand.
+fn set_draw(events: &mut [Event], seat: u8, index: usize, tile: Tile, player_id: u8) -> Result<()> {
+    let Event::Tsumo { pai: drawn, .. } = events[index] else {
+        bail!("event {index} is not a draw");
+    };
+    let discard = events[index + 1..].iter().find_map(|event| match event {
+        Event::Dahai { actor, tsumogiri, .. } if *actor == seat => Some(*tsumogiri),
+        _ => None,
+    });
+    ensure!(
+        discard != Some(true),
+        "the kawa has the drawn tile thrown out tsumogiri, so the draw is {}",
+        tiles_vec_to_string(&[drawn])
+    );
+    let Some(start) = events.iter().position(|event| matches!(event, Event::StartKyoku { .. })) else {
+        bail!("the log has no kyoku");
+    };
+    let slots = match &events[start] {
+        Event::StartKyoku { tehais, .. } => tehais[seat as usize].len(),
+        _ => 0,
+    };
+    for slot in 0..slots {
+        let mut swapped = events.to_vec();
+        if let Event::StartKyoku { tehais, .. } = &mut swapped[start] {
+            if tehais[seat as usize][slot] != tile {
+                continue;
+            }
+            tehais[seat as usize][slot] = drawn;
+        }
+        if let Event::Tsumo { pai, .. } = &mut swapped[index] {
+            *pai = tile;
+        }
+        if replays(&swapped, seat) {
+            events.clone_from_slice(&swapped);
+            return Ok(());
+        }
+    }
+    let wins = events
+        .iter()
+        .any(|event| matches!(event, Event::Hora { actor, .. } if *actor == seat));
+    if seat != player_id && !wins && used_counts(events)[tile.deaka().as_usize()] < 4 {
+        let mut replaced = events.to_vec();
+        if let Event::Tsumo { pai, .. } = &mut replaced[index] {
+            *pai = tile;
+        }
+        if replays(&replaced, seat) {
+            events.clone_from_slice(&replaced);
+            return Ok(());
+        }
+    }
+    bail!(
+        "no {} in the hand to draw instead of {} without changing what it discards and calls",
+        tiles_vec_to_string(&[tile]),
+        tiles_vec_to_string(&[drawn])
+    )
+}
+
+// Replaces the generator's draws of each absolute seat by `draws`, that seat's tiles in the order it drew
+// them, replacement draws of kans included. A seat with fewer draws listed keeps the generator's later ones.
+pub(crate) fn set_draws(events: &mut [Event], draws: &[Vec<Tile>; 4], player_id: u8) -> Result<()> {
+    for (seat, draws) in (0..4u8).zip(draws) {
+        let tsumos: Vec<usize> = events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| matches!(event, Event::Tsumo { actor, .. } if *actor == seat))
+            .map(|(i, _)| i)
+            .collect();
+        ensure!(
+            draws.len() <= tsumos.len(),
+            "seat {seat} draws {} times in the log, not {}",
+            tsumos.len(),
+            draws.len()
+        );
+        for (n, (index, tile)) in tsumos.into_iter().zip(draws).enumerate() {
+            if matches!(events[index], Event::Tsumo { pai, .. } if pai == *tile) {
+                continue;
+            }
+            set_draw(events, seat, index, *tile, player_id).with_context(|| format!("draw {} of seat {seat}", n + 1))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use std::ops::Range;
+
+use riichi::mjai::Event;
+
+// events of the `index`-th kyoku, from StartKyoku through EndKyoku
+pub(crate) fn kyoku_range(events: &[Event], index: usize) -> Option<Range<usize>> {
+    let start = events
+        .iter()
+        .enumerate()
+        .filter(|(_, event)| matches!(event, Event::StartKyoku { .. }))
+        .nth(index)?
+        .0;
+    let end = start + events[start..].iter().position(|event| matches!(event, Event::EndKyoku))? + 1;
+    Some(start..end)
+}
+
+pub(crate) fn kyoku_oya(events: &[Event]) -> Option<u8> {
+    events.iter().find_map(|event| match event {
+        Event::StartKyoku { oya, .. } => Some(*oya),
+        _ => None,
+    })
+}
+
+// Renumbers every absolute seat by `shift`, so seat `s` becomes `(s + shift) % 4`.
+pub(crate) fn rotate_seats(events: &mut [Event], shift: u8) {
+    let shift = shift % 4;
+    if shift == 0 {
+        return;
+    }
+    let rotate = |seat: &mut u8| *seat = (*seat + shift) % 4;
+    for event in events {
+        match event {
+            Event::StartGame { names, id, .. } => {
+                names.rotate_right(shift as usize);
+                if let Some(id) = id {
+                    rotate(id);
+                }
+            }
+            Event::StartKyoku { oya, scores, tehais, .. } => {
+                rotate(oya);
+                scores.rotate_right(shift as usize);
+                tehais.rotate_right(shift as usize);
+            }
+            Event::Tsumo { actor, .. }
+            | Event::Dahai { actor, .. }
+            | Event::Kakan { actor, .. }
+            | Event::Ankan { actor, .. }
+            | Event::Reach { actor }
+            | Event::ReachAccepted { actor } => rotate(actor),
+            Event::Chi { actor, target, .. } | Event::Pon { actor, target, .. } | Event::Daiminkan { actor, target, .. } => {
+                rotate(actor);
+                rotate(target);
+            }
+            Event::Hora {
+                actor, target, deltas, ..
+            } => {
+                rotate(actor);
+                rotate(target);
+                if let Some(deltas) = deltas {
+                    deltas.rotate_right(shift as usize);
+                }
+            }
+            Event::Ryukyoku { deltas: Some(deltas) } => deltas.rotate_right(shift as usize),
+            _ => {}
+        }
+    }
+}
+
+// Rotates the seats so the kyoku's dealer is consistent with `chiicha` having been the first East.
+pub(crate) fn set_chiicha(events: &mut [Event], chiicha: u8) {
+    let Some((kyoku, oya)) = events.iter().find_map(|event| match event {
+        Event::StartKyoku { kyoku, oya, .. } => Some((*kyoku, *oya)),
+        _ => None,
+    }) else {
+        return;
+    };
+    // mjai kyoku numbers start at 1
+    let expected_oya = (chiicha + kyoku + 3) % 4;
+    rotate_seats(events, (expected_oya + 4 - oya) % 4);
+}
+
+// Declares the riichi of each seat whose `riichi` discard, counted from the end of its kawa, the generator
+// played without a reach event: reach before that discard and reach_accepted after it unless it dealt in.
+pub(crate) fn add_missing_riichi(events: &mut Vec<Event>, riichi: [Option<usize>; 4]) {
+    for (seat, from_end) in riichi.iter().enumerate() {
+        let (seat, Some(from_end)) = (seat as u8, from_end) else {
+            continue;
+        };
+        if events
+            .iter()
+            .any(|event| matches!(event, Event::Reach { actor } if *actor == seat))
+        {
+            continue;
+        }
+        let discards: Vec<usize> = events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| matches!(event, Event::Dahai { actor, .. } if *actor == seat))
+            .map(|(i, _)| i)
+            .collect();
+        let Some(&i) = discards.len().checked_sub(from_end + 1).and_then(|n| discards.get(n)) else {
+            continue;
+        };
+        let ron = matches!(events.get(i + 1), Some(Event::Hora { target, .. }) if *target == seat);
+        if !ron {
+            events.insert(i + 1, Event::ReachAccepted { actor: seat });
+        }
+        events.insert(i, Event::Reach { actor: seat });
+    }
+}
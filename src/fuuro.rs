@@ -0,0 +1,296 @@
+use anyhow::{anyhow, bail, ensure, Context, Error, Result};
+use riichi::convlog::generate::Fuurohai;
+use riichi::hand::parse_tile;
+use riichi::tile::Tile;
+use riichi::tu8;
+
+use crate::rng::Rng;
+use crate::tiles_vec_to_string;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MeldKind {
+    Chi,
+    Pon,
+    Daiminkan,
+    Ankan,
+    Kakan,
+}
+
+impl MeldKind {
+    pub(crate) fn is_kan(self) -> bool {
+        matches!(self, MeldKind::Daiminkan | MeldKind::Ankan | MeldKind::Kakan)
+    }
+}
+
+// The relative seat a call named after `@` is from: kamicha (3), toimen (2), shimocha (1) or the number.
+pub(crate) fn source_seat(name: &str) -> Result<u8> {
+    match name {
+        "kamicha" | "3" => Ok(3),
+        "toimen" | "2" => Ok(2),
+        "shimocha" | "1" => Ok(1),
+        other => bail!("{other:?} is not a seat to call from, expected kamicha, toimen or shimocha"),
+    }
+}
+
+// One seat's RawBoard fuuro string; tiles in parentheses are sideways. Where the sideways tile sits tells
+// whom it was called from, left for the kamicha, middle for the toimen and right for the shimocha, unless
+// the call names it, e.g. (1p@toimen)1p1p, and the tile is then moved there.
+pub(crate) fn parse_fuuro(raw_fuuro: &str) -> Result<Vec<Fuurohai>> {
+    let mut tiles = vec![];
+    let mut sources = vec![];
+    let mut fuuro_iter = raw_fuuro.chars().peekable();
+    let mut in_parentheses = false;
+    loop {
+        match fuuro_iter.peek() {
+            Some('(') => {
+                _ = fuuro_iter.next();
+                if in_parentheses {
+                    bail!("nested opening parenthesis in fuuro");
+                }
+                in_parentheses = true;
+            }
+            Some(')') => {
+                _ = fuuro_iter.next();
+                if !in_parentheses {
+                    bail!("extra closing parenthesis in fuuro");
+                }
+                in_parentheses = false;
+            }
+            Some('@') => {
+                _ = fuuro_iter.next();
+                ensure!(
+                    in_parentheses && !tiles.is_empty(),
+                    "@ names the seat of a sideways tile, inside its parentheses"
+                );
+                let name: String = std::iter::from_fn(|| fuuro_iter.next_if(|c| *c != ')')).collect();
+                sources.push((tiles.len() - 1, source_seat(&name)?));
+            }
+            Some(_) => {
+                let tile_string = format!(
+                    "{}{}",
+                    fuuro_iter.next().unwrap(),
+                    fuuro_iter.next().context("incorrect fuuro")?
+                );
+                // ?? is a tile nobody remembers, fill_unknown picks one the meld can hold
+                let tile = match tile_string.as_str() {
+                    "??" => Tile::new_unchecked(tu8!(?)),
+                    _ => parse_tile(&tile_string)?,
+                };
+                tiles.push(Fuurohai {
+                    tile,
+                    sideways: in_parentheses,
+                });
+            }
+            None => break,
+        }
+    }
+    for (index, seat) in sources {
+        place_sideways(&mut tiles, index, seat)?;
+    }
+    Ok(tiles)
+}
+
+// Moves the sideways tiles of the meld holding `tiles[index]` to where a call from `seat` puts them.
+pub(crate) fn place_sideways(tiles: &mut [Fuurohai], index: usize, seat: u8) -> Result<()> {
+    let mut start = 0;
+    let (kind, len) = split_melds(tiles)?
+        .into_iter()
+        .map(|meld| (meld.kind, meld.tiles.len()))
+        .find(|(_, len)| {
+            start += len;
+            index < start
+        })
+        .context("incorrect fuuro")?;
+    start -= len;
+    let slot = match (kind, seat) {
+        (MeldKind::Ankan, _) => bail!("an ankan is not called from anyone"),
+        (MeldKind::Chi, 3) => 0,
+        (MeldKind::Chi, _) => bail!("a chi can only be called from the kamicha"),
+        (MeldKind::Daiminkan, 1) => 3,
+        (_, seat) => 3 - seat as usize,
+    };
+    let meld = &mut tiles[start..start + len];
+    let (sideways, upright): (Vec<Fuurohai>, Vec<Fuurohai>) = meld.iter().cloned().partition(|fuurohai| fuurohai.sideways);
+    let mut placed = upright;
+    for (i, fuurohai) in sideways.into_iter().enumerate() {
+        placed.insert(slot + i, fuurohai);
+    }
+    meld.clone_from_slice(&placed);
+    Ok(())
+}
+
+// The start of every called meld of `tiles` with the relative seat its sideways tiles say it is from.
+pub(crate) fn meld_sources(tiles: &[Fuurohai]) -> Result<Vec<(usize, u8)>> {
+    let mut start = 0;
+    let mut sources = vec![];
+    for meld in split_melds(tiles)? {
+        let first = meld.tiles.iter().position(|fuurohai| fuurohai.sideways);
+        if let Some(first) = first {
+            let seat = match (meld.kind, first) {
+                (MeldKind::Daiminkan, 3) => 1,
+                (_, first) => 3 - first.min(2) as u8,
+            };
+            sources.push((start, seat));
+        }
+        start += meld.tiles.len();
+    }
+    Ok(sources)
+}
+
+pub(crate) fn meld_to_string(meld: &[Fuurohai]) -> String {
+    meld.iter()
+        .map(|fuurohai| {
+            let tile = tiles_vec_to_string(&[fuurohai.tile]);
+            if fuurohai.sideways {
+                format!("({tile})")
+            } else {
+                tile
+            }
+        })
+        .collect()
+}
+
+pub(crate) struct Meld<'a> {
+    pub kind: MeldKind,
+    pub tiles: &'a [Fuurohai],
+}
+
+// What the `meld` of three or four tiles is, going by its tiles and which of them are sideways. A ?? tile
+// stands in for whichever tile makes it a meld.
+fn meld_kind(meld: &[Fuurohai]) -> Result<MeldKind> {
+    let known: Vec<Tile> = meld
+        .iter()
+        .map(|fuurohai| fuurohai.tile)
+        .filter(|tile| !tile.is_unknown())
+        .collect();
+    let first = known.first().copied().unwrap_or(meld[0].tile);
+    let same = known.iter().all(|tile| tile.deaka() == first.deaka());
+    let sideways: Vec<_> = meld.iter().map(|fuurohai| fuurohai.sideways).collect();
+    let kind = match (meld.len(), same) {
+        (4, false) => bail!("meld starting with {first} is neither a chi, pon nor kan"),
+        (4, true) => match sideways.iter().filter(|sideways| **sideways).count() {
+            0 => MeldKind::Ankan,
+            1 => MeldKind::Daiminkan,
+            2 if sideways.windows(2).any(|pair| pair[0] && pair[1]) => MeldKind::Kakan,
+            _ => bail!("kan of {first} has misplaced sideways tiles"),
+        },
+        (_, true) => MeldKind::Pon,
+        (_, false) => {
+            let mut kinds: Vec<_> = known.iter().map(|tile| tile.deaka().as_u8()).collect();
+            kinds.sort_unstable();
+            let (low, high) = (kinds[0], kinds[kinds.len() - 1]);
+            ensure!(
+                high < 27 && low / 9 == high / 9 && high - low <= 2 && kinds.windows(2).all(|pair| pair[0] != pair[1]),
+                "meld starting with {first} is neither a chi, pon nor kan"
+            );
+            MeldKind::Chi
+        }
+    };
+    if matches!(kind, MeldKind::Chi | MeldKind::Pon) {
+        ensure!(
+            sideways.iter().filter(|sideways| **sideways).count() == 1,
+            "meld starting with {first} needs exactly one sideways tile"
+        );
+    }
+    ensure!(
+        meld.iter().filter(|fuurohai| fuurohai.tile.is_aka()).count() <= 1,
+        "meld starting with {first} has more than one red five"
+    );
+    Ok(kind)
+}
+
+// The melds of `tiles[start..]`, trying a kan before a chi or pon at each meld and going back on a split that
+// leaves the rest unreadable. Fails with the error found furthest into the fuuro.
+fn split_from(tiles: &[Fuurohai], start: usize) -> Result<Vec<Meld<'_>>, (usize, Error)> {
+    if start == tiles.len() {
+        return Ok(vec![]);
+    }
+    let mut furthest: Option<(usize, Error)> = None;
+    let mut keep = |(at, error): (usize, Error)| {
+        if furthest.as_ref().is_none_or(|(furthest_at, _)| at >= *furthest_at) {
+            furthest = Some((at, error));
+        }
+    };
+    for len in [4, 3] {
+        let Some(meld) = tiles.get(start..start + len) else {
+            keep((start, anyhow!("incomplete meld starting with {}", tiles[start].tile)));
+            continue;
+        };
+        match meld_kind(meld) {
+            Ok(kind) => match split_from(tiles, start + len) {
+                Ok(mut rest) => {
+                    rest.insert(0, Meld { kind, tiles: meld });
+                    return Ok(rest);
+                }
+                Err(error) => keep(error),
+            },
+            Err(error) => keep((start, error)),
+        }
+    }
+    Err(furthest.unwrap_or_else(|| (start, anyhow!("incorrect fuuro"))))
+}
+
+// Fills the ?? tiles of every meld of `tiles` with unseen tiles the meld can hold, picked from `rng`; a kind
+// the board does not show in the meld has to pass `allowed` too.
+pub(crate) fn fill_unknown(
+    rng: &mut Rng,
+    tiles: &mut [Fuurohai],
+    counts: &mut [u8; 34],
+    allowed: impl Fn(usize) -> bool,
+) -> Result<()> {
+    let mut start = 0;
+    let mut melds = vec![];
+    for meld in split_melds(tiles)? {
+        melds.push((start, meld.tiles.len(), meld.kind));
+        start += meld.tiles.len();
+    }
+    for (start, len, kind) in melds {
+        let meld = &mut tiles[start..start + len];
+        let unknown: Vec<usize> = (0..len).filter(|i| meld[*i].tile.is_unknown()).collect();
+        if unknown.is_empty() {
+            continue;
+        }
+        let known: Vec<usize> = meld
+            .iter()
+            .filter(|fuurohai| !fuurohai.tile.is_unknown())
+            .map(|fuurohai| fuurohai.tile.deaka().as_usize())
+            .collect();
+        // the kinds the ?? tiles could take, one list per way of completing the meld
+        let candidates: Vec<Vec<usize>> = match (kind, known.first()) {
+            (MeldKind::Chi, _) => (0..27)
+                .filter(|low| low % 9 <= 6 && known.iter().all(|kind| (*low..low + 3).contains(kind)))
+                .map(|low| (low..low + 3).filter(|kind| !known.contains(kind)).collect())
+                .collect(),
+            (_, Some(kind)) => vec![vec![*kind; unknown.len()]],
+            (_, None) => (0..34).map(|kind| vec![kind; unknown.len()]).collect(),
+        };
+        let candidates: Vec<Vec<usize>> = candidates
+            .into_iter()
+            .filter(|kinds| {
+                kinds.iter().all(|kind| {
+                    let needed = kinds.iter().filter(|other| *other == kind).count() as u8;
+                    4u8.saturating_sub(counts[*kind]) >= needed && (known.contains(kind) || allowed(*kind))
+                })
+            })
+            .collect();
+        ensure!(
+            !candidates.is_empty(),
+            "no unseen tiles are left for the ?? of the meld starting at tile {}",
+            start + 1
+        );
+        let kinds = &candidates[rng.below(candidates.len())];
+        for (i, kind) in unknown.into_iter().zip(kinds) {
+            meld[i].tile = Tile::new_unchecked(*kind as u8);
+            counts[*kind] += 1;
+        }
+    }
+    Ok(())
+}
+
+// Splits one seat's fuuro into melds. The tiles keep the order they were written in, so a red five can
+// sit at any position of its meld (0p5p5p5p and 5p5p5p0p are both an ankan with the aka). Each meld is
+// three or four tiles with its own sideways tiles, so a pon (1p)1p1p followed by a chi (1p)2p3p is not read
+// as one kan.
+pub(crate) fn split_melds(tiles: &[Fuurohai]) -> Result<Vec<Meld<'_>>> {
+    split_from(tiles, 0).map_err(|(_, error)| error)
+}
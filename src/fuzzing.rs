@@ -0,0 +1,209 @@
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::*;
+use riichi::convlog::generate::Board;
+
+use crate::RawBoard;
+
+// Boards for fuzzing the parser and the generator. Tiles come off one shuffled set of 136 with a red five per
+// suit, so counts always add up, while turn order, riichi, scores and sticks are left loose: boards that
+// read but that no game gets to are the ones that crash generation.
+
+// a kind 0..34 with whether it is the red five
+type WallTile = (u8, bool);
+
+fn notation((kind, red): WallTile) -> String {
+    let suit = ['m', 'p', 's', 'z'][kind as usize / 9];
+    match red {
+        true => format!("0{suit}"),
+        false => format!("{}{suit}", kind % 9 + 1),
+    }
+}
+
+struct Wall(Vec<WallTile>);
+
+impl Wall {
+    fn new() -> Self {
+        let mut tiles = vec![];
+        for kind in 0..34u8 {
+            let red = matches!(kind, 4 | 13 | 22);
+            tiles.extend((0..4).map(|copy| (kind, red && copy == 0)));
+        }
+        Wall(tiles)
+    }
+
+    fn draw(&mut self, u: &mut Unstructured) -> arbitrary::Result<WallTile> {
+        if self.0.is_empty() {
+            return Err(arbitrary::Error::NotEnoughData);
+        }
+        let i = u.choose_index(self.0.len())?;
+        Ok(self.0.swap_remove(i))
+    }
+
+    fn draws(&mut self, u: &mut Unstructured, count: usize) -> arbitrary::Result<String> {
+        (0..count).map(|_| self.draw(u).map(notation)).collect()
+    }
+
+    fn take(&mut self, kind: u8) -> Option<WallTile> {
+        let i = self.0.iter().position(|tile| tile.0 == kind)?;
+        Some(self.0.swap_remove(i))
+    }
+
+    // a pon, a chi or an open kan of whatever is left, the chi called from the kamicha and the others with
+    // the sideways tile anywhere
+    fn meld(&mut self, u: &mut Unstructured) -> arbitrary::Result<Option<String>> {
+        let first = self.draw(u)?;
+        let mut kinds = match u.int_in_range(0..=2)? {
+            0 if first.0 < 27 && first.0 % 9 < 7 => vec![first.0 + 1, first.0 + 2],
+            1 => vec![first.0; 3],
+            _ => vec![first.0; 2],
+        };
+        let chi = kinds[0] != first.0;
+        let mut tiles = vec![first];
+        for kind in kinds.drain(..) {
+            match self.take(kind) {
+                Some(tile) => tiles.push(tile),
+                None => {
+                    self.0.extend(tiles);
+                    return Ok(None);
+                }
+            }
+        }
+        let sideways = if chi { 0 } else { u.choose_index(tiles.len())? };
+        let meld = tiles
+            .iter()
+            .enumerate()
+            .map(|(i, tile)| match i == sideways {
+                true => format!("({})", notation(*tile)),
+                false => notation(*tile),
+            })
+            .collect();
+        Ok(Some(meld))
+    }
+
+    fn kawa(&mut self, u: &mut Unstructured, len: usize, riichi: &mut bool) -> arbitrary::Result<String> {
+        let mut kawa = String::new();
+        for _ in 0..len {
+            kawa.push_str(&notation(self.draw(u)?));
+            let tsumogiri = u.arbitrary::<bool>()?;
+            if !*riichi && u.ratio(1, 12)? {
+                *riichi = true;
+                kawa.push(if tsumogiri { '-' } else { '!' });
+            } else if tsumogiri {
+                kawa.push('.');
+            }
+        }
+        Ok(kawa)
+    }
+}
+
+impl<'a> Arbitrary<'a> for RawBoard {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let winds = ['E', 'S', 'W', 'N'];
+        let mut wall = Wall::new();
+        let mut raw = RawBoard {
+            kyoku: format!("{}{}", u.choose(&winds[..2])?, u.int_in_range(1..=4)?),
+            jikaze: u.choose(&winds)?.to_string(),
+            honba: u.int_in_range(0..=8u8)?.to_string(),
+            kyotaku: u.int_in_range(0..=3u8)?.to_string(),
+            scores: (0..4)
+                .map(|_| u.int_in_range(0..=600u32).map(|hundreds| (hundreds * 100).to_string()))
+                .collect::<arbitrary::Result<_>>()?,
+            ..Default::default()
+        };
+        let indicators = u.int_in_range(1..=3)?;
+        raw.dora = wall.draws(u, indicators)?;
+        for _ in 0..4 {
+            let mut fuuro = String::new();
+            for _ in 0..u.int_in_range(0..=2)? {
+                if let Some(meld) = wall.meld(u)? {
+                    fuuro.push_str(&meld);
+                }
+            }
+            raw.fuuro.push(fuuro);
+        }
+        let melds = raw.fuuro[0].matches('(').count();
+        raw.tehai = wall.draws(u, 13 - 3 * melds)?;
+        if u.arbitrary::<bool>()? {
+            raw.tsumo = wall.draws(u, 1)?;
+        }
+        let turns = u.int_in_range(0..=15)?;
+        for seat in 0..4 {
+            let mut riichi = false;
+            let len = turns + u.int_in_range(0..=1)?;
+            raw.kawa.push(wall.kawa(u, len, &mut riichi)?);
+            if riichi && u.arbitrary::<bool>()? {
+                raw.deposits.push_str(&seat.to_string());
+            }
+        }
+        Ok(raw)
+    }
+}
+
+// A Board that RawBoard's parse accepts, straight from fuzz input.
+pub fn arbitrary_board(u: &mut Unstructured) -> arbitrary::Result<Board> {
+    RawBoard::arbitrary(u)?
+        .try_into()
+        .map_err(|_| arbitrary::Error::IncorrectFormat)
+}
+
+// proptest strategies backed by the Arbitrary implementation, so both draw the same boards
+pub fn raw_board_strategy() -> impl Strategy<Value = RawBoard> {
+    proptest::collection::vec(any::<u8>(), 512..2048).prop_filter_map("not enough bytes", |bytes| {
+        RawBoard::arbitrary(&mut Unstructured::new(&bytes)).ok()
+    })
+}
+
+pub fn board_strategy() -> impl Strategy<Value = Board> {
+    proptest::collection::vec(any::<u8>(), 512..2048).prop_filter_map("board does not parse", |bytes| {
+        arbitrary_board(&mut Unstructured::new(&bytes)).ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use riichi::convlog::generate::{generate_mjai_logs, Board};
+    use riichi::tile::Tile;
+
+    use super::board_strategy;
+    use crate::{extract_board, ExtractOptions};
+
+    // the tiles of a board in a fixed order, for comparing boards whose notation groups them differently
+    fn sorted(tiles: impl IntoIterator<Item = Tile>) -> Vec<Tile> {
+        let mut tiles: Vec<Tile> = tiles.into_iter().collect();
+        tiles.sort();
+        tiles
+    }
+
+    fn fuuro_tiles(board: &Board, seat: usize) -> Vec<Tile> {
+        sorted(board.fuuro[seat].tiles.iter().map(|fuurohai| fuurohai.tile))
+    }
+
+    proptest! {
+        // A board the generator makes a log of comes back from that log, parsed, as the same board.
+        #[test]
+        fn generate_round_trip(board in board_strategy()) {
+            let Ok(events) = generate_mjai_logs(board.clone()) else {
+                return Ok(());
+            };
+            let raw_board = extract_board(events, &board.jikaze.to_string(), &ExtractOptions::default());
+            let raw_board = raw_board.map_err(|e| TestCaseError::fail(format!("{e:#}")))?;
+            let parsed: Board = raw_board.try_into().map_err(|e: anyhow::Error| TestCaseError::fail(format!("{e:#}")))?;
+            prop_assert_eq!(parsed.bakaze, board.bakaze);
+            prop_assert_eq!(parsed.kyoku, board.kyoku);
+            prop_assert_eq!(parsed.honba, board.honba);
+            prop_assert_eq!(&parsed.dora_indicators, &board.dora_indicators);
+            prop_assert_eq!(sorted(parsed.tehai.iter().copied()), sorted(board.tehai.iter().copied()));
+            for seat in 0..4 {
+                let kawa = |board: &Board| {
+                    board.kawa[seat]
+                        .iter()
+                        .map(|sutehai| (sutehai.pai, sutehai.tedashi, sutehai.riichi))
+                        .collect::<Vec<_>>()
+                };
+                prop_assert_eq!(kawa(&parsed), kawa(&board), "kawa[{}]", seat);
+                prop_assert_eq!(fuuro_tiles(&parsed, seat), fuuro_tiles(&board, seat), "fuuro[{}]", seat);
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::str::FromStr;
+
+use riichi::convlog::generate::Board;
+use riichi::tile::Tile;
+use riichi::tu8;
+
+use crate::tiles_vec_to_string;
+
+// A self-contained html snippet of the board for forums and blogs that strip svg: tiles are unicode mahjong
+// glyphs styled inline, and each also has a `pai pai-<tile>` class for pages that bring their own sprites.
+
+pub(crate) fn glyph(tile: Tile) -> char {
+    let kind = tile.deaka().as_u8() as u32;
+    let code = match kind {
+        0..9 => 0x1f007 + kind,
+        9..18 => 0x1f019 + kind - 9,
+        18..27 => 0x1f010 + kind - 18,
+        27..31 => 0x1f000 + kind - 27,
+        // haku, hatsu, chun
+        _ => 0x1f006 - (kind - 31),
+    };
+    char::from_u32(code).unwrap_or('?')
+}
+
+// "123m(4p)" -> "🀇🀈🀉(🀜)" with `unicode`, "１２３ｍ（４ｐ）" otherwise. Red fives become plain fives in glyphs.
+pub(crate) fn display_notation(notation: &str, unicode: bool) -> String {
+    if !unicode {
+        return notation
+            .chars()
+            .map(|c| match c {
+                '!'..='~' => char::from_u32(c as u32 - 0x21 + 0xff01).unwrap_or(c),
+                _ => c,
+            })
+            .collect();
+    }
+    let mut out = String::new();
+    let mut numbers = String::new();
+    for c in notation.chars() {
+        match c {
+            '0'..='9' => numbers.push(c),
+            'm' | 'p' | 's' | 'z' => {
+                for number in numbers.drain(..) {
+                    // 0 is the red five, which the tile names only know as 5mr
+                    let name = if number == '0' {
+                        format!("5{c}r")
+                    } else {
+                        format!("{number}{c}")
+                    };
+                    match Tile::from_str(&name) {
+                        Ok(tile) => out.push(glyph(tile)),
+                        Err(_) => out.push(number),
+                    }
+                }
+            }
+            _ => {
+                out.push_str(&numbers);
+                numbers.clear();
+                out.push(c);
+            }
+        }
+    }
+    out.push_str(&numbers);
+    out
+}
+
+fn tile_html(tile: Tile, sideways: bool, tsumogiri: bool) -> String {
+    let name = tiles_vec_to_string(&[tile]);
+    let mut style = String::from("font-size:28px;line-height:1;");
+    if tile.is_aka() {
+        style.push_str("color:#c00;");
+    }
+    if tsumogiri {
+        style.push_str("opacity:.6;");
+    }
+    if sideways {
+        style.push_str("display:inline-block;transform:rotate(90deg);margin:0 4px;");
+    }
+    format!(
+        "<span class=\"pai pai-{name}\" title=\"{name}\" style=\"{style}\">{}</span>",
+        glyph(tile)
+    )
+}
+
+fn row(label: &str, content: &str) -> String {
+    format!(
+        "<div style=\"margin:2px 0;white-space:nowrap\"><b style=\"display:inline-block;width:5em\">{label}</b>{content}</div>"
+    )
+}
+
+pub(crate) fn board_to_html(board: &Board) -> String {
+    let jikaze = (board.jikaze.as_u8() - tu8!(E)) as usize;
+    let winds = ["E", "S", "W", "N"];
+    let dora: String = board
+        .dora_indicators
+        .iter()
+        .map(|tile| tile_html(*tile, false, false))
+        .collect();
+    let mut html = String::from(
+        "<div class=\"paifu\" style=\"display:inline-block;font-family:sans-serif;font-size:14px;\
+         border:1px solid #ccc;border-radius:4px;padding:6px 8px;background:#fafaf5;color:#222\">",
+    );
+    html.push_str(&row(
+        &format!("{}{}", board.bakaze, board.kyoku),
+        &format!("honba {} · kyotaku {} · dora {dora}", board.honba, board.kyotaku),
+    ));
+    for seat in 0..4 {
+        let kawa: String = board.kawa[seat]
+            .iter()
+            .map(|sutehai| tile_html(sutehai.pai, sutehai.riichi, !sutehai.tedashi))
+            .collect();
+        let fuuro: String = board.fuuro[seat]
+            .tiles
+            .iter()
+            .map(|fuurohai| tile_html(fuurohai.tile, fuurohai.sideways, false))
+            .collect();
+        let label = format!("{} {}", winds[(jikaze + seat) % 4], board.scores[seat]);
+        let separator = if fuuro.is_empty() {
+            ""
+        } else {
+            " <span style=\"margin:0 6px\">|</span> "
+        };
+        html.push_str(&row(&label, &format!("{kawa}{separator}{fuuro}")));
+    }
+    let tehai: String = board.tehai.iter().map(|tile| tile_html(*tile, false, false)).collect();
+    html.push_str(&row("hand", &tehai));
+    html.push_str("</div>");
+    html
+}
@@ -0,0 +1,86 @@
+use anyhow::{ensure, Context, Result};
+use riichi::convlog::tenhou::{Log, RawLog};
+use riichi::convlog::tenhou_to_mjai;
+use riichi::mjai::Event;
+use serde_json::Value;
+use serde_wasm_bindgen::from_value;
+use wasm_bindgen::prelude::*;
+
+use crate::wasm::stage_error;
+
+// Converts only `kyokus` of a tenhou log, keeping the header (names, rule, ...) of `log`.
+fn convert_kyokus(log: &Value, kyokus: &[Value]) -> Result<Vec<Event>> {
+    let mut partial = log.clone();
+    partial["log"] = Value::Array(kyokus.to_vec());
+    let raw_tenhou_log: RawLog = serde_json::from_value(partial).context("incorrect tenhou log")?;
+    let tenhou_log: Log = raw_tenhou_log.try_into()?;
+    tenhou_to_mjai(&tenhou_log)
+}
+
+// Imports a tenhou log that keeps growing, like an ongoing game being polled, converting only the kyokus
+// added since the last update. If an earlier kyoku changed, the whole log is imported again.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct TenhouImporter {
+    // serialized kyokus already imported, to notice a different game
+    kyokus: Vec<String>,
+    events: Vec<Event>,
+}
+
+impl TenhouImporter {
+    fn update_value(&mut self, log: &Value) -> Result<usize> {
+        let kyokus = log["log"].as_array().context("log must be an array of kyokus")?;
+        let serialized: Vec<String> = kyokus.iter().map(Value::to_string).collect();
+        if !serialized.starts_with(&self.kyokus) {
+            self.kyokus.clear();
+            self.events.clear();
+        }
+        let new_kyokus = &kyokus[self.kyokus.len()..];
+        if new_kyokus.is_empty() {
+            return Ok(0);
+        }
+
+        let mut events = convert_kyokus(log, new_kyokus)?;
+        if self.events.is_empty() {
+            self.events = events;
+        } else {
+            ensure!(
+                matches!(events.first(), Some(Event::StartGame { .. })) && matches!(events.last(), Some(Event::EndGame)),
+                "converted kyokus are not a whole game"
+            );
+            events.pop();
+            let end_game = self.events.pop();
+            self.events.extend(events.into_iter().skip(1));
+            self.events.extend(end_game);
+        }
+        self.kyokus = serialized;
+        Ok(new_kyokus.len())
+    }
+}
+
+#[wasm_bindgen]
+impl TenhouImporter {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Feeds the current state of the log and returns how many kyokus were newly imported.
+    pub fn update(&mut self, val: JsValue) -> Result<usize, JsValue> {
+        let log: Value = from_value(val).map_err(|e| stage_error("parse", "Deserialize", e))?;
+        self.update_value(&log).map_err(|e| stage_error("parse", "Parse", e))
+    }
+
+    pub fn kyoku_count(&self) -> usize {
+        self.kyokus.len()
+    }
+
+    // mjai events of every kyoku imported so far
+    pub fn mjai_log(&self) -> Result<JsValue, JsValue> {
+        serde_wasm_bindgen::to_value(&self.events).map_err(|e| stage_error("convert", "Serialize", e))
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -1,72 +1,523 @@
 #![allow(dead_code)]
+mod analysis;
+#[cfg(feature = "parallel")]
+mod batch;
+mod builder;
+mod cache;
+mod claim;
+mod dora;
+mod draws;
+mod events;
+mod fuuro;
+#[cfg(feature = "fuzzing")]
+mod fuzzing;
+mod html;
+#[cfg(feature = "wasm")]
+mod importer;
+mod locale;
+mod majsoul;
+mod mjlog;
+mod mortal;
+mod notation;
+mod nuki;
+mod pairi;
+mod quiz;
+mod replay;
+mod result;
+mod riichi_city;
+mod rng;
+mod rules;
+mod sanma;
+mod scenario;
+mod svg;
+mod tenhou;
+mod text;
+mod timeline;
+mod trace;
+mod tracker;
+mod ura;
+mod url;
+mod validate;
+mod wall;
+mod warnings;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod win;
+
 use std::array::from_fn;
 use std::str::FromStr;
 
-use anyhow::{bail, ensure, Context, Error, Result};
-use riichi::convlog::generate::{generate_mjai_logs, Board, Fuurohai, Sutehai};
+use anyhow::{anyhow, bail, ensure, Context, Error, Result};
+use riichi::convlog::generate::{generate_mjai_logs, Board, Sutehai};
 use riichi::convlog::tenhou::{Log, RawLog};
 use riichi::convlog::{mjai_to_tenhou, tenhou_to_mjai};
 use riichi::hand::{parse_tile, parse_tiles, tiles_to_string};
 use riichi::mjai::Event;
-use riichi::state::PlayerState;
 use riichi::tile::Tile;
 use riichi::tu8;
 use serde::Deserialize;
 use serde::Serialize;
-use serde_json::to_string;
-use serde_wasm_bindgen::from_value;
-use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsValue;
+use serde_json::{json, to_string, Value};
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
-}
+use crate::analysis::{
+    danger_analysis, hand_analysis, riichi_safety, tile_counts, wall_analysis, HandAnalysis, OpponentDanger, RiichiSafety,
+    TileCounts, WallAnalysis,
+};
+use crate::claim::{prepare_claim, truncate_at_claim};
+use crate::dora::{check_dora_count, reconcile_dora};
+use crate::draws::set_draws;
+use crate::events::{add_missing_riichi, kyoku_oya, kyoku_range, set_chiicha};
+use crate::fuuro::{fill_unknown, parse_fuuro, split_melds, MeldKind};
+use crate::majsoul::majsoul_to_mjai;
+use crate::mjlog::{mjai_to_mjlog, mjlog_to_mjai};
+use crate::mortal::{entry_event, review_events, review_input};
+use crate::replay::final_hands;
+use crate::result::kyoku_result;
+use crate::riichi_city::riichi_city_to_mjai;
+use crate::rng::Rng;
+use crate::rules::apply_honba;
+use crate::scenario::{
+    add_rons, play_outcome, random_board, redeal_hidden, redeal_sanma, set_shanten, swap_win_tile, Abortive, Ending,
+};
+use crate::timeline::key_moments;
+use crate::tracker::Tracker;
+use crate::ura::{fill_placeholders, set_ura, Placeholder, UraSpec};
+use crate::warnings::board_warnings;
+use crate::win::append_win;
+
+pub use crate::analysis::{AcceptedTile, DiscardUkeire, HandTileSafety, SaferTile, TileDanger, Ukeire, WaitScore, WallBlock};
+#[cfg(feature = "parallel")]
+pub use crate::batch::{generate_batch, tenhou_log_events_batch};
+pub use crate::builder::{BoardBuilder, Wind};
+#[cfg(feature = "fuzzing")]
+pub use crate::fuzzing::{arbitrary_board, board_strategy, raw_board_strategy};
+pub use crate::locale::{localize_error, Locale};
+pub use crate::notation::BoardError;
+pub use crate::result::{AgariResult, KyokuResult, Yaku};
+pub use crate::rules::RuleConfig;
+pub use crate::scenario::{Outcome, ScenarioOptions};
+pub use crate::timeline::KeyMoment;
+pub use crate::trace::TraceStep;
+pub use crate::validate::{validate_mjai, EventError};
+pub use crate::warnings::BoardWarning;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
 pub struct RawBoard {
     pub kyoku: String,
     pub jikaze: String,
+    // kyotaku, honba and scores may also be json numbers
+    #[serde(deserialize_with = "string_or_number")]
     pub kyotaku: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub honba: String,
     pub dora: String,
+    #[serde(deserialize_with = "strings_or_numbers")]
     pub scores: Vec<String>,
     pub tehai: String,
     pub kawa: Vec<String>,
     pub fuuro: Vec<String>,
+    // overrides the turn implied by the kawa; missing early discards are padded in
+    #[serde(default)]
+    pub junme: String,
+    // absolute seat (0-3) of the first East, which fixes the seat numbering of the generated game
+    #[serde(default)]
+    pub chiicha: String,
+    // relative seat (1-3) that just discarded the last tile of its kawa, for a snapshot of the player's
+    // call decision instead of their own turn; a tehai a tile short without it waits on the kamicha
+    #[serde(default)]
+    pub claim: String,
+    // tiles padded discards may not be, e.g. the player's winning tiles
+    #[serde(default)]
+    pub fill_avoid: String,
+    // relative seats (e.g. "3" or "13") whose padded discards must be genbutsu against the player
+    #[serde(default)]
+    pub fill_genbutsu: String,
+    // relative seats played by a tsumogiri bot or left vacant: every discard is tsumogiri and they never call
+    #[serde(default)]
+    pub bots: String,
+    // 3 for sanma, 4 when empty. A sanma board has three seats of scores (35000 when empty), kawa and fuuro,
+    // its calls lying left for the kamicha and right for the shimocha; there is no chi and no 2m to 8m.
+    #[serde(default)]
+    pub players: String,
+    // kita set aside for nukidora per seat of a three player board, e.g. ["NN", "", "N"]
+    #[serde(default)]
+    pub nuki: Vec<String>,
+    // ura indicators to show when the kyoku ends in the player's riichi tsumo, as ura_indicators does in
+    // GenerateOptions
+    #[serde(default)]
+    pub uradora: String,
+    // the player's freshly drawn tile, kept apart from a tehai that is then one tile short
+    #[serde(default)]
+    pub tsumo: String,
+    // relative seats (e.g. "02") whose riichi sticks are already off `scores` and counted in `kyotaku`, as a
+    // score display shows them mid-kyoku; the generator takes the sticks of the other riichis itself
+    #[serde(default)]
+    pub deposits: String,
+    // the tiles each seat draws from the board on, in order and rinshan draws included, e.g. ["5m3z", "", "1p"],
+    // for the log to come out the same every time; draws past the end of a list are the generator's
+    #[serde(default)]
+    pub draws: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrNumber {
+    String(String),
+    Number(serde_json::Number),
+}
+
+impl From<StringOrNumber> for String {
+    fn from(value: StringOrNumber) -> Self {
+        match value {
+            StringOrNumber::String(string) => string,
+            // JavaScript numbers come as floats
+            StringOrNumber::Number(number) => match (number.as_i64(), number.as_f64()) {
+                (Some(integer), _) => integer.to_string(),
+                (None, Some(float)) if float.fract() == 0.0 => (float as i64).to_string(),
+                _ => number.to_string(),
+            },
+        }
+    }
+}
+
+fn string_or_number<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    StringOrNumber::deserialize(deserializer).map(String::from)
+}
+
+fn strings_or_numbers<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let values = Vec::<StringOrNumber>::deserialize(deserializer)?;
+    Ok(values.into_iter().map(String::from).collect())
+}
+
+impl RawBoard {
+    // the tile fields as the parsers read them, see notation::plain_notation; kawa may be grouped like tehai
+    fn plain_notation(&mut self) {
+        for field in [
+            &mut self.tehai,
+            &mut self.tsumo,
+            &mut self.dora,
+            &mut self.uradora,
+            &mut self.fill_avoid,
+        ]
+        .into_iter()
+        .chain(&mut self.fuuro)
+        .chain(&mut self.draws)
+        {
+            *field = notation::plain_notation(field, false).0;
+        }
+        for kawa in &mut self.kawa {
+            *kawa = notation::plain_notation(kawa, true).0;
+        }
+    }
+
+    // every red five of the tile fields written 0m, see notation::zero_aka
+    pub fn zero_aka(&mut self) {
+        for field in [
+            &mut self.tehai,
+            &mut self.tsumo,
+            &mut self.dora,
+            &mut self.uradora,
+            &mut self.fill_avoid,
+        ]
+        .into_iter()
+        .chain(&mut self.kawa)
+        .chain(&mut self.fuuro)
+        .chain(&mut self.draws)
+        {
+            *field = notation::zero_aka(field);
+        }
+    }
+
+    fn chiicha(&self) -> Result<Option<u8>> {
+        if self.chiicha.is_empty() {
+            return Ok(None);
+        }
+        let chiicha: u8 = self.chiicha.parse().context("incorrect chiicha")?;
+        ensure!(chiicha < 4, "chiicha must be a seat from 0 to 3");
+        Ok(Some(chiicha))
+    }
+
+    // the generator's relative seat of the discarder
+    fn claim(&self) -> Result<Option<usize>> {
+        if self.claim.is_empty() {
+            return Ok(None);
+        }
+        let claim: usize = self.claim.parse().context("incorrect claim")?;
+        ensure!(
+            (1..4).contains(&claim),
+            "claim must be the relative seat of an opponent, from 1 to 3"
+        );
+        let (seats, empty) = self.seat_map()?;
+        ensure!(
+            empty.is_none() || claim < 3,
+            "claim must be the relative seat of an opponent, from 1 to 2"
+        );
+        Ok(Some(seats[claim]))
+    }
+
+    fn uradora(&self) -> Result<Option<UraSpec>> {
+        if self.uradora.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(UraSpec::Indicators(
+            parse_tiles(&notation::plain_notation(&self.uradora, false).0).context("incorrect uradora")?,
+        )))
+    }
+
+    // The first red five the board itself gives, which rules without red fives cannot play.
+    fn red_five(&self) -> Result<Option<Tile>> {
+        let board = parse_board(self.clone(), false)?;
+        let draws = self.draws()?;
+        let uradora = match self.uradora()? {
+            Some(UraSpec::Indicators(indicators)) => indicators,
+            _ => vec![],
+        };
+        let aka = visible_tiles(&board)
+            .chain(draws.iter().flatten())
+            .chain(&uradora)
+            .find(|tile| tile.is_aka())
+            .copied();
+        Ok(aka)
+    }
+
+    fn fill_rules(&self) -> Result<FillRules> {
+        let (_, empty_seat) = self.seat_map()?;
+        let mut rules = FillRules {
+            avoid: [false; 34],
+            genbutsu: [false; 4],
+            empty_seat,
+        };
+        if !self.fill_avoid.is_empty() {
+            for tile in parse_tiles(&self.fill_avoid).context("incorrect fill_avoid")? {
+                rules.avoid[tile.deaka().as_usize()] = true;
+            }
+        }
+        if empty_seat.is_some() {
+            rules.avoid[tu8!(2m) as usize..=tu8!(8m) as usize].fill(true);
+        }
+        // the counts of the padding do not see the kita
+        if self.nuki()?.iter().any(|count| *count > 0) {
+            rules.avoid[tu8!(N) as usize] = true;
+        }
+        let genbutsu = opponent_seats(&self.fill_genbutsu).context("incorrect fill_genbutsu")?;
+        rules.genbutsu = self.four_seat_flags(genbutsu).context("incorrect fill_genbutsu")?;
+        Ok(rules)
+    }
+
+    fn bots(&self) -> Result<[bool; 4]> {
+        let bots = opponent_seats(&self.bots).context("incorrect bots")?;
+        self.four_seat_flags(bots).context("incorrect bots")
+    }
+
+    fn deposits(&self) -> Result<[bool; 4]> {
+        let deposits = relative_seats(&self.deposits).context("incorrect deposits")?;
+        self.four_seat_flags(deposits).context("incorrect deposits")
+    }
+
+    fn draws(&self) -> Result<[Vec<Tile>; 4]> {
+        ensure!(self.draws.len() <= 4, "draws has more than four seats");
+        let (seats, empty) = self.seat_map()?;
+        ensure!(
+            empty.is_none() || self.draws.len() <= 3,
+            "draws of a three player board has three seats"
+        );
+        let mut draws: [Vec<Tile>; 4] = Default::default();
+        for (i, (seat, raw)) in seats
+            .iter()
+            .zip(&self.draws)
+            .enumerate()
+            .filter(|(_, (_, raw))| !raw.is_empty())
+        {
+            draws[*seat] =
+                parse_tiles(&notation::plain_notation(raw, false).0).with_context(|| format!("incorrect draws[{i}]"))?;
+        }
+        Ok(draws)
+    }
+
+    // how many kita each seat of the board has set aside
+    fn nuki(&self) -> Result<[u8; 4]> {
+        let mut nuki = [0; 4];
+        ensure!(self.nuki.len() <= 4, "nuki has more than four seats");
+        ensure!(
+            self.nuki.iter().all(String::is_empty) || (self.players()? == 3 && self.nuki.len() <= 3),
+            "nukidora is played by the three seats of a three player game"
+        );
+        for (seat, (count, raw)) in nuki
+            .iter_mut()
+            .zip(&self.nuki)
+            .enumerate()
+            .filter(|(_, (_, raw))| !raw.is_empty())
+        {
+            let tiles =
+                parse_tiles(&notation::plain_notation(raw, false).0).with_context(|| format!("incorrect nuki[{seat}]"))?;
+            ensure!(tiles.iter().all(|tile| tile.as_u8() == tu8!(N)), "nuki can only be N");
+            *count = tiles.len() as u8;
+        }
+        Ok(nuki)
+    }
+
+    fn players(&self) -> Result<u8> {
+        match self.players.as_str() {
+            "" | "4" => Ok(4),
+            "3" => Ok(3),
+            other => bail!("players must be 3 or 4, not {other:?}"),
+        }
+    }
+
+    // The generator's relative seat of each seat of the board, and of a three player board also the empty
+    // seat, which comes last; see sanma::four_seats.
+    fn seat_map(&self) -> Result<([usize; 4], Option<usize>)> {
+        if self.players()? == 4 {
+            return Ok(([0, 1, 2, 3], None));
+        }
+        let kyoku: u8 = self.kyoku.get(1..2).unwrap_or_default().parse().context("incorrect kyoku")?;
+        let jikaze: Tile = self.jikaze.parse().context("incorrect jikaze")?;
+        let (seats, empty) = sanma::four_seats(kyoku, jikaze.as_u8().wrapping_sub(tu8!(E)))?;
+        Ok(([seats[0], seats[1], seats[2], empty], Some(empty)))
+    }
+
+    // Flags by relative seat of the board at the generator's seats.
+    fn four_seat_flags(&self, flags: [bool; 4]) -> Result<[bool; 4]> {
+        let (seats, empty) = self.seat_map()?;
+        ensure!(empty.is_none() || !flags[3], "a three player board has seats 0 to 2");
+        let mut four = [false; 4];
+        for (seat, flag) in seats.iter().zip(flags) {
+            four[*seat] |= flag;
+        }
+        Ok(four)
+    }
+}
+
+// "02" -> the player and toimen flags
+fn relative_seats(raw: &str) -> Result<[bool; 4]> {
+    let mut seats = [false; 4];
+    for seat in raw.chars() {
+        let seat = seat.to_digit(10).context("seats are digits")? as usize;
+        ensure!(seat < 4, "seats must be from 0 to 3");
+        seats[seat] = true;
+    }
+    Ok(seats)
+}
+
+// "13" -> the shimocha and kamicha flags
+fn opponent_seats(raw: &str) -> Result<[bool; 4]> {
+    let seats = relative_seats(raw)?;
+    ensure!(!seats[0], "seats must be opponents, from 1 to 3");
+    Ok(seats)
 }
 
 impl TryInto<Board> for RawBoard {
     type Error = Error;
     fn try_into(self) -> Result<Board> {
+        self.into_board(true)
+    }
+}
+
+impl RawBoard {
+    // `check_dora` off lets the dora reconciliation parse boards whose indicators and kans disagree
+    fn into_board(mut self, check_dora: bool) -> Result<Board> {
+        self.plain_notation();
         let mut board = Board::default();
+        let claim = self.claim()?;
+        let fill_rules = self.fill_rules()?;
+        let bots = self.bots()?;
+        let deposits = self.deposits()?;
+        let players = self.players()?;
+        let kita: u8 = self.nuki()?.iter().sum();
+        let (seats, empty) = self.seat_map()?;
+        if let Some(empty) = empty {
+            ensure!(
+                self.scores.len() <= 3 && self.kawa.len() <= 3 && self.fuuro.len() <= 3,
+                "a three player board has three seats of scores, kawa and fuuro"
+            );
+            // the generator's four seats, the empty one without points
+            let mut scores = vec!["35000".to_owned(); 4];
+            for (seat, score) in seats.iter().zip(std::mem::take(&mut self.scores)) {
+                if !score.is_empty() {
+                    scores[*seat] = score;
+                }
+            }
+            scores[empty] = "0".to_owned();
+            self.scores = scores;
+            for values in [&mut self.kawa, &mut self.fuuro] {
+                let mut four = vec![String::new(); 4];
+                for (seat, value) in seats.iter().zip(std::mem::take(values)) {
+                    four[*seat] = value;
+                }
+                *values = four;
+            }
+        }
 
-        ensure!(self.kyoku.len() == 2, "kyoku must be <bakaze><honba> (e.g. S3)");
+        ensure!(self.kyoku.len() == 2, "kyoku must be <bakaze><kyoku> (e.g. S3)");
         board.bakaze = Tile::from_str(&self.kyoku[0..1]).context("incorrect bakaze")?;
+        // W and N are the extension rounds of a game nobody has won by the end of South
+        ensure!(
+            (tu8!(E)..=tu8!(N)).contains(&board.bakaze.as_u8()),
+            "bakaze must be one of E, S, W or N"
+        );
         board.kyoku = self.kyoku[1..2].parse().context("incorrect kyoku")?;
+        ensure!((1..=4).contains(&board.kyoku), "kyoku must be from 1 to 4");
 
         board.jikaze = self.jikaze.parse().context("incorrect jikaze")?;
+        if empty.is_some() {
+            let jikaze = sanma::four_seat_jikaze(board.kyoku, board.jikaze.as_u8() - tu8!(E));
+            board.jikaze = Tile::new_unchecked(tu8!(E) + jikaze);
+        }
         board.kyotaku = self.kyotaku.parse().context("incorrect kyotaku")?;
         board.honba = self.honba.parse().context("incorrect honba")?;
         board.dora_indicators = parse_tiles(&self.dora).context("incorrect dora")?;
-        for (score, raw_score) in board.scores.iter_mut().zip(self.scores) {
+        // the seat of the board a generator's seat is, for the errors
+        let board_seat = |seat: usize| seats.iter().position(|four| *four == seat).unwrap_or(seat);
+        for (seat, (score, raw_score)) in board.scores.iter_mut().zip(self.scores).enumerate() {
             if !raw_score.is_empty() {
-                *score = raw_score.parse().context("incorrect score")?;
+                *score = raw_score
+                    .parse()
+                    .with_context(|| format!("incorrect scores[{}]", board_seat(seat)))?;
             } else {
                 *score = 25000;
             }
         }
-        board.tehai = parse_tiles(&self.tehai).context("incorrect tehai")?;
+        // tenhou's hand analyzer strings work too, with the player's calls after `+`
+        let (tehai, pairi_melds) = pairi::split_hand(&self.tehai);
+        board.tehai = parse_tiles(tehai).context("incorrect tehai")?;
+        // the generator draws the last tile of the hand
+        if !self.tsumo.is_empty() {
+            ensure!(
+                claim.is_none(),
+                "a claim snapshot is before the player's draw, it has no tsumo"
+            );
+            board.tehai.push(parse_tile(&self.tsumo).context("incorrect tsumo")?);
+        }
+        let mut raw_fuuros = self.fuuro;
+        for (seat, raw_fuuro) in raw_fuuros.iter_mut().enumerate() {
+            if pairi::is_pairi(raw_fuuro) {
+                let (first, mut melds) = pairi::split_hand(raw_fuuro);
+                melds.insert(0, first);
+                *raw_fuuro = pairi::melds_to_fuuro(&melds).with_context(|| format!("incorrect fuuro[{}]", board_seat(seat)))?;
+            }
+        }
+        if !pairi_melds.is_empty() {
+            ensure!(
+                raw_fuuros.first().is_none_or(String::is_empty),
+                "calls cannot be given both in tehai and fuuro"
+            );
+            if raw_fuuros.is_empty() {
+                raw_fuuros.push(String::new());
+            }
+            raw_fuuros[0] = pairi::melds_to_fuuro(&pairi_melds).context("incorrect tehai")?;
+        }
 
-        // tsumogiri "1p", tedashi "1p.", riichi "1p-"
-        for (kawa, raw_kawa) in board.kawa.iter_mut().zip(self.kawa) {
+        // tedashi "1p", tsumogiri "1p.", tsumogiri riichi "1p-", tedashi riichi "1p!"; "??" is a discard nobody
+        // remembers, filled in below
+        let mut unknown = vec![];
+        for (seat, (kawa, raw_kawa)) in board.kawa.iter_mut().zip(self.kawa).enumerate() {
             if raw_kawa.is_empty() {
                 continue;
             }
+            let incorrect = || format!("incorrect kawa[{}]", board_seat(seat));
             let mut chars = raw_kawa.chars().peekable();
             while chars.peek().is_some() {
-                let tile_string = format!("{}{}", chars.next().unwrap(), chars.next().context("incorrect kawa")?);
+                let tile_string = format!("{}{}", chars.next().unwrap(), chars.next().with_context(incorrect)?);
                 let (tsumogiri, riichi) = match chars.peek() {
                     Some('.') => {
                         chars.next();
@@ -76,97 +527,956 @@ impl TryInto<Board> for RawBoard {
                         chars.next();
                         (true, true)
                     }
+                    Some('!') => {
+                        chars.next();
+                        (false, true)
+                    }
                     _ => (false, false),
                 };
+                let pai = if tile_string == "??" {
+                    unknown.push((seat, kawa.len()));
+                    Tile::new_unchecked(tu8!(?))
+                } else {
+                    parse_tile(&tile_string).with_context(incorrect)?
+                };
                 kawa.push(Sutehai {
-                    pai: parse_tile(&tile_string)?,
+                    pai,
                     tedashi: !tsumogiri,
                     riichi,
                 });
             }
         }
+        // a hand in riichi is locked, every later discard is its draw
+        for (seat, kawa) in board.kawa.iter_mut().enumerate() {
+            let declarations = kawa.iter().filter(|sutehai| sutehai.riichi).count();
+            if declarations > 1 {
+                return Err(anyhow!("riichi is declared {declarations} times, a hand declares it once"))
+                    .with_context(|| format!("incorrect kawa[{}]", board_seat(seat)));
+            }
+            if let Some(declared) = kawa.iter().position(|sutehai| sutehai.riichi) {
+                kawa[declared + 1..].iter_mut().for_each(|sutehai| sutehai.tedashi = false);
+            }
+        }
         // chi (1p)2p3p, pon (1p)1p1p, daiminkan (1p)1p1p1p, ankan 1p1p1p1p, pon+kakan 1p1p(1p)(1p)
-        for (fuuro, raw_fuuro) in board.fuuro.iter_mut().zip(self.fuuro) {
-            if raw_fuuro.is_empty() {
-                continue;
+        // a red five keeps the position it is written at, e.g. 0s5s5s5s or 5s5s5s0s; ?? is a tile nobody
+        // remembers, e.g. (??)2p3p
+        // (1p@toimen)1p1p names the seat instead of the sideways tile's position
+        for (seat, (fuuro, raw_fuuro)) in board.fuuro.iter_mut().zip(raw_fuuros).enumerate() {
+            let incorrect = || format!("incorrect fuuro[{}]", board_seat(seat));
+            fuuro.tiles = parse_fuuro(&raw_fuuro).with_context(incorrect)?;
+            split_melds(&fuuro.tiles).with_context(incorrect)?;
+            // left is the kamicha and right the shimocha among the three, wherever the empty seat sits
+            if let Some(empty) = empty {
+                sanma::place_calls(&mut fuuro.tiles, seat, empty).with_context(incorrect)?;
             }
-            let mut fuuro_iter = raw_fuuro.chars().peekable();
-            let mut in_parentheses = false;
-            loop {
-                match fuuro_iter.peek() {
-                    Some('(') => {
-                        _ = fuuro_iter.next();
-                        if in_parentheses {
-                            bail!("nested opening parenthesis in fuuro");
-                        }
-                        in_parentheses = true;
-                    }
-                    Some(')') => {
-                        _ = fuuro_iter.next();
-                        if !in_parentheses {
-                            bail!("extra closing parenthesis in fuuro");
-                        }
-                        in_parentheses = false;
-                    }
-                    Some(_) => {
-                        let tile_string = format!(
-                            "{}{}",
-                            fuuro_iter.next().unwrap(),
-                            fuuro_iter.next().context("incorrect fuuro")?
-                        );
-                        fuuro.tiles.push(Fuurohai {
-                            tile: parse_tile(&tile_string)?,
-                            sideways: in_parentheses,
-                        });
+        }
+        // ?? tiles are unseen ones picked at random, from a fixed seed so that the board alone still gives the
+        // same log
+        let mut rng = Rng::from_seed(0);
+        let mut counts = visible_counts(&board);
+        for (seat, fuuro) in board.fuuro.iter_mut().enumerate() {
+            fill_unknown(&mut rng, &mut fuuro.tiles, &mut counts, |kind| !fill_rules.avoid[kind])
+                .with_context(|| format!("incorrect fuuro[{}]", board_seat(seat)))?;
+        }
+        for (seat, i) in unknown {
+            board.kawa[seat][i].pai = random_filler(&mut rng, &mut counts, |kind| !fill_rules.avoid[kind])
+                .with_context(|| format!("incorrect kawa[{}]", board_seat(seat)))?;
+        }
+        if players == 3 {
+            ensure!(
+                visible_counts(&board)[tu8!(2m) as usize..=tu8!(8m) as usize]
+                    .iter()
+                    .all(|count| *count == 0),
+                "three player mahjong is played without 2m to 8m"
+            );
+        }
+        check_tile_counts(&board)?;
+        let north = visible_counts(&board)[tu8!(N) as usize] + kita;
+        ensure!(
+            north <= 4,
+            "N is on the board {north} times with the kita, there are only four"
+        );
+        for seat in (0..4).filter(|seat| deposits[*seat]) {
+            ensure!(
+                board.kawa[seat].iter().any(|sutehai| sutehai.riichi),
+                "seat {seat} has a riichi deposit but no riichi discard"
+            );
+            ensure!(board.kyotaku > 0, "kyotaku has fewer sticks than deposits");
+            // the generator takes the stick again when it replays the riichi
+            board.kyotaku -= 1;
+            board.scores[seat] += 1000;
+        }
+        let melds = split_melds(&board.fuuro[0].tiles)?.len();
+        let expected = 14 - 3 * melds;
+        if claim.is_some() {
+            ensure!(
+                board.tehai.len() == expected - 1,
+                "a claim snapshot needs the tehai before the draw, with {melds} calls {} tiles",
+                expected - 1
+            );
+        } else {
+            ensure!(
+                board.tehai.len() == expected || board.tehai.len() == expected - 1,
+                "tehai has {} tiles, with {melds} calls it needs {expected}, or {} before the draw",
+                board.tehai.len(),
+                expected - 1
+            );
+        }
+        if check_dora {
+            check_dora_count(&board)?;
+        }
+        if !self.junme.is_empty() {
+            let junme: usize = self.junme.parse().context("incorrect junme")?;
+            ensure!(junme >= 1, "junme starts at 1");
+            pad_kawa_to_junme(&mut board, junme, &fill_rules)?;
+        }
+        for seat in (0..4).filter(|seat| bots[*seat]) {
+            ensure!(board.fuuro[seat].tiles.is_empty(), "bot seat {seat} cannot have calls");
+            ensure!(
+                !board.kawa[seat].iter().any(|sutehai| sutehai.riichi),
+                "bot seat {seat} cannot declare riichi"
+            );
+            for sutehai in &mut board.kawa[seat] {
+                sutehai.tedashi = false;
+            }
+        }
+        Ok(board)
+    }
+}
+
+// Every tile the focused player can see.
+fn visible_tiles(board: &Board) -> impl Iterator<Item = &Tile> {
+    board
+        .tehai
+        .iter()
+        .chain(&board.dora_indicators)
+        .chain(board.kawa.iter().flatten().map(|sutehai| &sutehai.pai))
+        .chain(
+            board
+                .fuuro
+                .iter()
+                .flat_map(|fuuro| &fuuro.tiles)
+                .map(|fuurohai| &fuurohai.tile),
+        )
+}
+
+// Counts every tile the focused player can see, aka folded into its plain five.
+fn visible_counts(board: &Board) -> [u8; 34] {
+    let mut counts = [0; 34];
+    // ?? tiles not filled in yet
+    for tile in visible_tiles(board).filter(|tile| !tile.is_unknown()) {
+        counts[tile.deaka().as_usize()] += 1;
+    }
+    counts
+}
+
+// A set has four of each tile and one of each red five, whichever of tehai, kawa, fuuro or dora it shows in.
+fn check_tile_counts(board: &Board) -> Result<()> {
+    let counts = visible_counts(board);
+    if let Some(kind) = (0..34).find(|kind| counts[*kind] > 4) {
+        bail!(
+            "{} is on the board {} times, there are only four",
+            Tile::new_unchecked(kind as u8),
+            counts[kind]
+        );
+    }
+    for aka in [tu8!(5mr), tu8!(5pr), tu8!(5sr)] {
+        let tile = Tile::new_unchecked(aka);
+        let count = visible_tiles(board).filter(|visible| **visible == tile).count();
+        ensure!(count <= 1, "{tile} is on the board {count} times, there is only one");
+    }
+    Ok(())
+}
+
+// honors first, then from the terminals inwards, like typical early discards
+const FILLER_PREFERENCE: [usize; 34] = [
+    27, 28, 29, 30, 31, 32, 33, 0, 8, 9, 17, 18, 26, 1, 7, 10, 16, 19, 25, 2, 6, 11, 15, 20, 24, 3, 5, 12, 14, 21, 23, 4, 13, 22,
+];
+
+fn filler_tile(counts: &mut [u8; 34]) -> Result<Tile> {
+    filler_tile_where(counts, |_| true)
+}
+
+fn filler_tile_where(counts: &mut [u8; 34], allowed: impl Fn(usize) -> bool) -> Result<Tile> {
+    let mut best: Option<(usize, u8)> = None;
+    for kind in FILLER_PREFERENCE {
+        let unseen = 4u8.saturating_sub(counts[kind]);
+        if unseen > 0 && allowed(kind) && best.is_none_or(|(_, best_unseen)| unseen > best_unseen) {
+            best = Some((kind, unseen));
+        }
+    }
+    let (kind, _) = best.context("no unseen tiles left for padding")?;
+    counts[kind] += 1;
+    Ok(Tile::new_unchecked(kind as u8))
+}
+
+// An unseen tile for a ?? discard, at random with the early discards FILLER_PREFERENCE ranks first more likely.
+fn random_filler(rng: &mut Rng, counts: &mut [u8; 34], allowed: impl Fn(usize) -> bool) -> Result<Tile> {
+    let wall: Vec<usize> = FILLER_PREFERENCE
+        .iter()
+        .enumerate()
+        .filter(|(_, kind)| allowed(**kind))
+        .flat_map(|(rank, kind)| vec![*kind; 4u8.saturating_sub(counts[*kind]) as usize * (34 - rank)])
+        .collect();
+    ensure!(!wall.is_empty(), "no unseen tiles left for ??");
+    let kind = wall[rng.below(wall.len())];
+    counts[kind] += 1;
+    Ok(Tile::new_unchecked(kind as u8))
+}
+
+// Constraints on the discards padded into the kawa, so they do not contradict the scenario.
+struct FillRules {
+    // kinds no padded discard may be, e.g. the player's winning tiles
+    avoid: [bool; 34],
+    // relative seats whose padded discards must be genbutsu against the player
+    genbutsu: [bool; 4],
+    // the relative seat of a three player board nobody sits at, whose kawa board_events fills
+    empty_seat: Option<usize>,
+}
+
+// At junme `n` the focused player has drawn n times and not yet discarded, seats between the dealer and
+// them have discarded n times and the rest n - 1 times. Calls can shift this by one per meld; an ankan is
+// made on the seat's own turn and shifts nothing.
+fn pad_kawa_to_junme(board: &mut Board, junme: usize, rules: &FillRules) -> Result<()> {
+    let jikaze = (board.jikaze.as_u8() - tu8!(E)) as usize;
+    let mut counts = visible_counts(board);
+    for seat in (0..4).filter(|seat| Some(*seat) != rules.empty_seat) {
+        let expected = if seat != 0 && (seat + jikaze) % 4 < jikaze {
+            junme
+        } else {
+            junme - 1
+        };
+        let calls = split_melds(&board.fuuro[seat].tiles)?
+            .iter()
+            .filter(|meld| meld.kind != MeldKind::Ankan)
+            .count();
+        ensure!(
+            board.kawa[seat].len() <= expected + calls,
+            "junme {junme} allows at most {} discards in kawa[{seat}] but it has {}",
+            expected + calls,
+            board.kawa[seat].len()
+        );
+        // the player's own kawa is padded first, so its fillers count as genbutsu too
+        let mut genbutsu = [false; 34];
+        for sutehai in &board.kawa[0] {
+            genbutsu[sutehai.pai.deaka().as_usize()] = true;
+        }
+        let allowed = |kind: usize| !rules.avoid[kind] && (!rules.genbutsu[seat] || genbutsu[kind]);
+        let mut padded = Vec::with_capacity(expected);
+        for _ in board.kawa[seat].len()..expected {
+            let pai = filler_tile_where(&mut counts, allowed).with_context(|| format!("kawa[{seat}]"))?;
+            padded.push(Sutehai {
+                pai,
+                tedashi: true,
+                riichi: false,
+            });
+        }
+        padded.append(&mut board.kawa[seat]);
+        board.kawa[seat] = padded;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct GenerateOptions {
+    // add every seat's concealed hand at the end of the kyoku to the result, the tenhou log ("final_hands")
+    // and the mjlog
+    pub reveal_hands: bool,
+    // for a riichi tsumo, either the exact ura indicators or how many ura dora the winner should hit
+    pub ura_indicators: String,
+    pub ura_hits: Option<u8>,
+    // tenhou log header; names are relative to the focused player like RawBoard.scores, and also go into the
+    // mjai StartGame. dan (e.g. "七段"), rate and sx ("M", "F" or "C" for a computer) are the tenhou
+    // header's other seat fields.
+    pub title: Vec<String>,
+    pub names: Vec<String>,
+    pub dan: Vec<String>,
+    pub rate: Vec<f64>,
+    pub sx: Vec<String>,
+    // fail instead of returning a log the tenhou viewer cannot display
+    pub strict: bool,
+    // what to write for kan dora and ura indicators nobody gave: "omit" (the default), "fixed" to repeat
+    // `placeholder_tile`, or "random" for unseen tiles picked from `placeholder_seed`
+    pub indicator_placeholder: String,
+    pub placeholder_tile: String,
+    pub placeholder_seed: Option<u64>,
+    pub rules: RuleConfig,
+    // also export the log as tenhou mjlog xml
+    pub mjlog: bool,
+    // also return a tenhou.net/6 link that opens the log from the focused player's seat
+    pub viewer_url: bool,
+    // deals the tiles the generator makes up for the opponents' hidden hands from this seed, so the same board
+    // and seed always give the same logs; also the placeholder seed when that is unset. A board that gives the
+    // draws cannot be seeded.
+    pub seed: Option<u64>,
+    // also return mjai_log as newline-delimited json, as Mortal and other mjai bots read it
+    pub mjai_jsonl: bool,
+    // also return the log the way Mortal's reviewer reads it, see mortal_jsonl
+    pub mortal_jsonl: bool,
+    // how the kyoku ends, played on from the board; the generator's own ending when unset
+    pub outcome: Option<Outcome>,
+    // camelCase keys in the serialized result, mjai events included, e.g. tenhouLog and uraMarkers; set_camel_case_js
+    // turns them on for every JavaScript binding
+    pub camel_case: bool,
+    // "en" or "ja" for the error messages of the JavaScript bindings, in place of set_locale_js
+    pub locale: String,
+    // return mjai_log from the JavaScript bindings as mjai_log_json, one json string, which is much cheaper
+    // to hand over than the events as objects
+    pub mjai_log_string: bool,
+    // also return the trace of how generate made the log, see GenerateResult.trace
+    pub trace: bool,
+    // also return the 136 tiles of the wall the log was played from, see GenerateResult.wall
+    pub wall: bool,
+}
+
+impl GenerateOptions {
+    fn ura(&self) -> Result<Option<UraSpec>> {
+        match (self.ura_indicators.is_empty(), self.ura_hits) {
+            (true, None) => Ok(None),
+            (false, None) => Ok(Some(UraSpec::Indicators(
+                parse_tiles(&self.ura_indicators).context("incorrect ura indicators")?,
+            ))),
+            (true, Some(hits)) => Ok(Some(UraSpec::Hits(hits))),
+            (false, Some(_)) => bail!("ura_indicators and ura_hits cannot both be set"),
+        }
+    }
+
+    fn placeholder(&self) -> Result<Placeholder> {
+        match self.indicator_placeholder.as_str() {
+            "" | "omit" => Ok(Placeholder::Omit),
+            "fixed" => Ok(Placeholder::Fixed(
+                parse_tile(&self.placeholder_tile).context("incorrect placeholder tile")?,
+            )),
+            "random" => Ok(Placeholder::Random(Rng::new(self.placeholder_seed.or(self.seed))?)),
+            other => bail!("unknown indicator placeholder {other:?}, expected omit, fixed or random"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct GenerateResult {
+    pub tenhou_log: String,
+    pub human_tenhou_log: String,
+    #[serde(skip_serializing_if = "Vec::is_empty", serialize_with = "nuki::serialize_events")]
+    pub mjai_log: Vec<Event>,
+    // the mjai_log_string option's json array of the events, in place of mjai_log
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mjai_log_json: Option<String>,
+    pub player_id: i32,
+    // same game, same id; see tenhou::log_id
+    pub log_id: String,
+    pub timeline: Vec<KeyMoment>,
+    // by absolute seat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_hands: Option<Vec<String>>,
+    // how the kyoku ended, when it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<KyokuResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mjlog_xml: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub viewer_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mjai_jsonl: Option<String>,
+    // one JSONL file per kyoku
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mortal_jsonl: Option<Vec<String>>,
+    // with the trace option, the choices generate made around the generator in order, then a narration of the
+    // finished log read back event by event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Vec<TraceStep>>,
+    // the first kyoku's dealt wall, haipai, draws, replacement draws, dora then ura indicators, see wall::wall
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wall: Option<Vec<String>>,
+}
+
+impl GenerateResult {
+    pub fn from_events(events: Vec<Event>, options: &GenerateOptions) -> Result<Self> {
+        options.rules.check_rotation(&events).context("parse error")?;
+        let sanma = sanma::is_sanma(&events);
+        let converted = nuki::without_kita(&events);
+        let raw_tenhou_log = match sanma {
+            true => mjai_to_tenhou(&sanma::seated(&converted)),
+            false => mjai_to_tenhou(&converted),
+        }
+        .context("tenhou conversion error")?;
+        let player_id = player_id_of(&events)?;
+        let mut tenhou_value = serde_json::to_value(&raw_tenhou_log).context("serialization error")?;
+        if sanma {
+            tenhou::empty_seat(&mut tenhou_value);
+        }
+        tenhou::restore_meld_aka(&mut tenhou_value, &converted);
+        nuki::set_tenhou_kita(&mut tenhou_value, &events);
+        if !options.title.is_empty() {
+            tenhou::set_title(&mut tenhou_value, &options.title);
+        }
+        if !options.names.is_empty() {
+            tenhou::set_names(&mut tenhou_value, &options.names, player_id);
+        }
+        for (key, values, default) in [
+            ("dan", options.dan.iter().map(|dan| json!(dan)).collect::<Vec<_>>(), json!("")),
+            ("rate", options.rate.iter().map(|rate| json!(rate)).collect(), json!(1500.0)),
+            ("sx", options.sx.iter().map(|sx| json!(sx)).collect(), json!("C")),
+        ] {
+            if !values.is_empty() {
+                tenhou::set_seats(&mut tenhou_value, key, &values, player_id, default);
+            }
+        }
+        if let Some(rule) = options.rules.tenhou_rule(sanma).context("parse error")? {
+            tenhou_value["rule"] = rule;
+        }
+        if let Some(lobby) = options.rules.lobby {
+            tenhou_value["lobby"] = json!(lobby);
+        }
+        if options.rules.honba_each().context("parse error")? {
+            let honba_value = options.rules.honba_value().context("parse error")?;
+            tenhou::pay_honba_each(&mut tenhou_value, &events, honba_value);
+        }
+        let abortive = options.outcome.as_ref().and_then(Outcome::abortive);
+        if let Some(abortive) = abortive {
+            tenhou::set_abortive(&mut tenhou_value, abortive.label());
+        }
+        if options.reveal_hands {
+            tenhou::set_final_hands(&mut tenhou_value, &events);
+        }
+        if options.strict {
+            tenhou::validate(&tenhou_value).context("validation error")?;
+        }
+        let tenhou_log_string = to_string(&tenhou_value).context("serialization error")?;
+        let result = kyoku_result(&tenhou_value, &events);
+        let timeline = key_moments(&events);
+        let mjlog_xml = if options.mjlog {
+            let flags = options.rules.mjlog_type(sanma).context("parse error")?;
+            let abortive = abortive.map(Abortive::mjlog_type);
+            Some(mjai_to_mjlog(&events, flags, options.reveal_hands, abortive).context("mjlog conversion error")?)
+        } else {
+            None
+        };
+
+        let viewer_url = options.viewer_url.then(|| url::viewer_url(&tenhou_log_string, player_id));
+        let mjai_jsonl = if options.mjai_jsonl {
+            Some(mjai_jsonl(&events).context("serialization error")?)
+        } else {
+            None
+        };
+        let mortal_jsonl = if options.mortal_jsonl {
+            Some(mortal_jsonl(&events, player_id).context("serialization error")?)
+        } else {
+            None
+        };
+        let wall = if options.wall {
+            let wall = wall::wall(&events).context("wall reconstruction error")?;
+            Some(wall.iter().map(|tile| tiles_vec_to_string(&[*tile])).collect())
+        } else {
+            None
+        };
+
+        Ok(GenerateResult {
+            tenhou_log: tenhou_log_string,
+            mjai_log: events,
+            mjai_log_json: None,
+            player_id: player_id as i32,
+            log_id: tenhou::log_id(&tenhou_value),
+            timeline,
+            human_tenhou_log: tenhou::to_string_pretty(&tenhou_value),
+            final_hands: None,
+            result,
+            mjlog_xml,
+            viewer_url,
+            mjai_jsonl,
+            mortal_jsonl,
+            trace: None,
+            wall,
+        })
+    }
+}
+
+fn player_id_of(events: &[Event]) -> Result<u8> {
+    match events.first() {
+        Some(Event::StartGame { id: Some(id), .. }) => Ok(*id),
+        _ => bail!("mjai logs do not start with StartGame"),
+    }
+}
+
+// TryInto<Board> with the error located in the board's notation, see notation::BoardError.
+fn parse_board(raw_board: RawBoard, check_dora: bool) -> Result<Board> {
+    let explained = raw_board.clone();
+    raw_board
+        .into_board(check_dora)
+        .map_err(|e| Error::new(BoardError::locate(&explained, &e)))
+}
+
+fn board_events(raw_board: RawBoard) -> Result<Vec<Event>> {
+    let located = |e: Error| Error::new(BoardError::locate(&raw_board, &e)).context("parse error");
+    let chiicha = raw_board.chiicha().map_err(located)?;
+    let claim = raw_board.claim().map_err(located)?;
+    let bots = raw_board.bots().map_err(located)?;
+    let uradora = raw_board.uradora().map_err(located)?;
+    let draws = raw_board.draws().map_err(located)?;
+    let (seats, empty) = raw_board.seat_map().map_err(located)?;
+    let nuki = raw_board.nuki().map_err(located)?;
+    let mut board = parse_board(raw_board, true).context("parse error")?;
+    // a hand a tile short is waiting for its draw, which ends the log after the kamicha's discard like a claim
+    let melds = split_melds(&board.fuuro[0].tiles).context("parse error")?.len();
+    let short = board.tehai.len() + 3 * melds == 13;
+    let kamicha = if empty.is_some() { seats[2] } else { 3 };
+    let claim = claim.or(short.then_some(kamicha));
+    if let Some(claim) = claim {
+        trace::note(|| match short {
+            true => format!("the hand is a tile short, so it draws one more and the log ends after the discard of seat {claim}"),
+            false => format!(
+                "the log ends after the last discard of seat {claim} for the player to call it; a tile was added to the \
+                 hand for the generator and taken back"
+            ),
+        });
+        prepare_claim(&mut board, claim).context("log generation error")?;
+    }
+    if let Some(empty) = empty {
+        trace::note(|| format!("seat {empty} is left empty, it draws and throws out 2m to 8m the three players never see"));
+        sanma::fill_empty_seat(&mut board, empty).context("log generation error")?;
+    }
+    let riichi: [Option<usize>; 4] = from_fn(|seat| {
+        let kawa = &board.kawa[seat];
+        kawa.iter().rposition(|sutehai| sutehai.riichi).map(|i| kawa.len() - 1 - i)
+    });
+    let mut events = generate_mjai_logs(board).context("log generation error")?;
+    let player_id = player_id_of(&events)?;
+    add_missing_riichi(&mut events, from_fn(|seat| riichi[(seat + 4 - player_id as usize) % 4]));
+    if let Some(Event::StartGame { names, .. }) = events.first_mut() {
+        for seat in (0..4).filter(|seat| bots[*seat]) {
+            names[(player_id as usize + seat) % 4] = "bot".to_owned();
+        }
+    }
+    if let Some(claim) = claim {
+        truncate_at_claim(&mut events, (player_id + claim as u8) % 4).context("log generation error")?;
+    }
+    if draws.iter().any(|draws| !draws.is_empty()) {
+        trace::note(|| "the draws are the board's, traded with tiles of the haipai so that the kawa stays the same".to_owned());
+        let draws = from_fn(|seat| draws[(seat + 4 - player_id as usize) % 4].clone());
+        set_draws(&mut events, &draws, player_id).context("log generation error")?;
+    }
+    if let Some(empty) = empty {
+        // tenhou's seat 0 is the first East of a three player game
+        ensure!(
+            chiicha.is_none_or(|chiicha| chiicha == 0),
+            "parse error: the first East of a three player game is seat 0"
+        );
+        sanma::leave_seat_empty(&mut events, player_id, empty);
+        let player_id = player_id_of(&events)?;
+        for (seat, count) in nuki.iter().enumerate().filter(|(_, count)| **count > 0) {
+            trace::note(|| format!("seat {seat} sets {count} kita aside on its first turns"));
+            nuki::add_kita(&mut events, (player_id + seat as u8) % 3, *count).context("log generation error")?;
+        }
+        // the four seat generator dealt 2m to 8m and the kita's N into hidden hands too, which are dealt again
+        // from a fixed seed so that the board alone still gives the same log
+        redeal_sanma(&mut Rng::from_seed(0), &mut events, player_id).context("log generation error")?;
+    } else if let Some(chiicha) = chiicha {
+        trace::note(|| format!("seat {chiicha} is the first dealer of the game"));
+        set_chiicha(&mut events, chiicha);
+    }
+    if let Some(uradora) = uradora {
+        trace::note(|| "the uradora indicators are the board's".to_owned());
+        set_ura(&mut events, &uradora).context("log generation error")?;
+    }
+    Ok(events)
+}
+
+fn finish_events(mut events: Vec<Event>, options: &GenerateOptions) -> Result<GenerateResult> {
+    let player_id = player_id_of(&events)?;
+    if let Some(Event::StartGame { names, .. }) = events.first_mut() {
+        for (seat, name) in options.names.iter().enumerate().take(4).filter(|(_, name)| !name.is_empty()) {
+            names[(player_id as usize + seat) % 4] = name.clone();
+        }
+    }
+    let ura = options.ura().context("parse error")?;
+    if let Some(ura) = ura {
+        set_ura(&mut events, &ura).context("log generation error")?;
+    }
+    let honba_value = options.rules.honba_value().context("parse error")?;
+    let each = options.rules.honba_each().context("parse error")?;
+    apply_honba(&mut events, honba_value, each);
+    let placeholder = options.placeholder().context("parse error")?;
+    fill_placeholders(&mut events, placeholder).context("log generation error")?;
+    options.rules.apply(&mut events).context("log generation error")?;
+    let mut result = GenerateResult::from_events(events, options)?;
+    if let Some(kyoku_result) = &result.result {
+        options
+            .rules
+            .check_kuitan(kyoku_result, &result.mjai_log)
+            .context("log generation error")?;
+    }
+    if options.reveal_hands {
+        result.final_hands = Some(
+            final_hands(&result.mjai_log)
+                .iter()
+                .map(|hand| tiles_vec_to_string(hand))
+                .collect(),
+        );
+    }
+    Ok(result)
+}
+
+pub fn generate(raw_board: RawBoard, options: &GenerateOptions) -> Result<GenerateResult> {
+    Ok(generate_decision(raw_board, options)?.0)
+}
+
+// The generated log along with how many of its draws and discards lead up to the board's decision.
+fn generate_decision(raw_board: RawBoard, options: &GenerateOptions) -> Result<(GenerateResult, usize)> {
+    if !options.trace {
+        return generate_log(raw_board, options);
+    }
+    trace::start();
+    let result = generate_log(raw_board, options);
+    let mut steps = trace::finish();
+    let (mut result, decision) = result?;
+    steps.extend(trace::trace_events(&result.mjai_log, result.player_id as u8));
+    result.trace = Some(steps);
+    Ok((result, decision))
+}
+
+fn generate_log(raw_board: RawBoard, options: &GenerateOptions) -> Result<(GenerateResult, usize)> {
+    ensure!(
+        raw_board.uradora.is_empty() || (options.ura_indicators.is_empty() && options.ura_hits.is_none()),
+        "parse error: the board's uradora and the ura options cannot both be set"
+    );
+    let ending = options
+        .outcome
+        .as_ref()
+        .map(Outcome::ending)
+        .transpose()
+        .context("parse error")?;
+    if let Some(Ending::Win { winners, .. }) = &ending {
+        ensure!(
+            winners.len() == 1 || options.rules.ron != "atamahane",
+            "parse error: with atamahane only the first in turn order rons, the outcome has {} winners",
+            winners.len()
+        );
+    }
+    ensure!(
+        ending.is_none() || raw_board.players != "3",
+        "parse error: a three player board cannot be played on to an outcome, its scoring is not four player's"
+    );
+    if !options.rules.aka().context("parse error")? {
+        let ura = match options.ura().context("parse error")? {
+            Some(UraSpec::Indicators(indicators)) => indicators,
+            _ => vec![],
+        };
+        let outcome = match &ending {
+            Some(Ending::Win { tile, .. }) => vec![*tile],
+            Some(Ending::Ryukyoku { hands }) => hands.iter().flatten().flatten().copied().collect(),
+            _ => vec![],
+        };
+        let aka = raw_board.red_five().context("parse error")?;
+        if let Some(aka) = aka.or(ura.into_iter().chain(outcome).find(|tile| tile.is_aka())) {
+            bail!("parse error: the board has {aka}, but the rules play without red fives");
+        }
+    }
+    // a seed would redeal the draws the board gives
+    ensure!(
+        options.seed.is_none() || raw_board.draws.iter().all(|draws| draws.is_empty()),
+        "parse error: the seed cannot redeal the opponents when the board gives the draws, set placeholder_seed instead"
+    );
+    let kuitan = options.rules.kuitan.unwrap_or(true);
+    let decision;
+    let events = match &ending {
+        Some(Ending::Win { winners, loser, tile }) if winners.contains(&0) => {
+            let win = WinOptions {
+                tile: tiles_vec_to_string(&[*tile]),
+                ron: (*loser != 0).then_some(*loser),
+            };
+            let mut events = win_events(raw_board, &win)?;
+            decision = quiz::moves(&events);
+            let others: Vec<u8> = winners.iter().copied().filter(|winner| *winner != 0).collect();
+            trace::note(|| format!("the player wins on {}, the last tile of the log", win.tile));
+            let player_id = player_id_of(&events)?;
+            let mut rng = Rng::new(options.seed).context("log generation error")?;
+            if options.seed.is_some() {
+                trace::note(|| "the seed redeals the tiles of the opponents that the board does not show".to_owned());
+                redeal_hidden(&mut rng, &mut events, player_id).context("log generation error")?;
+            }
+            if !others.is_empty() {
+                trace::note(|| format!("seats {others:?} are dealt waits on the same tile and ron it too"));
+                add_rons(&mut rng, &mut events, player_id, &others, *loser, kuitan).context("log generation error")?;
+            }
+            events
+        }
+        _ => {
+            let mut events = board_events(raw_board)?;
+            decision = quiz::moves(&events);
+            if options.seed.is_some() || ending.is_some() {
+                let player_id = player_id_of(&events)?;
+                let mut rng = Rng::new(options.seed).context("log generation error")?;
+                if options.seed.is_some() {
+                    trace::note(|| "the seed redeals the tiles of the opponents that the board does not show".to_owned());
+                    redeal_hidden(&mut rng, &mut events, player_id).context("log generation error")?;
+                }
+                if let Some(ending) = &ending {
+                    trace::note(|| "the kyoku is played on from the board to the outcome option".to_owned());
+                    play_outcome(&mut rng, &mut events, player_id, ending, kuitan).context("log generation error")?;
+                }
+            }
+            events
+        }
+    };
+    let result = finish_events(events, options)?;
+    if let Some(Ending::Win { winners, tile, .. }) = ending {
+        let agari = result.result.as_ref().map_or(0, |result| result.agari.len());
+        ensure!(
+            agari == winners.len(),
+            "log generation error: only {agari} of seats {winners:?} win on {}, a hand may have no yaku",
+            tiles_vec_to_string(&[tile])
+        );
+    }
+    Ok((result, decision))
+}
+
+// Json strings on both ends. An empty `options_json` means the default options.
+pub fn generate_from_json(board_json: &str, options_json: &str) -> Result<String> {
+    let options: GenerateOptions = if options_json.is_empty() {
+        GenerateOptions::default()
+    } else {
+        serde_json::from_str(options_json).context("options deserialize error")?
+    };
+    let raw_board: RawBoard = serde_json::from_str(board_json).context("deserialize error")?;
+    let result = generate(raw_board, &options)?;
+    if options.camel_case {
+        let mut value = serde_json::to_value(&result).context("serialize result error")?;
+        camel_case_keys(&mut value);
+        return to_string(&value).context("serialize result error");
+    }
+
+    to_string(&result).context("serialize result error")
+}
+
+// Renames every object key of `value` from snake_case to camelCase, the way JavaScript code names fields.
+pub fn camel_case_keys(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            let fields = std::mem::take(object);
+            for (key, mut field) in fields {
+                camel_case_keys(&mut field);
+                let mut words = key.split('_');
+                let mut camel = words.next().unwrap_or_default().to_owned();
+                for word in words {
+                    let mut chars = word.chars();
+                    if let Some(first) = chars.next() {
+                        camel.extend(first.to_uppercase());
+                        camel.push_str(chars.as_str());
                     }
-                    None => break,
                 }
+                object.insert(camel, field);
             }
         }
-        Ok(board)
+        Value::Array(items) => items.iter_mut().for_each(camel_case_keys),
+        _ => {}
+    }
+}
+
+#[derive(Serialize)]
+pub struct QuizResult {
+    // the same for both logs, the answer's log_id
+    pub correlation_id: String,
+    // ends at the decision, without the result or the other seats' tiles
+    pub quiz: GenerateResult,
+    // the whole log with `options` applied
+    pub answer: GenerateResult,
+}
+
+// Both halves of a "guess then reveal" post from a single generation, so they always show the same game.
+pub fn generate_quiz(raw_board: RawBoard, options: &GenerateOptions) -> Result<QuizResult> {
+    let (answer, decision) = generate_decision(raw_board, options)?;
+    let quiz_events = quiz::truncate_at_decision(answer.mjai_log.clone(), decision, answer.player_id as u8);
+    let quiz = GenerateResult::from_events(quiz_events, options)?;
+    Ok(QuizResult {
+        correlation_id: answer.log_id.clone(),
+        quiz,
+        answer,
+    })
+}
+
+#[derive(Serialize)]
+pub struct ScenarioResult {
+    pub board: RawBoard,
+    pub log: GenerateResult,
+}
+
+// Generates a random board and log meeting per-seat shanten targets, e.g. the player 1-shanten with two
+// opponents tenpai.
+pub fn generate_scenario(scenario: &ScenarioOptions, options: &GenerateOptions) -> Result<ScenarioResult> {
+    let mut rng = Rng::new(scenario.seed).context("scenario error")?;
+
+    let board = random_board(&mut rng, scenario).context("scenario error")?;
+    let mut events = board_events(board.clone())?;
+    let player_id = player_id_of(&events)?;
+    if scenario.seed.is_some() {
+        redeal_hidden(&mut rng, &mut events, player_id).context("scenario error")?;
     }
+    for (seat, target) in scenario.shanten.iter().enumerate().skip(1).take(3) {
+        if let Some(target) = target {
+            set_shanten(&mut rng, &mut events, (player_id + seat as u8) % 4, *target).context("scenario error")?;
+        }
+    }
+    Ok(ScenarioResult {
+        board,
+        log: finish_events(events, options)?,
+    })
 }
 
 #[derive(Serialize)]
-struct GenerateResult {
-    tenhou_log: String,
-    human_tenhou_log: String,
-    mjai_log: Vec<Event>,
-    player_id: i32,
-}
-
-#[wasm_bindgen]
-pub fn generate_logs_js(val: JsValue) -> Result<JsValue, JsValue> {
-    let raw_board: RawBoard = from_value(val).map_err(|e| JsValue::from_str(&format!("deserialize error: {e}")))?;
-    let board: Board = raw_board
-        .try_into()
-        .map_err(|e| JsValue::from_str(&format!("parse error: {e}")))?;
-    let events = generate_mjai_logs(board).map_err(|e| JsValue::from_str(&format!("log generation error: {e}")))?;
-    let raw_tenhou_log = mjai_to_tenhou(&events).map_err(|e| JsValue::from_str(&format!("tenhou conversion error: {e}")))?;
-    let tenhou_log_string = to_string(&raw_tenhou_log).map_err(|e| JsValue::from_str(&format!("serialization error: {e}")))?;
-    let pretty_tenhou_log = raw_tenhou_log
-        .to_string_pretty()
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
-    let player_id = match events.first() {
-        Some(Event::StartGame { id: Some(id), .. }) => *id,
-        _ => return Err(JsValue::from_str("mjai logs do not start with StartGame")),
+pub struct HighlightResult {
+    pub log: GenerateResult,
+    pub start_index: usize,
+}
+
+// The `kyoku`-th kyoku (0-based) of a whole game as a log of its own, between the game's StartGame and an EndGame.
+pub fn single_kyoku(events: &[Event], kyoku: usize) -> Result<Vec<Event>> {
+    let range = kyoku_range(events, kyoku).with_context(|| format!("no kyoku at index {kyoku}"))?;
+    let start_game = match events.first() {
+        Some(event @ Event::StartGame { .. }) => event.clone(),
+        _ => bail!("mjai logs do not start with StartGame"),
     };
+    let mut log = vec![start_game];
+    log.extend_from_slice(&events[range]);
+    log.push(Event::EndGame);
+    Ok(log)
+}
+
+// A tenhou log cannot start mid-kyoku, so the clip keeps the real haipai and draws of the whole
+// kyoku and marks the highlight with `start_index`, the mjai event of the player's `turn`-th draw.
+pub fn generate_highlight(events: &[Event], jikaze: &str, kyoku: usize, turn: usize) -> Result<HighlightResult> {
+    let jikaze = parse_jikaze(jikaze)?;
+    let range = kyoku_range(events, kyoku).with_context(|| format!("no kyoku at index {kyoku}"))?;
+    let kyoku_events = &events[range];
+    let oya = kyoku_oya(kyoku_events).context("kyoku does not start with StartKyoku")?;
+    let player_id = sanma::player_seat(kyoku_events, oya, jikaze)?;
+
+    let start = turn
+        .checked_sub(1)
+        .and_then(|n| {
+            kyoku_events
+                .iter()
+                .enumerate()
+                .filter(|(_, event)| matches!(event, Event::Tsumo { actor, .. } if *actor == player_id))
+                .nth(n)
+        })
+        .map(|(i, _)| i)
+        .with_context(|| format!("player has no turn {turn} in kyoku {kyoku}"))?;
+
+    let mut clip = single_kyoku(events, kyoku)?;
+    if let Some(Event::StartGame { id, .. }) = clip.first_mut() {
+        *id = Some(player_id);
+    }
+
+    Ok(HighlightResult {
+        log: GenerateResult::from_events(clip, &GenerateOptions::default())?,
+        start_index: start + 1,
+    })
+}
+
+// Converted once per distinct log while the conversion cache is on, see cache::set_capacity.
+pub fn tenhou_log_events(log: &serde_json::Value) -> Result<Vec<Event>> {
+    // hashing the whole log is only worth it when there is a cache to look in
+    let hash = cache::enabled().then(|| cache::fnv1a(log.to_string().as_bytes()));
+    if let Some(events) = hash.and_then(cache::get) {
+        return Ok(events);
+    }
+    let raw_tenhou_log = RawLog::deserialize(log).context("deserialize error")?;
+    let tenhou_log: Log = raw_tenhou_log.try_into().context("deserialize error")?;
+    ensure!(!tenhou_log.kyokus.is_empty(), "no kyokus");
+    let events = tenhou_to_mjai(&tenhou_log).context("parse error")?;
+    if let Some(hash) = hash {
+        cache::insert(hash, &events);
+    }
+    Ok(events)
+}
+
+pub fn mjlog_events(xml: &str) -> Result<Vec<Event>> {
+    let hash = cache::enabled().then(|| cache::fnv1a(xml.as_bytes()));
+    if let Some(events) = hash.and_then(cache::get) {
+        return Ok(events);
+    }
+    let events = mjlog_to_mjai(xml).context("parse error")?;
+    if let Some(hash) = hash {
+        cache::insert(hash, &events);
+    }
+    Ok(events)
+}
+
+// The decoded actions of a MahjongSoul record, see majsoul.rs for the shape.
+pub fn majsoul_events(record: &serde_json::Value) -> Result<Vec<Event>> {
+    majsoul_to_mjai(record).context("parse error")
+}
+
+// A Riichi City replay, see riichi_city.rs for the shape.
+pub fn riichi_city_events(replay: &serde_json::Value) -> Result<Vec<Event>> {
+    riichi_city_to_mjai(replay).context("parse error")
+}
 
-    let result = GenerateResult {
-        tenhou_log: tenhou_log_string,
-        mjai_log: events,
-        player_id: player_id as i32,
-        human_tenhou_log: pretty_tenhou_log,
+// The whole game of a Mortal review, see mortal.rs for the shape.
+pub fn mortal_review_events(review: &serde_json::Value) -> Result<Vec<Event>> {
+    Ok(review_events(review).context("parse error")?.0)
+}
+
+// The board at the `entry`-th reviewed decision of kyoku `kyoku`, both 0-based, as the reviewed player saw
+// it. The entry takes the place of the kyoku, event_index and turn of `options`.
+pub fn extract_review_board(
+    review: &serde_json::Value,
+    kyoku: usize,
+    entry: usize,
+    options: &ExtractOptions,
+) -> Result<RawBoard> {
+    let (events, player_id) = review_events(review).context("parse error")?;
+    let range = kyoku_range(&events, kyoku).with_context(|| format!("no kyoku at index {kyoku}"))?;
+    let oya = kyoku_oya(&events[range.clone()]).context("no kyokus")?;
+    let event_index = entry_event(review, &events[range], player_id, kyoku, entry).context("parse error")?;
+    let jikaze = ["E", "S", "W", "N"][((4 + player_id - oya) % 4) as usize];
+    let options = ExtractOptions {
+        kyoku,
+        event_index: Some(event_index),
+        turn: None,
+        ..options.clone()
     };
+    extract_board(events, jikaze, &options)
+}
+
+// One event per line, each line ending in a newline, with the keys in mjai's order ("type" first).
+pub fn mjai_jsonl(events: &[Event]) -> Result<String> {
+    let mut jsonl = String::new();
+    for event in nuki::mjai_values(events)? {
+        jsonl.push_str(&to_string(&event)?);
+        jsonl.push('\n');
+    }
+    Ok(jsonl)
+}
+
+// `events` from `player_id`'s seat as Mortal's reviewer takes them, one JSONL file per kyoku: each opens with
+// start_game carrying the player's id and hides what the player cannot see as "?".
+pub fn mortal_jsonl(events: &[Event], player_id: u8) -> Result<Vec<String>> {
+    review_input(events, player_id).iter().map(|game| mjai_jsonl(game)).collect()
+}
+
+// An mjai log as JSONL, one event per line, or as a json array of events.
+pub fn mjai_events(log: &str) -> Result<Vec<Event>> {
+    if log.trim_start().starts_with('[') {
+        return serde_json::from_str(log).context("deserialize error");
+    }
+    log.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| serde_json::from_str(line).with_context(|| format!("deserialize error on line {}", number + 1)))
+        .collect()
+}
 
-    serde_wasm_bindgen::to_value(&result).map_err(|e| JsValue::from_str(&format!("serialize result error: {e}")))
+fn parse_jikaze(jikaze: &str) -> Result<Tile> {
+    Tile::from_str(jikaze).context("invalid jikaze")
 }
 
 pub fn tiles_vec_to_string(tiles: &[Tile]) -> String {
     let mut tiles_34 = [0; 34];
     let mut aka = [false; 3];
     for tile in tiles {
-        tiles_34[tile.as_usize()] += 1;
+        tiles_34[tile.deaka().as_usize()] += 1;
         match tile.as_u8() {
             tu8!(5mr) => aka[0] = true,
             tu8!(5pr) => aka[1] = true,
@@ -178,98 +1488,297 @@ pub fn tiles_vec_to_string(tiles: &[Tile]) -> String {
     tiles_to_string(&tiles_34, aka).replace(" ", "")
 }
 
-#[wasm_bindgen]
-pub fn generate_board_from_tenhou_js(val: JsValue, jikaze: JsValue) -> Result<JsValue, JsValue> {
-    let raw_tenhou_log: RawLog = from_value(val).map_err(|e| JsValue::from_str(&format!("deserialize error: {e}")))?;
-    let tenhou_log: Log = raw_tenhou_log
-        .try_into()
-        .map_err(|e| JsValue::from_str(&format!("deserialize error: {e}")))?;
-    if tenhou_log.kyokus.is_empty() {
-        return Err(JsValue::from_str("no kyokus"));
-    }
-    let events = tenhou_to_mjai(&tenhou_log).map_err(|e| JsValue::from_str(&format!("parse error: {e}")))?;
-
-    let jikaze_str = jikaze.as_string().ok_or_else(|| JsValue::from_str("invalid jikaze"))?;
-    let jikaze = Tile::from_str(&jikaze_str).map_err(|e| JsValue::from_str(&format!("invalid jikaze: {e}")))?;
-
-    let kyoku = tenhou_log.kyokus[0].meta.kyoku_num;
-    let oya = kyoku % 4;
-    let player_id = (4 + oya + jikaze.as_u8() - tu8!(E)) % 4;
-    // TODO: Do not actually use state to process, use our own
-    let mut state = PlayerState::new(player_id as u8);
-
-    let mut visible_kawa: [Vec<(Tile, bool, bool)>; 4] = from_fn(|_| vec![]);
-    let mut fuuro: [Vec<Vec<(Tile, bool)>>; 4] = from_fn(|_| vec![]);
-    for event in events {
-        state
-            .update(&event)
-            .map_err(|e| JsValue::from_str(&format!("invalid event: {e}")))?;
-        match event {
-            Event::Dahai { actor, pai, tsumogiri } => {
-                visible_kawa[state.rel(actor)].push((pai, tsumogiri, state.riichi_declared[state.rel(actor)]));
-            }
-            Event::Chi {
-                actor,
-                target,
-                pai,
-                consumed,
-            }
-            | Event::Pon {
-                actor,
-                target,
-                pai,
-                consumed,
-            } => {
-                let mut naki = consumed.map(|tile| (tile, false)).to_vec();
-                naki.insert(((4 + actor - target) % 4) as usize - 1, (pai, true));
-                fuuro[state.rel(actor)].push(naki);
-                visible_kawa[state.rel(target)].pop();
-            }
-            Event::Daiminkan {
-                actor,
-                target,
-                pai,
-                consumed,
-            } => {
-                let mut naki = consumed.map(|tile| (tile, false)).to_vec();
-                let t = ((4 + actor - target) % 4) as usize - 1;
-                naki.insert(if t == 2 { 3 } else { t }, (pai, true));
-                visible_kawa[state.rel(target)].pop();
-            }
-            Event::Ankan { actor, consumed } => {
-                let naki = consumed.map(|tile| (tile, false)).to_vec();
-                fuuro[state.rel(actor)].push(naki);
-            }
-            Event::Kakan { actor, pai, .. } => {
-                'outer: for naki in &mut fuuro[state.rel(actor)] {
-                    for (i, (tile, sideways)) in naki.iter().enumerate() {
-                        if tile.deaka() == pai.deaka() && *sideways {
-                            naki.insert(i + 1, (pai, true));
-                            break 'outer;
-                        }
-                    }
-                }
-            }
-            Event::EndKyoku => break,
-            _ => {}
+#[derive(Serialize)]
+pub struct ReconcileResult {
+    pub board: RawBoard,
+    // one line per change, empty when the board was consistent
+    pub report: Vec<String>,
+}
+
+// Makes the dora indicators agree with the kans on the table: `mode` "trim" drops extra indicators, "kan"
+// adds ankan to the opponents for them instead. Missing indicators are added from unseen tiles either way.
+pub fn reconcile_board(mut raw_board: RawBoard, mode: &str) -> Result<ReconcileResult> {
+    let inject_kans = match mode {
+        "trim" => false,
+        "kan" => true,
+        _ => bail!("mode must be trim or kan"),
+    };
+    let board = parse_board(raw_board.clone(), false).context("parse error")?;
+    let report = reconcile_dora(&mut raw_board, &board, inject_kans).context("reconcile error")?;
+    Ok(ReconcileResult {
+        board: raw_board,
+        report,
+    })
+}
+
+// The board as the focused player sees it.
+fn seen_board(raw_board: RawBoard) -> Result<Board> {
+    parse_board(raw_board, true).context("parse error")
+}
+
+// The generator's seat of each seat of the board, without the empty seat of a three player board.
+fn board_seats(raw_board: &RawBoard) -> Result<Vec<usize>> {
+    let players = raw_board.players().context("parse error")? as usize;
+    let (seats, _) = raw_board.seat_map().context("parse error")?;
+    Ok(seats[..players].to_vec())
+}
+
+// Things the board allows that a real game would not have led to, such as scores that do not add up. A board
+// that cannot be parsed is still an error.
+pub fn check_board(raw_board: RawBoard) -> Result<Vec<BoardWarning>> {
+    let kyoku = raw_board.kyoku.clone();
+    let deposits = raw_board.deposits().context("parse error")?;
+    let seats = board_seats(&raw_board)?;
+    let board = seen_board(raw_board)?;
+    Ok(board_warnings(&board, &kyoku, deposits, &seats))
+}
+
+// Kabe and one chance blocks of the board, from the focused player's point of view.
+pub fn analyze_walls(raw_board: RawBoard) -> Result<WallAnalysis> {
+    let board = seen_board(raw_board)?;
+    Ok(wall_analysis(&visible_counts(&board)))
+}
+
+// Danger of every tile kind against each opponent, from the focused player's point of view.
+pub fn analyze_danger(raw_board: RawBoard) -> Result<Vec<OpponentDanger>> {
+    let seats = board_seats(&raw_board)?;
+    let board = seen_board(raw_board)?;
+    Ok(danger_analysis(&board, &visible_counts(&board), &seats))
+}
+
+// The board as an svg image, see svg.rs.
+pub fn render_board_svg(raw_board: RawBoard) -> Result<String> {
+    Ok(svg::board_to_svg(&seen_board(raw_board)?))
+}
+
+// Live wall left and unseen copies of every tile, from the focused player's point of view.
+pub fn count_tiles(raw_board: RawBoard) -> Result<TileCounts> {
+    let players = raw_board.players().context("parse error")? as usize;
+    let kita = raw_board.nuki().context("parse error")?.iter().sum();
+    let board = seen_board(raw_board)?;
+    tile_counts(&board, &visible_counts(&board), players, kita).context("parse error")
+}
+
+// Genbutsu and suji of the focused player's tiles against each opponent in riichi.
+pub fn analyze_riichi_safety(raw_board: RawBoard) -> Result<Vec<RiichiSafety>> {
+    let seats = board_seats(&raw_board)?;
+    Ok(riichi_safety(&seen_board(raw_board)?, &seats))
+}
+
+// Shanten and ukeire of the focused player's hand.
+pub fn analyze_hand(raw_board: RawBoard) -> Result<HandAnalysis> {
+    let board = seen_board(raw_board)?;
+    Ok(hand_analysis(&board, &visible_counts(&board)))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct WinOptions {
+    // the tile the hand wins on, which the board's tehai does not hold yet
+    pub tile: String,
+    // a ron on the last discard of this relative seat (1-3), or a tsumo when unset
+    pub ron: Option<u8>,
+}
+
+// The log of the board ending in the focused player's win on `options.tile`.
+fn win_events(mut raw_board: RawBoard, options: &WinOptions) -> Result<Vec<Event>> {
+    parse_tile(&options.tile).context("parse error: incorrect winning tile")?;
+    // the tenhou conversion scores every win the four player way
+    ensure!(
+        raw_board.players != "3",
+        "parse error: wins of a three player board cannot be scored"
+    );
+    ensure!(
+        raw_board.tsumo.is_empty() && raw_board.claim.is_empty(),
+        "parse error: the board of a win gives neither tsumo nor claim, the winning tile stands for them"
+    );
+    match options.ron {
+        Some(seat) => {
+            ensure!(
+                (1..4).contains(&seat),
+                "parse error: ron must be the relative seat of an opponent, from 1 to 3"
+            );
+            // thrown out as a tsumogiri, the loser's draw rather than a tile the generator puts in its hand
+            raw_board.kawa.resize(4, String::new());
+            raw_board.kawa[seat as usize].push_str(&format!("{}.", options.tile));
+            raw_board.claim = seat.to_string();
         }
+        None => raw_board.tsumo = options.tile.clone(),
     }
+    let mut events = board_events(raw_board)?;
+    let player_id = player_id_of(&events)?;
+    let target = options.ron.map_or(player_id, |seat| (player_id + seat) % 4);
+    append_win(&mut events, player_id, target);
+    Ok(events)
+}
 
-    let kawa_strings = visible_kawa
-        .into_iter()
-        .map(|actor_kawa| {
-            actor_kawa
-                .into_iter()
+// The yaku, fu, han and payments if the focused player won on `options.tile`, scored like the generated logs.
+pub fn score_win(raw_board: RawBoard, options: &WinOptions) -> Result<AgariResult> {
+    let events = win_events(raw_board, options)?;
+    let result = GenerateResult::from_events(events, &GenerateOptions::default())?;
+    result
+        .result
+        .and_then(|result| result.agari.into_iter().next())
+        .with_context(|| format!("the hand does not win on {}", options.tile))
+}
+
+// The waits of the focused player's tenpai hand, before its draw, each scored as a tsumo and as a ron.
+pub fn analyze_waits(raw_board: RawBoard) -> Result<Vec<WaitScore>> {
+    let board = seen_board(raw_board.clone())?;
+    ensure!(
+        board.tehai.len() % 3 == 1 && raw_board.claim.is_empty(),
+        "parse error: waits are of a hand before its draw, without tsumo or claim"
+    );
+    let analysis = hand_analysis(&board, &visible_counts(&board));
+    ensure!(
+        analysis.ukeire.shanten == 0,
+        "the hand is not tenpai, it is {} shanten",
+        analysis.ukeire.shanten
+    );
+    // one log of a tsumo and one of a ron on the kamicha's discard, each wait then taking the place of the
+    // winning tile of both
+    let logs = match analysis.ukeire.accepted.iter().find(|accepted| accepted.remaining > 0) {
+        Some(drawable) => {
+            let log = |ron| {
+                let options = WinOptions {
+                    tile: drawable.tile.clone(),
+                    ron,
+                };
+                win_events(raw_board.clone(), &options)
+            };
+            Some((log(None)?, log(Some(3))?))
+        }
+        None => None,
+    };
+    let score = |log: &[Event], tile: &str| -> Result<Option<AgariResult>> {
+        let mut events = log.to_vec();
+        swap_win_tile(&mut events, parse_tile(tile).context("parse error: incorrect winning tile")?)
+            .context("log generation error")?;
+        let result = GenerateResult::from_events(events, &GenerateOptions::default())?;
+        // a wait without a yaku is no win
+        Ok(result.result.and_then(|result| result.agari.into_iter().next()))
+    };
+    let mut waits = vec![];
+    for accepted in analysis.ukeire.accepted {
+        let (tsumo, ron) = match &logs {
+            // every copy is in sight, the hand cannot win on it
+            Some(_) if accepted.remaining == 0 => (None, None),
+            Some((tsumo, ron)) => (score(tsumo, &accepted.tile)?, score(ron, &accepted.tile)?),
+            None => (None, None),
+        };
+        waits.push(WaitScore {
+            tsumo,
+            ron,
+            tile: accepted.tile,
+            remaining: accepted.remaining,
+        });
+    }
+    Ok(waits)
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ExtractOptions {
+    // key scores, kawa and fuuro by absolute seat (the first East first) instead of starting at the player;
+    // such a board has to be rotated back before it can be generated from
+    pub absolute_seats: bool,
+    // which kyoku of the game to extract, 0-based, e.g. 6 for South 3 of a hanchan without renchan
+    pub kyoku: usize,
+    // stop at the kyoku's event at this index, StartKyoku being 0, instead of at its end
+    pub event_index: Option<usize>,
+    // or right after the player's draw of this turn, 1-based like generate_highlight's turn
+    pub turn: Option<usize>,
+    // "unicode" writes the tiles as mahjong glyphs and "fullwidth" in fullwidth characters, for pasting into
+    // chat; such boards are for reading only and do not parse back. "kanji" writes the honors as 東南西北白發中,
+    // which does. Empty is the usual notation.
+    pub tile_format: String,
+    // extract_snapshots takes one board per discard instead of one per event
+    pub discards_only: bool,
+}
+
+// Index in `events` of the last event ExtractOptions lets the extraction replay.
+fn extract_stop(events: &[Event], player_id: u8, options: &ExtractOptions) -> Result<Option<usize>> {
+    match (options.event_index, options.turn) {
+        (None, None) => Ok(None),
+        (Some(_), Some(_)) => bail!("event_index and turn cannot both be set"),
+        (Some(index), None) => {
+            // `events` start with the StartGame before StartKyoku
+            ensure!(index + 1 < events.len(), "the kyoku has no event at index {index}");
+            Ok(Some(index + 1))
+        }
+        (None, Some(turn)) => turn
+            .checked_sub(1)
+            .and_then(|n| {
+                events
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, event)| matches!(event, Event::Tsumo { actor, .. } if *actor == player_id))
+                    .nth(n)
+            })
+            .map(|(i, _)| Some(i))
+            .with_context(|| format!("player has no turn {turn} in kyoku {}", options.kyoku)),
+    }
+}
+
+// The board of the `options.kyoku`-th kyoku of `events` as the player sitting at `jikaze` saw it at its end,
+// or at the point `options` stop at.
+pub fn extract_board(events: Vec<Event>, jikaze: &str, options: &ExtractOptions) -> Result<RawBoard> {
+    let jikaze_str = jikaze.to_owned();
+    let jikaze = parse_jikaze(jikaze)?;
+    let mut events = single_kyoku(&events, options.kyoku)?;
+
+    let oya = kyoku_oya(&events).context("no kyokus")?;
+    let player_id = sanma::player_seat(&events, oya, jikaze)?;
+    let mut tracker = Tracker::default();
+    tracker.sanma = sanma::is_sanma(&events);
+    if let Some(stop) = extract_stop(&events, player_id, options)? {
+        events.truncate(stop + 1);
+    }
+    // the player's draw, while it is the last event replayed
+    let mut drawn = None;
+    for (index, event) in events.iter().enumerate() {
+        if matches!(event, Event::EndKyoku) {
+            break;
+        }
+        tracker.update(event).context("invalid event")?;
+        if let Some(seat) = nuki::kita_seat(&events, index) {
+            tracker.nuki[seat as usize] += 1;
+        }
+        drawn = match event {
+            Event::Tsumo { actor, pai } if *actor == player_id => Some(*pai),
+            _ => None,
+        };
+    }
+    tracked_board(&tracker, drawn, player_id, jikaze_str, options)
+}
+
+// The board `tracker` shows the player at `player_id`, whose wind is `jikaze_str`.
+fn tracked_board(
+    tracker: &Tracker,
+    drawn: Option<Tile>,
+    player_id: u8,
+    jikaze_str: String,
+    options: &ExtractOptions,
+) -> Result<RawBoard> {
+    // relative seat r is absolute seat player_id + r, among three in a three player game
+    let seats = if tracker.sanma { 3 } else { 4 };
+    let relative = |seat: usize| (player_id as usize + seat) % seats;
+    let mut deposited: Vec<bool> = (0..seats).map(|seat| tracker.deposited[relative(seat)]).collect();
+
+    let kawa_strings = (0..seats)
+        .map(|seat| {
+            tracker.kawa[relative(seat)]
+                .iter()
+                .copied()
                 .map(|(pai, tsumogiri, riichi)| {
                     format!(
                         "{}{}",
                         tiles_vec_to_string(&[pai]),
-                        if tsumogiri {
-                            "."
-                        } else if riichi {
-                            "-"
-                        } else {
-                            ""
+                        match (tsumogiri, riichi) {
+                            (true, true) => "-",
+                            (false, true) => "!",
+                            (true, false) => ".",
+                            (false, false) => "",
                         }
                     )
                 })
@@ -278,11 +1787,11 @@ pub fn generate_board_from_tenhou_js(val: JsValue, jikaze: JsValue) -> Result<Js
         })
         .collect::<Vec<_>>();
 
-    let fuuro_strings = fuuro
-        .into_iter()
-        .map(|fuuro| {
-            fuuro
-                .into_iter()
+    let fuuro_strings = (0..seats)
+        .map(|seat| {
+            tracker.fuuro[relative(seat)]
+                .iter()
+                .cloned()
                 .rev()
                 .flatten()
                 .map(|(tile, sideways)| {
@@ -297,17 +1806,132 @@ pub fn generate_board_from_tenhou_js(val: JsValue, jikaze: JsValue) -> Result<Js
         })
         .collect::<Vec<_>>();
 
-    let board = RawBoard {
-        kyoku: format!("{}{}", state.bakaze, state.kyoku + 1),
+    let mut hand = tracker.tehais[player_id as usize].clone();
+    if let Some(pai) = drawn {
+        hand.pop_if(|tile| *tile == pai);
+    }
+    ensure!(
+        hand.iter().all(|tile| !tile.is_unknown()),
+        "the log does not show the tehai of the player at {jikaze_str}"
+    );
+    let mut tehai = [0; 34];
+    let mut akas = [false; 3];
+    for tile in &hand {
+        tehai[tile.deaka().as_usize()] += 1;
+        if tile.is_aka() {
+            akas[tile.as_usize() - tu8!(5mr) as usize] = true;
+        }
+    }
+
+    let mut board = RawBoard {
+        kyoku: format!("{}{}", tracker.bakaze, tracker.kyoku),
         jikaze: jikaze_str,
-        kyotaku: state.kyotaku.to_string(),
-        honba: state.honba.to_string(),
-        dora: tiles_vec_to_string(&state.dora_indicators),
-        scores: state.scores.iter().map(|score| score.to_string()).collect::<Vec<_>>(),
-        tehai: tiles_to_string(&state.tehai, state.akas_in_hand).replace(" ", ""),
+        kyotaku: tracker.kyotaku.to_string(),
+        honba: tracker.honba.to_string(),
+        dora: tiles_vec_to_string(&tracker.dora_indicators),
+        scores: (0..seats).map(|seat| tracker.scores[relative(seat)].to_string()).collect(),
+        tehai: tiles_to_string(&tehai, akas).replace(" ", ""),
+        tsumo: drawn.map(|pai| tiles_vec_to_string(&[pai])).unwrap_or_default(),
         kawa: kawa_strings,
         fuuro: fuuro_strings,
+        players: if tracker.sanma { "3".to_owned() } else { String::new() },
+        nuki: match tracker.nuki.iter().any(|count| *count > 0) {
+            true => (0..seats)
+                .map(|seat| "N".repeat(tracker.nuki[relative(seat)] as usize))
+                .collect(),
+            false => vec![],
+        },
+        ..Default::default()
+    };
+    if options.absolute_seats {
+        ensure!(!tracker.sanma, "the seats of a three player board are always relative");
+        // index r holds relative seat r, which is absolute seat player_id + r
+        let shift = player_id as usize;
+        board.scores.rotate_right(shift);
+        board.kawa.rotate_right(shift);
+        board.fuuro.rotate_right(shift);
+        deposited.rotate_right(shift);
+    }
+    board.deposits = (0..seats)
+        .filter(|seat| deposited[*seat])
+        .map(|seat| seat.to_string())
+        .collect();
+    let unicode = match options.tile_format.as_str() {
+        "" => return Ok(board),
+        "kanji" => None,
+        "unicode" => Some(true),
+        "fullwidth" => Some(false),
+        other => bail!("unknown tile format {other:?}, expected unicode, fullwidth or kanji"),
     };
+    for field in [&mut board.tehai, &mut board.tsumo, &mut board.dora]
+        .into_iter()
+        .chain(&mut board.kawa)
+        .chain(&mut board.fuuro)
+    {
+        *field = match unicode {
+            Some(unicode) => html::display_notation(field, unicode),
+            None => notation::kanji_honors(field),
+        };
+    }
+    Ok(board)
+}
+
+// The board after every event of the `options.kyoku`-th kyoku of `events` up to where `options` stop, or after
+// every discard with `options.discards_only`, as extract_board gives the last one.
+pub fn extract_snapshots(events: Vec<Event>, jikaze: &str, options: &ExtractOptions) -> Result<Vec<RawBoard>> {
+    let jikaze_str = jikaze.to_owned();
+    let jikaze = parse_jikaze(jikaze)?;
+    let mut events = single_kyoku(&events, options.kyoku)?;
+
+    let oya = kyoku_oya(&events).context("no kyokus")?;
+    let player_id = sanma::player_seat(&events, oya, jikaze)?;
+    let mut tracker = Tracker::default();
+    tracker.sanma = sanma::is_sanma(&events);
+    if let Some(stop) = extract_stop(&events, player_id, options)? {
+        events.truncate(stop + 1);
+    }
+    let mut snapshots = vec![];
+    for (index, event) in events.iter().enumerate() {
+        if matches!(event, Event::EndKyoku) {
+            break;
+        }
+        tracker.update(event).context("invalid event")?;
+        if let Some(seat) = nuki::kita_seat(&events, index) {
+            tracker.nuki[seat as usize] += 1;
+        }
+        let drawn = match event {
+            Event::StartGame { .. } => continue,
+            Event::Dahai { .. } => None,
+            _ if options.discards_only => continue,
+            Event::Tsumo { actor, pai } if *actor == player_id => Some(*pai),
+            _ => None,
+        };
+        snapshots.push(tracked_board(&tracker, drawn, player_id, jikaze_str.clone(), options)?);
+    }
+    Ok(snapshots)
+}
 
-    serde_wasm_bindgen::to_value(&board).map_err(|e| JsValue::from_str(&format!("serialize result error: {e}")))
+// The board of every kyoku of `events`, as extract_board gives it for each `options.kyoku`, following the
+// player who sits at `jikaze` in the first kyoku through the seat changes.
+pub fn extract_boards(events: Vec<Event>, jikaze: &str, options: &ExtractOptions) -> Result<Vec<RawBoard>> {
+    let first = single_kyoku(&events, 0)?;
+    let player_id = sanma::player_seat(&first, kyoku_oya(&first).context("no kyokus")?, parse_jikaze(jikaze)?)?;
+    let seats = if sanma::is_sanma(&first) { 3 } else { 4 };
+    let oyas: Vec<u8> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::StartKyoku { oya, .. } => Some(*oya),
+            _ => None,
+        })
+        .collect();
+    let mut boards = vec![];
+    for (kyoku, oya) in oyas.into_iter().enumerate() {
+        let jikaze = ["E", "S", "W", "N"][((seats + player_id - oya) % seats) as usize];
+        let options = ExtractOptions {
+            kyoku,
+            ..options.clone()
+        };
+        boards.push(extract_board(events.clone(), jikaze, &options).with_context(|| format!("kyoku {kyoku}"))?);
+    }
+    Ok(boards)
 }
@@ -0,0 +1,102 @@
+use std::cell::Cell;
+use std::str::FromStr;
+
+use anyhow::{bail, Error};
+
+// The language of the error messages handed to players. The library's own messages are English; other
+// locales put a translation keyed by the error code in front and keep the English detail after it.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum Locale {
+    #[default]
+    En,
+    Ja,
+}
+
+impl FromStr for Locale {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Locale::En),
+            "ja" => Ok(Locale::Ja),
+            other => bail!("unknown locale {other:?}, expected en or ja"),
+        }
+    }
+}
+
+thread_local! {
+    static LOCALE: Cell<Locale> = const { Cell::new(Locale::En) };
+}
+
+pub(crate) fn set_locale(locale: Locale) {
+    LOCALE.set(locale);
+}
+
+pub(crate) fn current() -> Locale {
+    LOCALE.get()
+}
+
+// the codes of BoardError kinds and of the binding's stages
+fn japanese(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "Syntax" => "牌の表記が読めません",
+        "InvalidTile" => "存在しない牌です",
+        "InvalidSeat" => "席の指定が正しくありません",
+        "InvalidNumber" => "数値が正しくありません",
+        "InvalidJikaze" => "自風が正しくありません",
+        "Inconsistent" => "盤面に矛盾があります",
+        "Parse" => "盤面を読み込めません",
+        "Deserialize" => "入力の形式が正しくありません",
+        "InvalidOptions" => "オプションが正しくありません",
+        "Generation" => "この盤面から牌譜を作れません",
+        "Scenario" => "指定された結末を作れません",
+        "Serialize" => "結果を書き出せません",
+        "Conversion" => "変換できません",
+        "Export" => "書き出せません",
+        "InvalidLog" => "牌譜が正しくありません",
+        "InvalidEvent" => "牌譜の進行に誤りがあります",
+        "NoKyoku" => "その局はありません",
+        _ => return None,
+    })
+}
+
+// "kawa[1]" -> "河[1]"
+fn japanese_field(field: &str) -> String {
+    let name = field.split('[').next().unwrap_or(field);
+    let translated = match name {
+        "kyoku" => "局",
+        "jikaze" => "自風",
+        "honba" => "本場",
+        "kyotaku" => "供託",
+        "scores" => "点数",
+        "dora" => "ドラ表示牌",
+        "uradora" => "裏ドラ表示牌",
+        "tehai" => "手牌",
+        "tsumo" => "ツモ",
+        "kawa" => "河",
+        "fuuro" => "副露",
+        "nuki" => "抜きドラ",
+        "junme" => "巡目",
+        "chiicha" => "起家",
+        "claim" => "鳴き",
+        "deposits" => "供託済みのリーチ",
+        "draws" => "ツモ順",
+        _ => name,
+    };
+    format!("{translated}{}", &field[name.len()..])
+}
+
+// The message of an error with `code`, raised on `field` of a board if any, whose English message is
+// `detail`, e.g. "手牌: 存在しない牌です (tile 8z does not exist)" in Japanese.
+pub fn localize_error(locale: Locale, code: &str, field: Option<&str>, detail: &str) -> String {
+    let Some(message) = (match locale {
+        Locale::En => None,
+        Locale::Ja => japanese(code),
+    }) else {
+        return detail.to_owned();
+    };
+    match field.map(japanese_field) {
+        Some(field) => format!("{field}: {message} ({detail})"),
+        None => format!("{message} ({detail})"),
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::Read;
+use std::process::ExitCode;
+
+use anyhow::{bail, Context, Result};
+use paifu_gen::{
+    extract_board, generate, mjai_events, mjai_jsonl, mjlog_events, mortal_jsonl, mortal_review_events, render_board_svg,
+    single_kyoku, tenhou_log_events, ExtractOptions, GenerateOptions, RawBoard,
+};
+use riichi::mjai::Event;
+use serde::Deserialize;
+
+const USAGE: &str = "usage: paifu_gen [--kyoku N] [--jikaze E|S|W|N] [--format FORMAT] [--out DIR] [FILE...]
+
+Reads RawBoard json, tenhou/6 json logs, mjai logs (jsonl or a json array), Mortal reviews or mjlog xml
+from each FILE, or stdin when there is none or it is -. A board is generated into a log, FORMAT tenhou
+(the default), mjai (one event per line), mortal (as Mortal's reviewer reads it) or json (the whole
+result), or drawn as an svg image with FORMAT svg. A log gives the board of kyoku N (0-based, 0 by
+default) as the player at --jikaze saw it, FORMAT board (the default) or mjai. Mortal takes one file per
+kyoku, so with --out DIR FORMAT mortal writes each kyoku to DIR/kyoku<K>.jsonl instead of printing it.
+";
+
+#[derive(Default)]
+struct Args {
+    kyoku: usize,
+    jikaze: Option<String>,
+    format: Option<String>,
+    out: Option<String>,
+    files: Vec<String>,
+}
+
+fn parse_args() -> Result<Option<Args>> {
+    let mut args = Args::default();
+    let mut raw = std::env::args().skip(1);
+    while let Some(arg) = raw.next() {
+        let mut value = |flag: &str| raw.next().with_context(|| format!("{flag} needs a value"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--kyoku" => args.kyoku = value("--kyoku")?.parse().context("incorrect --kyoku")?,
+            "--jikaze" => args.jikaze = Some(value("--jikaze")?),
+            "--format" => args.format = Some(value("--format")?),
+            "--out" => args.out = Some(value("--out")?),
+            flag if flag.starts_with("--") => bail!("unknown flag {flag}"),
+            _ => args.files.push(arg),
+        }
+    }
+    if args.files.is_empty() {
+        args.files.push("-".to_owned());
+    }
+    Ok(Some(args))
+}
+
+fn read_input(file: &str) -> Result<String> {
+    if file == "-" {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input).context("reading stdin")?;
+        return Ok(input);
+    }
+    std::fs::read_to_string(file).with_context(|| format!("reading {file}"))
+}
+
+fn convert_log(events: &[Event], args: &Args) -> Result<String> {
+    let events = single_kyoku(events, args.kyoku)?;
+    match args.format.as_deref().unwrap_or("board") {
+        "board" => {
+            let jikaze = args
+                .jikaze
+                .as_deref()
+                .context("extracting a board from a log needs --jikaze")?;
+            let board = extract_board(events, jikaze, &ExtractOptions::default())?;
+            serde_json::to_string_pretty(&board).context("serialize result error")
+        }
+        "mjai" => mjai_jsonl(&events).context("serialize result error"),
+        format => bail!("a log converts to board or mjai, not {format}"),
+    }
+}
+
+fn convert_board(mut raw_board: RawBoard, args: &Args) -> Result<String> {
+    if let Some(jikaze) = &args.jikaze {
+        raw_board.jikaze = jikaze.clone();
+    }
+    if args.format.as_deref() == Some("svg") {
+        return render_board_svg(raw_board);
+    }
+    let result = generate(raw_board, &GenerateOptions::default())?;
+    match args.format.as_deref().unwrap_or("tenhou") {
+        "tenhou" => Ok(result.tenhou_log),
+        "mjai" => mjai_jsonl(&result.mjai_log).context("serialize result error"),
+        "mortal" => {
+            let kyokus = mortal_jsonl(&result.mjai_log, result.player_id as u8).context("serialize result error")?;
+            write_kyokus(kyokus, args.out.as_deref())
+        }
+        "json" => serde_json::to_string(&result).context("serialize result error"),
+        format => bail!("a board converts to tenhou, mjai, mortal, json or svg, not {format}"),
+    }
+}
+
+// One mortal file per kyoku: each into its own file of `out`, or printed when there is only the one.
+fn write_kyokus(kyokus: Vec<String>, out: Option<&str>) -> Result<String> {
+    let Some(out) = out else {
+        return match <[String; 1]>::try_from(kyokus) {
+            Ok([kyoku]) => Ok(kyoku),
+            Err(kyokus) => bail!(
+                "the log has {} kyoku, give --out DIR to write one file per kyoku",
+                kyokus.len()
+            ),
+        };
+    };
+    let mut written = vec![];
+    for (k, kyoku) in kyokus.iter().enumerate() {
+        let path = std::path::Path::new(out).join(format!("kyoku{k}.jsonl"));
+        std::fs::write(&path, kyoku).with_context(|| format!("writing {}", path.display()))?;
+        written.push(path.display().to_string());
+    }
+    Ok(written.join("\n"))
+}
+
+fn convert(input: &str, args: &Args) -> Result<String> {
+    if input.trim_start().starts_with('<') {
+        return convert_log(&mjlog_events(input)?, args);
+    }
+    // several lines of events do not parse as a single json value
+    let value: serde_json::Value = match serde_json::from_str(input) {
+        Ok(value) => value,
+        Err(_) if input.trim_start().starts_with('{') => return convert_log(&mjai_events(input)?, args),
+        Err(e) => return Err(e).context("deserialize error"),
+    };
+    if value.get("review").is_some() {
+        return convert_log(&mortal_review_events(&value)?, args);
+    }
+    if value.get("log").is_some() {
+        return convert_log(&tenhou_log_events(&value)?, args);
+    }
+    if value.is_array() || value.get("type").is_some() {
+        return convert_log(&mjai_events(input)?, args);
+    }
+    let raw_board = RawBoard::deserialize(&value).context("deserialize error")?;
+    convert_board(raw_board, args)
+}
+
+fn run() -> Result<()> {
+    let Some(args) = parse_args()? else {
+        print!("{USAGE}");
+        return Ok(());
+    };
+    for file in &args.files {
+        let output = convert(&read_input(file)?, &args).with_context(|| file.clone())?;
+        println!("{}", output.trim_end_matches('\n'));
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("paifu_gen: {e:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
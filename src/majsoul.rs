@@ -0,0 +1,354 @@
+use anyhow::{bail, ensure, Context, Result};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+use serde_json::Value;
+
+// MahjongSoul records once decoded from protobuf: {"head": {"accounts": [..]}, "data": {"actions": [..]}}, where
+// each action wraps a {"name": ".lq.RecordNewRound", "data": {..}}. Fields left at zero are often missing.
+
+// "1m".."9m", "0m" for the red five, "1z".."7z" for ESWN and haku, hatsu, chun
+fn parse_tile(tile: &str) -> Result<Tile> {
+    let &[number, suit] = tile.as_bytes() else {
+        bail!("incorrect tile {tile:?}");
+    };
+    let id = match (number, suit) {
+        (b'0', b'm') => tu8!(5mr),
+        (b'0', b'p') => tu8!(5pr),
+        (b'0', b's') => tu8!(5sr),
+        (b'1'..=b'9', b'm') => number - b'1',
+        (b'1'..=b'9', b'p') => 9 + number - b'1',
+        (b'1'..=b'9', b's') => 18 + number - b'1',
+        (b'1'..=b'7', b'z') => 27 + number - b'1',
+        _ => bail!("incorrect tile {tile:?}"),
+    };
+    Ok(Tile::new_unchecked(id))
+}
+
+fn tile_field(data: &Value, field: &str) -> Result<Tile> {
+    parse_tile(data[field].as_str().with_context(|| format!("incorrect {field}"))?)
+}
+
+fn tiles_field(data: &Value, field: &str) -> Result<Vec<Tile>> {
+    match &data[field] {
+        Value::Null => Ok(vec![]),
+        Value::Array(tiles) => tiles
+            .iter()
+            .map(|tile| parse_tile(tile.as_str().with_context(|| format!("incorrect {field}"))?))
+            .collect(),
+        _ => bail!("incorrect {field}"),
+    }
+}
+
+fn number(data: &Value, field: &str) -> i64 {
+    data[field].as_i64().unwrap_or_default()
+}
+
+fn seat(data: &Value) -> Result<u8> {
+    let seat = number(data, "seat");
+    ensure!((0..4).contains(&seat), "incorrect seat {seat}");
+    Ok(seat as u8)
+}
+
+fn four(data: &Value, field: &str) -> Result<[i32; 4]> {
+    let mut values = [0; 4];
+    if let Some(array) = data[field].as_array() {
+        ensure!(array.len() == 4, "incorrect {field}, only four player records are supported");
+        for (value, entry) in values.iter_mut().zip(array) {
+            *value = entry.as_i64().with_context(|| format!("incorrect {field}"))? as i32;
+        }
+    }
+    Ok(values)
+}
+
+// (name, data) of every recorded action, skipping the player inputs some exports interleave
+fn actions(record: &Value) -> Result<Vec<(&str, &Value)>> {
+    let list = [&record["data"]["actions"], &record["actions"], record]
+        .into_iter()
+        .find_map(Value::as_array)
+        .context("incorrect record, expected an array of actions")?;
+    Ok(list
+        .iter()
+        .filter_map(|action| {
+            let action = if action["result"].is_object() {
+                &action["result"]
+            } else {
+                action
+            };
+            let name = action["name"].as_str()?;
+            Some((name.trim_start_matches(".lq."), &action["data"]))
+        })
+        .collect())
+}
+
+fn names(record: &Value) -> [String; 4] {
+    let mut names: [String; 4] = Default::default();
+    for account in record["head"]["accounts"].as_array().into_iter().flatten() {
+        if let (Ok(seat), Some(name)) = (seat(account), account["nickname"].as_str()) {
+            names[seat as usize] = name.to_owned();
+        }
+    }
+    names
+}
+
+#[derive(Default)]
+struct Round {
+    hands: [Vec<Tile>; 4],
+    pons: [Vec<[Tile; 3]>; 4],
+    dora_count: usize,
+    // the seat whose discard or added kan tile can be ronned
+    last_actor: u8,
+    pending_reach: Option<u8>,
+}
+
+impl Round {
+    fn take(&mut self, seat: u8, tile: Tile) -> Result<()> {
+        let hand = &mut self.hands[seat as usize];
+        let i = hand
+            .iter()
+            .position(|held| *held == tile)
+            .with_context(|| format!("seat {seat} does not hold {tile}"))?;
+        hand.remove(i);
+        Ok(())
+    }
+
+    fn new_doras(&mut self, data: &Value, events: &mut Vec<Event>) -> Result<()> {
+        let doras = tiles_field(data, "doras")?;
+        for dora_marker in doras.iter().skip(self.dora_count) {
+            events.push(Event::Dora {
+                dora_marker: *dora_marker,
+            });
+        }
+        self.dora_count = self.dora_count.max(doras.len());
+        Ok(())
+    }
+
+    fn accept_reach(&mut self, events: &mut Vec<Event>) {
+        if let Some(actor) = self.pending_reach.take() {
+            events.push(Event::ReachAccepted { actor });
+        }
+    }
+}
+
+fn start_kyoku(data: &Value, round: &mut Round, events: &mut Vec<Event>) -> Result<()> {
+    let oya = number(data, "ju");
+    ensure!((0..4).contains(&oya), "incorrect ju {oya}");
+    let chang = number(data, "chang");
+    ensure!((0..4).contains(&chang), "incorrect chang {chang}");
+    let mut doras = tiles_field(data, "doras")?;
+    if doras.is_empty() {
+        doras.push(tile_field(data, "dora")?);
+    }
+
+    *round = Round {
+        dora_count: doras.len(),
+        ..Default::default()
+    };
+    let mut tehais = [[Tile::default(); 13]; 4];
+    let mut first_draw = None;
+    for (seat, tehai) in tehais.iter_mut().enumerate() {
+        let mut tiles = tiles_field(data, &format!("tiles{seat}"))?;
+        ensure!(!tiles.is_empty(), "only four player records are supported");
+        // the dealer's fourteenth tile is their first draw
+        if seat == oya as usize && tiles.len() == 14 {
+            first_draw = tiles.pop();
+        }
+        ensure!(tiles.len() == 13, "tiles{seat} has {} tiles", tiles.len());
+        tehai.copy_from_slice(&tiles);
+        round.hands[seat] = tiles;
+    }
+
+    events.push(Event::StartKyoku {
+        bakaze: Tile::new_unchecked(tu8!(E) + chang as u8),
+        dora_marker: doras[0],
+        kyoku: oya as u8 + 1,
+        honba: number(data, "ben") as u8,
+        kyotaku: number(data, "liqibang") as u8,
+        oya: oya as u8,
+        scores: four(data, "scores")?,
+        tehais,
+    });
+    if let Some(pai) = first_draw {
+        round.hands[oya as usize].push(pai);
+        events.push(Event::Tsumo { actor: oya as u8, pai });
+    }
+    Ok(())
+}
+
+fn call(data: &Value, round: &mut Round, events: &mut Vec<Event>) -> Result<()> {
+    let actor = seat(data)?;
+    let tiles = tiles_field(data, "tiles")?;
+    let froms = data["froms"].as_array().context("incorrect froms")?;
+    ensure!(tiles.len() == froms.len(), "incorrect froms");
+    let mut pai = None;
+    let mut consumed = vec![];
+    for (tile, from) in tiles.iter().zip(froms) {
+        let from = from.as_u64().context("incorrect froms")? as u8;
+        if from == actor {
+            consumed.push(*tile);
+        } else {
+            pai = Some((*tile, from));
+        }
+    }
+    let (pai, target) = pai.context("call without a called tile")?;
+    for tile in &consumed {
+        round.take(actor, *tile)?;
+    }
+
+    round.accept_reach(events);
+    events.push(match (number(data, "type"), consumed.as_slice()) {
+        (0, &[a, b]) => Event::Chi {
+            actor,
+            target,
+            pai,
+            consumed: [a, b],
+        },
+        (1, &[a, b]) => {
+            round.pons[actor as usize].push([pai, a, b]);
+            Event::Pon {
+                actor,
+                target,
+                pai,
+                consumed: [a, b],
+            }
+        }
+        (2, &[a, b, c]) => Event::Daiminkan {
+            actor,
+            target,
+            pai,
+            consumed: [a, b, c],
+        },
+        (kind, _) => bail!("incorrect call of type {kind} with {} tiles", tiles.len()),
+    });
+    Ok(())
+}
+
+fn kan(data: &Value, round: &mut Round, events: &mut Vec<Event>) -> Result<()> {
+    let actor = seat(data)?;
+    let tile = tile_field(data, "tiles")?;
+    round.accept_reach(events);
+    match number(data, "type") {
+        // ankan
+        3 => {
+            let hand = &mut round.hands[actor as usize];
+            let consumed: Vec<Tile> = hand.iter().copied().filter(|held| held.deaka() == tile.deaka()).collect();
+            ensure!(consumed.len() == 4, "seat {actor} does not hold four {tile}");
+            hand.retain(|held| held.deaka() != tile.deaka());
+            events.push(Event::Ankan {
+                actor,
+                consumed: [consumed[0], consumed[1], consumed[2], consumed[3]],
+            });
+        }
+        // kakan
+        2 => {
+            let pons = &mut round.pons[actor as usize];
+            let i = pons
+                .iter()
+                .position(|pon| pon[0].deaka() == tile.deaka())
+                .with_context(|| format!("seat {actor} has no pon of {tile}"))?;
+            let consumed = pons.remove(i);
+            let pai = round.hands[actor as usize]
+                .iter()
+                .copied()
+                .find(|held| held.deaka() == tile.deaka())
+                .unwrap_or(tile);
+            round.take(actor, pai)?;
+            round.last_actor = actor;
+            events.push(Event::Kakan { actor, pai, consumed });
+        }
+        kind => bail!("incorrect kan of type {kind}"),
+    }
+    Ok(())
+}
+
+fn hora(data: &Value, round: &mut Round, events: &mut Vec<Event>) -> Result<()> {
+    let hules = data["hules"].as_array().context("incorrect hules")?;
+    // the record only has the total, the first winner carries it
+    let mut deltas = Some(four(data, "delta_scores")?);
+    for hule in hules {
+        let actor = seat(hule)?;
+        let tsumo = hule["zimo"].as_bool().unwrap_or_default();
+        let ura_markers = tiles_field(hule, "li_doras")?;
+        events.push(Event::Hora {
+            actor,
+            target: if tsumo { actor } else { round.last_actor },
+            deltas: Some(deltas.take().unwrap_or_default()),
+            ura_markers: (!ura_markers.is_empty()).then_some(ura_markers),
+        });
+    }
+    Ok(())
+}
+
+// One StartGame, then StartKyoku .. EndKyoku per round and an EndGame, the same shape tenhou_to_mjai gives.
+pub(crate) fn majsoul_to_mjai(record: &Value) -> Result<Vec<Event>> {
+    let mut events = vec![Event::StartGame {
+        names: names(record),
+        kyoku_first: 0,
+        // every MahjongSoul ruleset in the records has the red fives
+        aka_flag: true,
+        id: None,
+    }];
+    let mut round = Round::default();
+    let mut in_kyoku = false;
+    for (name, data) in actions(record)? {
+        if name != "RecordNewRound" {
+            ensure!(in_kyoku, "{name} before RecordNewRound");
+        }
+        match name {
+            "RecordNewRound" => {
+                if in_kyoku {
+                    events.push(Event::EndKyoku);
+                }
+                start_kyoku(data, &mut round, &mut events).context("incorrect RecordNewRound")?;
+                in_kyoku = true;
+            }
+            "RecordDealTile" => {
+                let actor = seat(data)?;
+                let pai = tile_field(data, "tile")?;
+                round.accept_reach(&mut events);
+                round.new_doras(data, &mut events)?;
+                round.hands[actor as usize].push(pai);
+                events.push(Event::Tsumo { actor, pai });
+            }
+            "RecordDiscardTile" => {
+                let actor = seat(data)?;
+                let pai = tile_field(data, "tile")?;
+                round.take(actor, pai)?;
+                if data["is_liqi"].as_bool().unwrap_or_default() || data["is_wliqi"].as_bool().unwrap_or_default() {
+                    events.push(Event::Reach { actor });
+                    round.pending_reach = Some(actor);
+                }
+                events.push(Event::Dahai {
+                    actor,
+                    pai,
+                    tsumogiri: data["moqie"].as_bool().unwrap_or_default(),
+                });
+                round.last_actor = actor;
+                round.new_doras(data, &mut events)?;
+            }
+            "RecordChiPengGang" => call(data, &mut round, &mut events).context("incorrect RecordChiPengGang")?,
+            "RecordAnGangAddGang" => kan(data, &mut round, &mut events).context("incorrect RecordAnGangAddGang")?,
+            "RecordHule" => hora(data, &mut round, &mut events).context("incorrect RecordHule")?,
+            "RecordNoTile" => {
+                let mut deltas = [0; 4];
+                for scores in data["scores"].as_array().into_iter().flatten() {
+                    for (delta, d) in deltas.iter_mut().zip(four(scores, "delta_scores")?) {
+                        *delta += d;
+                    }
+                }
+                round.accept_reach(&mut events);
+                events.push(Event::Ryukyoku { deltas: Some(deltas) });
+            }
+            "RecordLiuJu" => {
+                round.accept_reach(&mut events);
+                events.push(Event::Ryukyoku { deltas: Some([0; 4]) });
+            }
+            "RecordBaBei" => bail!("nukidora is only played in three player records, which are not supported"),
+            _ => {}
+        }
+    }
+    ensure!(in_kyoku, "record has no rounds");
+    events.push(Event::EndKyoku);
+    events.push(Event::EndGame);
+    Ok(events)
+}
@@ -0,0 +1,783 @@
+use anyhow::{ensure, Context, Result};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+
+use crate::nuki::kita_seat;
+
+// Tenhou's mjlog xml: <mjloggm><GO/><UN/><TAIKYOKU/><INIT/><T12/><D12/>..<AGARI/>..</mjloggm>. Tiles are ids
+// 0-135, four per kind in libriichi's kind order; 16, 52 and 88 are the red fives when the game has them.
+
+struct Element<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, &'a str)>,
+}
+
+impl Element<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find_map(|(name, value)| (*name == key).then_some(*value))
+    }
+
+    fn attr(&self, key: &str) -> Result<&str> {
+        self.get(key).with_context(|| format!("{} has no {key}", self.name))
+    }
+
+    fn numbers(&self, key: &str) -> Result<Vec<i64>> {
+        match self.get(key) {
+            None | Some("") => Ok(vec![]),
+            Some(value) => value
+                .split(',')
+                .map(|number| number.parse().with_context(|| format!("incorrect {} {key}", self.name)))
+                .collect(),
+        }
+    }
+
+    fn number(&self, key: &str) -> Result<i64> {
+        self.attr(key)?
+            .parse()
+            .with_context(|| format!("incorrect {} {key}", self.name))
+    }
+}
+
+// Only what mjlog uses: flat elements with double quoted attributes.
+fn elements(xml: &str) -> Result<Vec<Element<'_>>> {
+    let mut elements = vec![];
+    for tag in xml.split('<').skip(1) {
+        let tag = tag.split_once('>').context("unclosed tag")?.0.trim_end_matches('/').trim();
+        if tag.starts_with(['?', '!', '/']) {
+            continue;
+        }
+        let (name, mut rest) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        let mut attributes = vec![];
+        while let Some((key, value)) = rest.split_once("=\"") {
+            let (value, next) = value
+                .split_once('"')
+                .with_context(|| format!("unclosed attribute in <{name}>"))?;
+            attributes.push((key.trim(), value));
+            rest = next;
+        }
+        elements.push(Element { name, attributes });
+    }
+    Ok(elements)
+}
+
+// names are percent encoded utf-8
+fn decode_percent(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        match (bytes[i], hex.and_then(|hex| u8::from_str_radix(hex, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub(crate) fn tile(id: i64, aka: bool) -> Result<Tile> {
+    ensure!((0..136).contains(&id), "incorrect tile id {id}");
+    Ok(match id {
+        16 if aka => Tile::new_unchecked(tu8!(5mr)),
+        52 if aka => Tile::new_unchecked(tu8!(5pr)),
+        88 if aka => Tile::new_unchecked(tu8!(5sr)),
+        _ => Tile::new_unchecked((id / 4) as u8),
+    })
+}
+
+// The `m` attribute of <N>: the kind of call, its tiles and who it was called from, relative to `actor`. A
+// kita is left to the caller.
+fn decode_meld(actor: u8, m: i64, aka: bool) -> Result<Event> {
+    let target = (actor + (m & 3) as u8) % 4;
+    let ids = |ids: &[i64]| -> Result<Vec<Tile>> { ids.iter().map(|id| tile(*id, aka)).collect() };
+    if m & 0x4 != 0 {
+        let t = (m & 0xFC00) >> 10;
+        let (called, t) = ((t % 3) as usize, t / 3);
+        let base = (t / 7) * 9 + t % 7;
+        let mut tiles = ids(&[
+            base * 4 + ((m >> 3) & 3),
+            (base + 1) * 4 + ((m >> 5) & 3),
+            (base + 2) * 4 + ((m >> 7) & 3),
+        ])?;
+        let pai = tiles.remove(called);
+        return Ok(Event::Chi {
+            actor,
+            target,
+            pai,
+            consumed: [tiles[0], tiles[1]],
+        });
+    }
+    if m & 0x18 != 0 {
+        let unused = (m >> 5) & 3;
+        let t = (m & 0xFE00) >> 9;
+        let (called, kind) = ((t % 3) as usize, t / 3);
+        let mut tiles = ids(&(0..4).filter(|i| *i != unused).map(|i| kind * 4 + i).collect::<Vec<_>>())?;
+        if m & 0x10 != 0 {
+            return Ok(Event::Kakan {
+                actor,
+                pai: tile(kind * 4 + unused, aka)?,
+                consumed: [tiles[0], tiles[1], tiles[2]],
+            });
+        }
+        let pai = tiles.remove(called);
+        return Ok(Event::Pon {
+            actor,
+            target,
+            pai,
+            consumed: [tiles[0], tiles[1]],
+        });
+    }
+    let called = (m & 0xFF00) >> 8;
+    let kind = called / 4;
+    if target == actor {
+        let tiles = ids(&[kind * 4, kind * 4 + 1, kind * 4 + 2, kind * 4 + 3])?;
+        return Ok(Event::Ankan {
+            actor,
+            consumed: [tiles[0], tiles[1], tiles[2], tiles[3]],
+        });
+    }
+    let tiles = ids(&(0..4).map(|i| kind * 4 + i).filter(|id| *id != called).collect::<Vec<_>>())?;
+    Ok(Event::Daiminkan {
+        actor,
+        target,
+        pai: tile(called, aka)?,
+        consumed: [tiles[0], tiles[1], tiles[2]],
+    })
+}
+
+// "s0,d0,s1,d1,.." in hundreds of points
+fn deltas(element: &Element) -> Result<[i32; 4]> {
+    let sc = element.numbers("sc")?;
+    ensure!(sc.len() >= 8, "{} sc must have a score and a delta per seat", element.name);
+    Ok([sc[1], sc[3], sc[5], sc[7]].map(|delta| delta as i32 * 100))
+}
+
+fn seat(element: &Element, key: &str) -> Result<u8> {
+    let seat = element.number(key)?;
+    ensure!((0..4).contains(&seat), "incorrect {} {key} {seat}", element.name);
+    Ok(seat as u8)
+}
+
+fn draw_seat(name: &str, letters: &str) -> Option<(u8, i64)> {
+    let mut chars = name.chars();
+    let seat = letters.find(chars.next()?)? as u8;
+    let id = chars.as_str().parse().ok()?;
+    Some((seat, id))
+}
+
+pub(crate) fn mjlog_to_mjai(xml: &str) -> Result<Vec<Event>> {
+    let elements = elements(xml)?;
+    ensure!(
+        elements.iter().any(|element| element.name == "mjloggm"),
+        "not an mjlog, expected <mjloggm>"
+    );
+    // the 0x2 bit of the lobby type is set for games without red fives
+    let aka = match elements.iter().find(|element| element.name == "GO") {
+        Some(go) => go.number("type")? & 0x2 == 0,
+        None => true,
+    };
+    let mut names: [String; 4] = Default::default();
+    if let Some(un) = elements.iter().find(|element| element.name == "UN") {
+        for (seat, name) in names.iter_mut().enumerate() {
+            *name = decode_percent(un.get(&format!("n{seat}")).unwrap_or_default());
+        }
+    }
+
+    let mut events = vec![Event::StartGame {
+        names,
+        kyoku_first: 0,
+        aka_flag: aka,
+        id: None,
+    }];
+    let mut in_kyoku = false;
+    let mut last_draw: [Option<i64>; 4] = [None; 4];
+    for element in &elements {
+        match element.name {
+            "INIT" => {
+                if in_kyoku {
+                    events.push(Event::EndKyoku);
+                }
+                in_kyoku = true;
+                last_draw = [None; 4];
+                let seed = element.numbers("seed")?;
+                ensure!(
+                    seed.len() == 6,
+                    "INIT seed must be kyoku, honba, kyotaku, two dice and the dora"
+                );
+                let mut ten = element.numbers("ten")?;
+                ensure!(
+                    matches!(ten.len(), 3 | 4),
+                    "INIT ten must have a score per seat of three or four players"
+                );
+                ten.resize(4, 0);
+                let mut tehais = [[Tile::default(); 13]; 4];
+                for (seat, tehai) in tehais.iter_mut().enumerate() {
+                    let hai = element.numbers(&format!("hai{seat}"))?;
+                    // the empty seat of a three player game, see sanma::is_sanma
+                    if seat == 3 && hai.is_empty() {
+                        tehai.fill(Tile::new_unchecked(tu8!(?)));
+                        ten[3] = 0;
+                        continue;
+                    }
+                    ensure!(hai.len() == 13, "INIT hai{seat} has {} tiles", hai.len());
+                    for (slot, id) in tehai.iter_mut().zip(hai) {
+                        *slot = tile(id, aka)?;
+                    }
+                }
+                ensure!((0..16).contains(&seed[0]), "incorrect INIT kyoku {}", seed[0]);
+                events.push(Event::StartKyoku {
+                    bakaze: Tile::new_unchecked(tu8!(E) + (seed[0] / 4) as u8),
+                    dora_marker: tile(seed[5], aka)?,
+                    kyoku: (seed[0] % 4) as u8 + 1,
+                    honba: seed[1] as u8,
+                    kyotaku: seed[2] as u8,
+                    oya: seat(element, "oya")?,
+                    scores: [ten[0], ten[1], ten[2], ten[3]].map(|score| score as i32 * 100),
+                    tehais,
+                });
+            }
+            "N" => {
+                let actor = seat(element, "who")?;
+                let m = element.number("m")?;
+                // a kita sets the N it just drew aside, which never enters the hand as in nuki.rs
+                if m & 0x20 != 0 {
+                    let drawn = matches!(events.last(), Some(Event::Tsumo { actor: drawer, .. }) if *drawer == actor);
+                    ensure!(
+                        drawn && last_draw[actor as usize] == Some(m >> 8) && tile(m >> 8, aka)?.as_u8() == tu8!(N),
+                        "seat {actor} sets aside an N it did not just draw, which is not supported"
+                    );
+                    events.pop();
+                    events.push(Event::None);
+                } else {
+                    events.push(decode_meld(actor, m, aka)?);
+                }
+                last_draw[actor as usize] = None;
+            }
+            "REACH" => {
+                let actor = seat(element, "who")?;
+                match element.number("step")? {
+                    1 => events.push(Event::Reach { actor }),
+                    _ => events.push(Event::ReachAccepted { actor }),
+                }
+            }
+            "DORA" => events.push(Event::Dora {
+                dora_marker: tile(element.number("hai")?, aka)?,
+            }),
+            "AGARI" => {
+                let ura = element.numbers("doraHaiUra")?;
+                events.push(Event::Hora {
+                    actor: seat(element, "who")?,
+                    target: seat(element, "fromWho")?,
+                    deltas: Some(deltas(element)?),
+                    ura_markers: if ura.is_empty() {
+                        None
+                    } else {
+                        Some(ura.into_iter().map(|id| tile(id, aka)).collect::<Result<_>>()?)
+                    },
+                });
+            }
+            "RYUUKYOKU" => events.push(Event::Ryukyoku {
+                deltas: Some(deltas(element)?),
+            }),
+            name => {
+                if let Some((actor, id)) = draw_seat(name, "TUVW") {
+                    last_draw[actor as usize] = Some(id);
+                    events.push(Event::Tsumo {
+                        actor,
+                        pai: tile(id, aka)?,
+                    });
+                } else if let Some((actor, id)) = draw_seat(name, "DEFG") {
+                    let tsumogiri = last_draw[actor as usize].take() == Some(id);
+                    events.push(Event::Dahai {
+                        actor,
+                        pai: tile(id, aka)?,
+                        tsumogiri,
+                    });
+                }
+            }
+        }
+    }
+    ensure!(in_kyoku, "mjlog has no kyokus");
+    events.push(Event::EndKyoku);
+    events.push(Event::EndGame);
+    Ok(events)
+}
+
+// Hands out ids for the tiles a log shows, reusing the id a tile already has when it moves on.
+struct Ids {
+    aka: bool,
+    free: Vec<Vec<i64>>,
+    hands: [Vec<(Tile, i64)>; 4],
+}
+
+impl Ids {
+    fn new(aka: bool) -> Self {
+        Self {
+            aka,
+            free: (0..34).map(|kind| (kind * 4..kind * 4 + 4).collect()).collect(),
+            hands: Default::default(),
+        }
+    }
+
+    fn alloc(&mut self, tile: Tile) -> Result<i64> {
+        let kind = tile.deaka().as_usize();
+        let five = matches!(tile.deaka().as_u8(), tu8!(5m) | tu8!(5p) | tu8!(5s));
+        // the red five is the first id of its kind
+        let matching = |id: &i64| !(self.aka && five) || (id % 4 == 0) == tile.is_aka();
+        let i = self.free[kind]
+            .iter()
+            .position(matching)
+            .with_context(|| format!("the log shows more than four {tile}"))?;
+        Ok(self.free[kind].remove(i))
+    }
+
+    fn draw(&mut self, seat: u8, tile: Tile) -> Result<i64> {
+        let id = self.alloc(tile)?;
+        self.hands[seat as usize].push((tile, id));
+        Ok(id)
+    }
+
+    // `latest` takes the most recently drawn copy, as for tsumogiri
+    fn take(&mut self, seat: u8, tile: Tile, latest: bool) -> Result<i64> {
+        let hand = &mut self.hands[seat as usize];
+        let mut matching = hand.iter().enumerate().filter(|(_, (held, _))| *held == tile).map(|(i, _)| i);
+        let i = if latest { matching.next_back() } else { matching.next() };
+        let i = i.with_context(|| format!("seat {seat} does not hold {tile}"))?;
+        Ok(hand.remove(i).1)
+    }
+}
+
+fn ids_string(ids: &[i64]) -> String {
+    ids.iter().map(i64::to_string).collect::<Vec<_>>().join(",")
+}
+
+fn sc_string(scores: &[i32; 4], deltas: &[i32; 4]) -> String {
+    let sc: Vec<String> = scores
+        .iter()
+        .zip(deltas)
+        .flat_map(|(score, delta)| [(score / 100).to_string(), (delta / 100).to_string()])
+        .collect();
+    sc.join(",")
+}
+
+// Chi and pon record which of their tiles was called, counting in id order.
+fn called_index(called: i64, consumed: &[i64]) -> i64 {
+    consumed.iter().filter(|id| **id < called).count() as i64
+}
+
+// The mjlog xml of a mjai log. Fu, han and yaku are not part of a mjai log, so AGARI only carries the hand,
+// the indicators and the score changes; its `ten` holds the winner's gain. `flags` are the <GO> type flags of
+// the rules, the red five flag following the log's own. With `reveal` every RYUUKYOKU shows all four hands,
+// the way tenhou shows the tenpai ones. The last RYUUKYOKU gets the `abortive` type, e.g. "yao9", since mjai
+// does not tell an abortive draw from an exhaustive one.
+pub(crate) fn mjai_to_mjlog(events: &[Event], flags: u32, reveal: bool, abortive: Option<&str>) -> Result<String> {
+    let mut aka = true;
+    let mut header = String::new();
+    let mut elements: Vec<String> = vec![];
+    let mut ids = Ids::new(aka);
+    let mut scores = [0; 4];
+    let (mut honba, mut kyotaku) = (0, 0);
+    let mut last_draw: [Option<i64>; 4] = [None; 4];
+    let mut last_discard = None;
+    let mut dora_ids = vec![];
+    // per seat, the pon kind and its m for a later kakan
+    let mut pons: [Vec<(usize, i64)>; 4] = Default::default();
+    let mut last_result = None;
+
+    for (index, event) in events.iter().enumerate() {
+        match event {
+            // the N is drawn and set aside at once, the replacement draw follows
+            Event::None => {
+                if let Some(actor) = kita_seat(events, index) {
+                    let id = ids.alloc(Tile::new_unchecked(tu8!(N)))?;
+                    elements.push(format!("<{}{id}/>", ["T", "U", "V", "W"][actor as usize]));
+                    elements.push(format!("<N who=\"{actor}\" m=\"{}\"/>", (id << 8) | 0x20));
+                }
+            }
+            Event::StartGame { names, aka_flag, .. } => {
+                aka = *aka_flag;
+                let names: Vec<String> = names
+                    .iter()
+                    .enumerate()
+                    .map(|(seat, name)| format!("n{seat}=\"{}\"", crate::url::encode_component(name)))
+                    .collect();
+                header = format!(
+                    "<GO type=\"{}\" lobby=\"0\"/><UN {} dan=\"0,0,0,0\" rate=\"1500.00,1500.00,1500.00,1500.00\" sx=\"C,C,C,C\"/>",
+                    if aka { flags & !0x2 } else { flags | 0x2 },
+                    names.join(" ")
+                );
+            }
+            Event::StartKyoku {
+                bakaze,
+                dora_marker,
+                kyoku,
+                honba: kyoku_honba,
+                kyotaku: kyoku_kyotaku,
+                oya,
+                scores: kyoku_scores,
+                tehais,
+            } => {
+                ids = Ids::new(aka);
+                (honba, kyotaku, scores) = (*kyoku_honba, *kyoku_kyotaku, *kyoku_scores);
+                last_draw = [None; 4];
+                pons = Default::default();
+                dora_ids = vec![ids.alloc(*dora_marker)?];
+                let mut hai = vec![];
+                // the empty seat of a three player game gets an empty hai3
+                for (seat, tehai) in tehais.iter().enumerate() {
+                    let tiles = tehai
+                        .iter()
+                        .filter(|tile| !tile.is_unknown())
+                        .map(|tile| ids.draw(seat as u8, *tile))
+                        .collect::<Result<Vec<_>>>()?;
+                    hai.push(format!("hai{seat}=\"{}\"", ids_string(&tiles)));
+                }
+                if elements.is_empty() {
+                    elements.push(format!("<TAIKYOKU oya=\"{oya}\"/>"));
+                }
+                let round = (bakaze.as_u8() - tu8!(E)) * 4 + kyoku - 1;
+                elements.push(format!(
+                    "<INIT seed=\"{round},{honba},{kyotaku},0,0,{}\" ten=\"{}\" oya=\"{oya}\" {}/>",
+                    dora_ids[0],
+                    ids_string(&scores.map(|score| score as i64 / 100)),
+                    hai.join(" ")
+                ));
+            }
+            Event::Tsumo { actor, pai } => {
+                let id = ids.draw(*actor, *pai)?;
+                last_draw[*actor as usize] = Some(id);
+                elements.push(format!("<{}{id}/>", ["T", "U", "V", "W"][*actor as usize]));
+            }
+            Event::Dahai { actor, pai, tsumogiri } => {
+                let id = ids.take(*actor, *pai, *tsumogiri)?;
+                last_draw[*actor as usize] = None;
+                last_discard = Some(id);
+                elements.push(format!("<{}{id}/>", ["D", "E", "F", "G"][*actor as usize]));
+            }
+            Event::Chi {
+                actor,
+                target,
+                pai,
+                consumed,
+            }
+            | Event::Pon {
+                actor,
+                target,
+                pai,
+                consumed,
+            } => {
+                let called = last_discard
+                    .filter(|id| tile(*id, aka).ok() == Some(*pai))
+                    .context("call of a tile that was not discarded")?;
+                let mut held = vec![];
+                for tile in consumed {
+                    held.push(ids.take(*actor, *tile, false)?);
+                }
+                let rel = ((4 + target - actor) % 4) as i64;
+                let r = called_index(called, &held);
+                let m = if matches!(event, Event::Chi { .. }) {
+                    let mut all = [held[0], held[1], called];
+                    all.sort();
+                    let base = all[0] / 4;
+                    let t = (base / 9) * 7 + base % 9;
+                    ((t * 3 + r) << 10) | 0x4 | ((all[0] % 4) << 3) | ((all[1] % 4) << 5) | ((all[2] % 4) << 7) | rel
+                } else {
+                    let kind = called / 4;
+                    let used = [held[0] % 4, held[1] % 4, called % 4];
+                    let unused = (0..4).find(|offset| !used.contains(offset)).unwrap_or_default();
+                    let m = ((kind * 3 + r) << 9) | 0x8 | (unused << 5) | rel;
+                    pons[*actor as usize].push((kind as usize, m));
+                    m
+                };
+                last_draw[*actor as usize] = None;
+                elements.push(format!("<N who=\"{actor}\" m=\"{m}\"/>"));
+            }
+            Event::Daiminkan {
+                actor, target, consumed, ..
+            } => {
+                let called = last_discard.context("call of a tile that was not discarded")?;
+                for tile in consumed {
+                    ids.take(*actor, *tile, false)?;
+                }
+                let m = (called << 8) | ((4 + target - actor) % 4) as i64;
+                elements.push(format!("<N who=\"{actor}\" m=\"{m}\"/>"));
+            }
+            Event::Ankan { actor, consumed } => {
+                let mut held = vec![];
+                for tile in consumed {
+                    held.push(ids.take(*actor, *tile, false)?);
+                }
+                let m = held.iter().min().copied().unwrap_or_default() << 8;
+                elements.push(format!("<N who=\"{actor}\" m=\"{m}\"/>"));
+            }
+            Event::Kakan { actor, pai, .. } => {
+                let id = ids.take(*actor, *pai, false)?;
+                let pons = &mut pons[*actor as usize];
+                let i = pons
+                    .iter()
+                    .position(|(kind, _)| *kind == pai.deaka().as_usize())
+                    .with_context(|| format!("seat {actor} has no pon of {pai}"))?;
+                let (_, pon_m) = pons.remove(i);
+                // the added tile is the one the pon left out
+                let m = (pon_m & !0x8 & !(3 << 5)) | 0x10 | ((id % 4) << 5);
+                last_discard = Some(id);
+                elements.push(format!("<N who=\"{actor}\" m=\"{m}\"/>"));
+            }
+            Event::Dora { dora_marker } => {
+                let id = ids.alloc(*dora_marker)?;
+                dora_ids.push(id);
+                elements.push(format!("<DORA hai=\"{id}\"/>"));
+            }
+            Event::Reach { actor } => elements.push(format!("<REACH who=\"{actor}\" step=\"1\"/>")),
+            Event::ReachAccepted { actor } => {
+                scores[*actor as usize] -= 1000;
+                kyotaku += 1;
+                elements.push(format!(
+                    "<REACH who=\"{actor}\" ten=\"{}\" step=\"2\"/>",
+                    ids_string(&scores.map(|score| score as i64 / 100))
+                ));
+            }
+            Event::Hora {
+                actor,
+                target,
+                deltas,
+                ura_markers,
+            } => {
+                let deltas = deltas.unwrap_or_default();
+                let mut hai: Vec<i64> = ids.hands[*actor as usize].iter().map(|(_, id)| *id).collect();
+                let machi = if actor == target {
+                    last_draw[*actor as usize].context("tsumo without a draw")?
+                } else {
+                    let id = last_discard.context("ron without a discard")?;
+                    hai.push(id);
+                    id
+                };
+                hai.sort();
+                let ura = ura_markers
+                    .iter()
+                    .flatten()
+                    .map(|tile| ids.alloc(*tile))
+                    .collect::<Result<Vec<_>>>()?;
+                let ura = if ura.is_empty() {
+                    String::new()
+                } else {
+                    format!(" doraHaiUra=\"{}\"", ids_string(&ura))
+                };
+                last_result = Some(elements.len());
+                elements.push(format!(
+                    "<AGARI ba=\"{honba},{kyotaku}\" hai=\"{}\" machi=\"{machi}\" ten=\"0,{},0\" yaku=\"\" doraHai=\"{}\"{ura} who=\"{actor}\" fromWho=\"{target}\" sc=\"{}\"/>",
+                    ids_string(&hai),
+                    deltas[*actor as usize],
+                    ids_string(&dora_ids),
+                    sc_string(&scores, &deltas)
+                ));
+                for (score, delta) in scores.iter_mut().zip(deltas) {
+                    *score += delta;
+                }
+                kyotaku = 0;
+            }
+            Event::Ryukyoku { deltas } => {
+                let deltas = deltas.unwrap_or_default();
+                let hands = if reveal {
+                    let hands: Vec<String> = ids
+                        .hands
+                        .iter()
+                        .enumerate()
+                        .map(|(seat, hand)| {
+                            let mut hai: Vec<i64> = hand.iter().map(|(_, id)| *id).collect();
+                            hai.sort();
+                            format!(" hai{seat}=\"{}\"", ids_string(&hai))
+                        })
+                        .collect();
+                    hands.concat()
+                } else {
+                    String::new()
+                };
+                let last = !events[index + 1..]
+                    .iter()
+                    .any(|event| matches!(event, Event::Ryukyoku { .. }));
+                let kind = match abortive {
+                    Some(kind) if last => format!(" type=\"{kind}\""),
+                    _ => String::new(),
+                };
+                last_result = Some(elements.len());
+                elements.push(format!(
+                    "<RYUUKYOKU{kind} ba=\"{honba},{kyotaku}\" sc=\"{}\"{hands}/>",
+                    sc_string(&scores, &deltas)
+                ));
+                for (score, delta) in scores.iter_mut().zip(deltas) {
+                    *score += delta;
+                }
+            }
+            Event::EndGame => {
+                if let Some(i) = last_result {
+                    let owari: Vec<String> = scores.iter().map(|score| format!("{},0.0", score / 100)).collect();
+                    let element = elements[i].trim_end_matches("/>").to_owned();
+                    elements[i] = format!("{element} owari=\"{}\"/>", owari.join(","));
+                }
+            }
+            _ => {}
+        }
+    }
+    ensure!(elements.len() > 1, "log has no kyokus");
+    Ok(format!(
+        "<mjloggm ver=\"2.3\"><SHUFFLE seed=\"\" ref=\"\"/>{header}{}</mjloggm>",
+        elements.join("")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use riichi::mjai::Event;
+    use riichi::tile::Tile;
+
+    use super::{mjai_to_mjlog, mjlog_to_mjai};
+
+    fn t(tile: &str) -> Tile {
+        tile.parse().unwrap()
+    }
+
+    fn tiles(tiles: &str) -> [Tile; 13] {
+        let tiles: Vec<Tile> = tiles.split(' ').map(t).collect();
+        tiles.try_into().unwrap()
+    }
+
+    fn tsumo(actor: u8, pai: &str) -> Event {
+        Event::Tsumo { actor, pai: t(pai) }
+    }
+
+    fn dahai(actor: u8, pai: &str, tsumogiri: bool) -> Event {
+        Event::Dahai {
+            actor,
+            pai: t(pai),
+            tsumogiri,
+        }
+    }
+
+    // A chi and a pon with red fives among their tiles, an open kan of a red five, a closed kan and an added
+    // kan come back from the mjlog as they went in.
+    #[test]
+    fn calls_round_trip() {
+        let events = vec![
+            Event::StartGame {
+                names: ["A", "B", "C", "D"].map(str::to_owned),
+                kyoku_first: 0,
+                aka_flag: true,
+                id: None,
+            },
+            Event::StartKyoku {
+                bakaze: t("E"),
+                dora_marker: t("9p"),
+                kyoku: 1,
+                honba: 0,
+                kyotaku: 0,
+                oya: 0,
+                scores: [25000; 4],
+                tehais: [
+                    tiles("3m E E E E 1p 1p 1p 2p 2p 2p 3p 3p"),
+                    tiles("4m 5mr 5p 9m 9m 9m 8p 8p 8p 6s 6s 6s 7s"),
+                    tiles("5pr 5p 5p 1s 1s 1s 2s 2s 2s 3s 3s 3s 9s"),
+                    tiles("5sr 5s 5s 4s 4s 4s 7m 7m 7m 8m 8m 8m N"),
+                ],
+            },
+            tsumo(0, "9m"),
+            dahai(0, "3m", false),
+            Event::Chi {
+                actor: 1,
+                target: 0,
+                pai: t("3m"),
+                consumed: [t("4m"), t("5mr")],
+            },
+            dahai(1, "5p", false),
+            Event::Pon {
+                actor: 2,
+                target: 1,
+                pai: t("5p"),
+                consumed: [t("5pr"), t("5p")],
+            },
+            dahai(2, "9s", false),
+            tsumo(3, "2m"),
+            dahai(3, "2m", true),
+            tsumo(0, "5s"),
+            dahai(0, "5s", true),
+            Event::Daiminkan {
+                actor: 3,
+                target: 0,
+                pai: t("5s"),
+                consumed: [t("5sr"), t("5s"), t("5s")],
+            },
+            tsumo(3, "W"),
+            dahai(3, "W", true),
+            tsumo(0, "6m"),
+            Event::Ankan {
+                actor: 0,
+                consumed: [t("E"); 4],
+            },
+            tsumo(0, "6m"),
+            dahai(0, "6m", true),
+            tsumo(2, "C"),
+            Event::Kakan {
+                actor: 2,
+                pai: t("5p"),
+                consumed: [t("5pr"), t("5p"), t("5p")],
+            },
+            tsumo(2, "F"),
+            dahai(2, "F", true),
+            Event::Ryukyoku { deltas: Some([0; 4]) },
+            Event::EndKyoku,
+            Event::EndGame,
+        ];
+        let xml = mjai_to_mjlog(&events, 0x9, false, None).unwrap();
+        assert_eq!(mjlog_to_mjai(&xml).unwrap(), events);
+    }
+
+    // A three player game with a kita, written with an empty hai3, reads back with seat 3 empty.
+    #[test]
+    fn sanma_kita_round_trip() {
+        let events = vec![
+            Event::StartGame {
+                names: ["A", "B", "C", ""].map(str::to_owned),
+                kyoku_first: 0,
+                aka_flag: true,
+                id: None,
+            },
+            Event::StartKyoku {
+                bakaze: t("E"),
+                dora_marker: t("9p"),
+                kyoku: 1,
+                honba: 0,
+                kyotaku: 0,
+                oya: 0,
+                scores: [35000, 35000, 35000, 0],
+                tehais: [
+                    tiles("1m 1m 1m 9m 9m 9m 1p 1p 1p 2p 2p 2p 3p"),
+                    tiles("4p 4p 4p 5p 5p 5p 6p 6p 6p 7p 7p 7p 8p"),
+                    tiles("1s 1s 1s 2s 2s 2s 3s 3s 3s 4s 4s 4s 5s"),
+                    [t("?"); 13],
+                ],
+            },
+            tsumo(0, "9p"),
+            dahai(0, "9p", true),
+            Event::None,
+            tsumo(1, "E"),
+            dahai(1, "E", true),
+            tsumo(2, "S"),
+            dahai(2, "S", true),
+            Event::Ryukyoku { deltas: Some([0; 4]) },
+            Event::EndKyoku,
+            Event::EndGame,
+        ];
+        let xml = mjai_to_mjlog(&events, 0x19, false, None).unwrap();
+        assert!(xml.contains("hai3=\"\""));
+        assert_eq!(mjlog_to_mjai(&xml).unwrap(), events);
+    }
+}
@@ -0,0 +1,94 @@
+use std::str::FromStr;
+
+use anyhow::{ensure, Context, Result};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+use serde_json::Value;
+
+// Mortal's reviews as mjai-reviewer writes them: {"player_id": 2, "mjai_log": [..], "review": {"kyokus": [{"entries":
+// [..]}]}}, with a kyoku per kyoku of the game. An entry is one decision of the reviewed player, taken right after
+// its `last_actor` drew or discarded `tile` with `tiles_left` tiles left in the wall.
+
+pub(crate) fn review_events(review: &Value) -> Result<(Vec<Event>, u8)> {
+    let player_id = review["player_id"]
+        .as_u64()
+        .filter(|player_id| *player_id < 4)
+        .context("incorrect player_id")?;
+    let events: Vec<Event> = serde_json::from_value(review["mjai_log"].clone()).context("incorrect mjai_log")?;
+    Ok((events, player_id as u8))
+}
+
+// Index in the kyoku's `events`, StartKyoku being 0, of the event the `index`-th decision of kyoku `kyoku`
+// answers. A tile drawn or discarded more than once is told apart by the tiles left in the wall.
+pub(crate) fn entry_event(review: &Value, events: &[Event], player_id: u8, kyoku: usize, index: usize) -> Result<usize> {
+    let entry = &review["review"]["kyokus"][kyoku]["entries"][index];
+    ensure!(entry.is_object(), "the review has no entry {index} in kyoku {kyoku}");
+    let last_actor = entry["last_actor"].as_u64().context("incorrect last_actor")? as u8;
+    let tile = Tile::from_str(entry["tile"].as_str().context("incorrect tile")?).context("incorrect tile")?;
+    let tiles_left = entry["tiles_left"].as_i64();
+    let mut wall = 70;
+    let mut candidates = vec![];
+    for (i, event) in events.iter().enumerate() {
+        let (actor, pai) = match event {
+            Event::StartKyoku { .. } => {
+                wall = 70;
+                continue;
+            }
+            Event::Tsumo { actor, pai } => {
+                wall -= 1;
+                if *actor != player_id {
+                    continue;
+                }
+                (*actor, *pai)
+            }
+            Event::Dahai { actor, pai, .. } | Event::Kakan { actor, pai, .. } if *actor != player_id => (*actor, *pai),
+            _ => continue,
+        };
+        if actor == last_actor && pai == tile {
+            candidates.push((i, wall));
+        }
+    }
+    candidates
+        .into_iter()
+        .min_by_key(|(_, wall)| tiles_left.map_or(0, |tiles_left| (wall - tiles_left).abs()))
+        .map(|(i, _)| i)
+        .with_context(|| format!("kyoku {kyoku} has no {tile} of seat {last_actor} for the entry to follow"))
+}
+
+// The kyokus of `events` as Mortal reads them from `player_id`'s seat, each a game of its own: start_game with
+// the player's id, the kyoku with the other seats' haipai and draws hidden as "?", then end_game.
+pub(crate) fn review_input(events: &[Event], player_id: u8) -> Vec<Vec<Event>> {
+    let unknown = Tile::new_unchecked(tu8!(?));
+    let Some(mut start_game) = events.first().cloned() else {
+        return vec![];
+    };
+    if let Event::StartGame { id, .. } = &mut start_game {
+        *id = Some(player_id);
+    }
+    let mut games = vec![];
+    let mut game = vec![];
+    for event in events {
+        let mut event = event.clone();
+        match &mut event {
+            Event::StartGame { .. } | Event::EndGame => continue,
+            Event::StartKyoku { tehais, .. } => {
+                game = vec![start_game.clone()];
+                for (seat, tehai) in tehais.iter_mut().enumerate() {
+                    if seat != player_id as usize {
+                        tehai.fill(unknown);
+                    }
+                }
+            }
+            Event::Tsumo { actor, pai } if *actor != player_id => *pai = unknown,
+            _ => {}
+        }
+        let end = matches!(event, Event::EndKyoku);
+        game.push(event);
+        if end {
+            game.push(Event::EndGame);
+            games.push(std::mem::take(&mut game));
+        }
+    }
+    games
+}
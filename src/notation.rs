@@ -0,0 +1,457 @@
+use std::fmt;
+use std::ops::Range;
+
+use riichi::hand::parse_tile;
+use serde::Serialize;
+
+use crate::{fuuro, pairi, tiles_vec_to_string, RawBoard};
+
+// One token of a RawBoard field. `start` and `end` are byte offsets into the field's string.
+#[derive(Serialize)]
+pub(crate) struct Token {
+    pub start: usize,
+    pub end: usize,
+    // tile, suit, tsumogiri, riichi, sideways_open, sideways_close, source, meld_separator, prefix, wind,
+    // number or error
+    pub kind: &'static str,
+    // what the token was read as: the tile, with "tedashi"/"sideways" notes, or the number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // InvalidTile, InvalidNumber, InvalidWind, InvalidSeat or Syntax
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct FieldTokens {
+    // e.g. "tehai" or "kawa[2]"
+    pub field: String,
+    pub tokens: Vec<Token>,
+}
+
+fn token(start: usize, end: usize, kind: &'static str, value: Option<String>) -> Token {
+    Token {
+        start,
+        end,
+        kind,
+        value,
+        error: None,
+        error_kind: None,
+    }
+}
+
+fn error(start: usize, end: usize, error_kind: &'static str, message: impl Into<String>) -> Token {
+    Token {
+        start,
+        end,
+        kind: "error",
+        value: None,
+        error: Some(message.into()),
+        error_kind: Some(error_kind),
+    }
+}
+
+// The honors 1z to 7z as Japanese players write them.
+const KANJI: [char; 7] = ['東', '南', '西', '北', '白', '發', '中'];
+
+// `raw` as the tile parsers read it: without the separators copied notation has (spaces, commas and pipes),
+// red fives written r5m as 0m, honors written in kanji (東 or 北 for kita, 発 for 發) as 1z to 7z, and with
+// `expand` a run like 12.3m split into one tile each, 1m2m.3m. Also the part of `raw` each byte of the result
+// comes from, to point errors back at what was typed.
+pub(crate) fn plain_notation(raw: &str, expand: bool) -> (String, Vec<Range<usize>>) {
+    // each piece with the source of every byte
+    let mut pieces: Vec<(String, Vec<Range<usize>>)> = vec![];
+    let mut pending: Vec<usize> = vec![];
+    let mut chars = raw.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let source = i..i + c.len_utf8();
+        match c {
+            ' ' | ',' | '|' => {}
+            'r' if chars.peek().is_some_and(|(_, next)| *next == '5') => {
+                chars.next();
+                let red = i..i + 2;
+                pending.extend(expand.then_some(pieces.len()));
+                pieces.push(("0".to_owned(), vec![red]));
+            }
+            '0'..='9' => {
+                pending.extend(expand.then_some(pieces.len()));
+                pieces.push((c.to_string(), vec![source]));
+            }
+            '発' => pieces.push(("6z".to_owned(), vec![source; 2])),
+            _ if KANJI.contains(&c) => {
+                let number = KANJI.iter().position(|kanji| *kanji == c).unwrap_or_default() + 1;
+                pieces.push((format!("{number}z"), vec![source; 2]));
+            }
+            'm' | 'p' | 's' | 'z' if !pending.is_empty() => {
+                for piece in pending.drain(..) {
+                    pieces[piece].0.push(c);
+                    pieces[piece].1.push(source.clone());
+                }
+            }
+            _ => pieces.push((c.to_string(), vec![source; c.len_utf8()])),
+        }
+    }
+    let mut plain = String::new();
+    let mut sources = vec![];
+    for (piece, piece_sources) in pieces {
+        plain.push_str(&piece);
+        sources.extend(piece_sources);
+    }
+    (plain, sources)
+}
+
+// `notation` with its red fives written 0m, as MahjongSoul and most other tools write them, the r5m that
+// plain_notation reads rewritten and everything else kept as typed.
+pub(crate) fn zero_aka(notation: &str) -> String {
+    notation.replace("r5", "0")
+}
+
+// `notation` with its honors in kanji, which plain_notation reads back.
+pub(crate) fn kanji_honors(notation: &str) -> String {
+    let mut out = String::new();
+    let mut numbers = String::new();
+    for c in notation.chars() {
+        match c {
+            '0'..='9' => numbers.push(c),
+            'z' => {
+                for number in numbers.drain(..) {
+                    match number.to_digit(10).filter(|number| (1..=7).contains(number)) {
+                        Some(number) => out.push(KANJI[number as usize - 1]),
+                        None => out.extend([number, 'z']),
+                    }
+                }
+            }
+            _ => {
+                out.push_str(&numbers);
+                numbers.clear();
+                out.push(c);
+            }
+        }
+    }
+    out.push_str(&numbers);
+    out
+}
+
+// The tokens of `raw` read through plain_notation, their offsets back in `raw`.
+fn plain_tokens(raw: &str, expand: bool, tokenize: fn(&str) -> Vec<Token>) -> Vec<Token> {
+    let (plain, sources) = plain_notation(raw, expand);
+    let mut tokens = tokenize(&plain);
+    for token in &mut tokens {
+        let first = sources.get(token.start).map_or(raw.len(), |source| source.start);
+        let last = token.end.checked_sub(1).and_then(|end| sources.get(end));
+        token.start = first;
+        token.end = last.map_or(raw.len(), |source| source.end).max(first);
+    }
+    tokens
+}
+
+fn tile_value(tile_string: &str) -> Result<String, String> {
+    parse_tile(tile_string)
+        .map(|tile| tiles_vec_to_string(&[tile]))
+        .map_err(|e| e.to_string())
+}
+
+// Grouped hands like "123m406p77z" or "ESW", and the hand analyzer's "q=...+555z".
+pub(crate) fn grouped_tokens(raw: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut offset = 0;
+    if let Some(i) = raw.find("q=") {
+        tokens.push(token(0, i + 2, "prefix", None));
+        offset = i + 2;
+    }
+    let mut digits: Vec<(usize, char)> = vec![];
+    for (i, c) in raw[offset..].char_indices().map(|(i, c)| (i + offset, c)) {
+        match c {
+            '0'..='9' => digits.push((i, c)),
+            'm' | 'p' | 's' | 'z' => {
+                if digits.is_empty() {
+                    tokens.push(error(i, i + 1, "Syntax", format!("suit {c} has no numbers before it")));
+                    continue;
+                }
+                for (start, digit) in digits.drain(..) {
+                    match tile_value(&format!("{digit}{c}")) {
+                        Ok(tile) => tokens.push(token(start, start + 1, "tile", Some(tile))),
+                        Err(e) => tokens.push(error(start, start + 1, "InvalidTile", e)),
+                    }
+                }
+                tokens.push(token(i, i + 1, "suit", None));
+            }
+            'E' | 'S' | 'W' | 'N' | 'P' | 'F' | 'C' => match tile_value(&c.to_string()) {
+                Ok(tile) => tokens.push(token(i, i + 1, "tile", Some(tile))),
+                Err(e) => tokens.push(error(i, i + 1, "InvalidTile", e)),
+            },
+            '+' => tokens.push(token(i, i + 1, "meld_separator", None)),
+            ' ' => {}
+            _ => tokens.push(error(i, i + c.len_utf8(), "Syntax", format!("unexpected {c:?}"))),
+        }
+    }
+    for (start, _) in digits {
+        tokens.push(error(start, start + 1, "Syntax", "number without a suit"));
+    }
+    tokens
+}
+
+// Two characters per tile, then "." for tsumogiri, "-" for a tsumogiri riichi declaration or "!" for a tedashi one.
+pub(crate) fn kawa_tokens(raw: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let chars: Vec<(usize, char)> = raw.char_indices().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = chars[i].0;
+        let Some(&(second, c)) = chars.get(i + 1) else {
+            tokens.push(error(start, raw.len(), "Syntax", "incomplete tile"));
+            break;
+        };
+        let end = second + c.len_utf8();
+        let marker = chars.get(i + 2).map(|(_, c)| *c);
+        let note = match marker {
+            Some('.' | '-') => "",
+            _ => " tedashi",
+        };
+        match tile_value(&raw[start..end]) {
+            _ if &raw[start..end] == "??" => tokens.push(token(start, end, "unknown", Some(format!("unknown tile{note}")))),
+            Ok(tile) => tokens.push(token(start, end, "tile", Some(format!("{tile}{note}")))),
+            Err(e) => tokens.push(error(start, end, "InvalidTile", e)),
+        }
+        i += 2;
+        match marker {
+            Some('.') => tokens.push(token(end, end + 1, "tsumogiri", None)),
+            Some('-' | '!') => tokens.push(token(end, end + 1, "riichi", None)),
+            _ => continue,
+        }
+        i += 1;
+    }
+    tokens
+}
+
+// Two characters per tile, sideways tiles in parentheses and the seat they came from after @.
+pub(crate) fn fuuro_tokens(raw: &str) -> Vec<Token> {
+    if pairi::is_pairi(raw) {
+        return grouped_tokens(raw);
+    }
+    let mut tokens = vec![];
+    let chars: Vec<(usize, char)> = raw.char_indices().collect();
+    let mut in_parentheses = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let (start, c) = chars[i];
+        match c {
+            '(' => {
+                if in_parentheses {
+                    tokens.push(error(start, start + 1, "Syntax", "nested opening parenthesis"));
+                } else {
+                    tokens.push(token(start, start + 1, "sideways_open", None));
+                }
+                in_parentheses = true;
+                i += 1;
+            }
+            '@' => {
+                let end = chars[i..].iter().find(|(_, c)| *c == ')').map_or(raw.len(), |(end, _)| *end);
+                match fuuro::source_seat(&raw[start + 1..end]) {
+                    Ok(seat) => tokens.push(token(start, end, "source", Some(format!("called from seat {seat}")))),
+                    Err(e) => tokens.push(error(start, end, "InvalidSeat", e.to_string())),
+                }
+                i += raw[start..end].chars().count();
+            }
+            ')' => {
+                if in_parentheses {
+                    tokens.push(token(start, start + 1, "sideways_close", None));
+                } else {
+                    tokens.push(error(start, start + 1, "Syntax", "extra closing parenthesis"));
+                }
+                in_parentheses = false;
+                i += 1;
+            }
+            _ => {
+                let Some(&(second, c)) = chars.get(i + 1) else {
+                    tokens.push(error(start, raw.len(), "Syntax", "incomplete tile"));
+                    break;
+                };
+                let end = second + c.len_utf8();
+                let note = if in_parentheses { " sideways" } else { "" };
+                match tile_value(&raw[start..end]) {
+                    _ if &raw[start..end] == "??" => {
+                        tokens.push(token(start, end, "unknown", Some(format!("unknown tile{note}"))))
+                    }
+                    Ok(tile) => tokens.push(token(start, end, "tile", Some(format!("{tile}{note}")))),
+                    Err(e) => tokens.push(error(start, end, "InvalidTile", e)),
+                }
+                i += 2;
+            }
+        }
+    }
+    tokens
+}
+
+fn number_tokens(raw: &str) -> Vec<Token> {
+    if raw.is_empty() {
+        return vec![];
+    }
+    match raw.parse::<i64>() {
+        Ok(number) => vec![token(0, raw.len(), "number", Some(number.to_string()))],
+        Err(e) => vec![error(0, raw.len(), "InvalidNumber", e.to_string())],
+    }
+}
+
+fn wind_token(raw: &str, start: usize, end: usize) -> Token {
+    match &raw[start..end] {
+        wind @ ("E" | "S" | "W" | "N") => token(start, end, "wind", Some(wind.to_owned())),
+        other => error(
+            start,
+            end,
+            "InvalidWind",
+            format!("{other:?} is not a wind, expected E, S, W or N"),
+        ),
+    }
+}
+
+// "S3": the round wind and the kyoku number
+fn kyoku_tokens(raw: &str) -> Vec<Token> {
+    match raw.char_indices().nth(1) {
+        Some((i, _)) => {
+            let mut tokens = vec![wind_token(raw, 0, i)];
+            tokens.extend(number_tokens(&raw[i..]).into_iter().map(|mut token| {
+                token.start += i;
+                token.end += i;
+                token
+            }));
+            tokens
+        }
+        None if raw.is_empty() => vec![],
+        None => vec![error(0, raw.len(), "Syntax", "kyoku must be <bakaze><kyoku> (e.g. S3)")],
+    }
+}
+
+// single digit seats from `first` to 3: opponents in fill_genbutsu and bots, any seat in deposits
+fn seat_tokens(raw: &str, first: char) -> Vec<Token> {
+    raw.char_indices()
+        .map(|(i, c)| match c {
+            c if (first..='3').contains(&c) => token(i, i + 1, "number", Some(c.to_string())),
+            _ => error(i, i + c.len_utf8(), "InvalidSeat", format!("seats are {first} to 3")),
+        })
+        .collect()
+}
+
+// For every field of the board, its tokens and how each one was read, for syntax highlighting.
+pub(crate) fn explain(board: &RawBoard) -> Vec<FieldTokens> {
+    let field = |name: &str, tokens: Vec<Token>| FieldTokens {
+        field: name.to_owned(),
+        tokens,
+    };
+    let mut fields = vec![
+        field("kyoku", kyoku_tokens(&board.kyoku)),
+        field(
+            "jikaze",
+            if board.jikaze.is_empty() {
+                vec![]
+            } else {
+                vec![wind_token(&board.jikaze, 0, board.jikaze.len())]
+            },
+        ),
+        field("kyotaku", number_tokens(&board.kyotaku)),
+        field("honba", number_tokens(&board.honba)),
+        field("dora", plain_tokens(&board.dora, false, grouped_tokens)),
+        field("tehai", plain_tokens(&board.tehai, false, grouped_tokens)),
+        field("tsumo", plain_tokens(&board.tsumo, false, grouped_tokens)),
+    ];
+    for (seat, score) in board.scores.iter().enumerate() {
+        fields.push(field(&format!("scores[{seat}]"), number_tokens(score)));
+    }
+    for (seat, kawa) in board.kawa.iter().enumerate() {
+        fields.push(field(&format!("kawa[{seat}]"), plain_tokens(kawa, true, kawa_tokens)));
+    }
+    for (seat, fuuro) in board.fuuro.iter().enumerate() {
+        fields.push(field(&format!("fuuro[{seat}]"), plain_tokens(fuuro, false, fuuro_tokens)));
+    }
+    for (seat, nuki) in board.nuki.iter().enumerate() {
+        fields.push(field(&format!("nuki[{seat}]"), plain_tokens(nuki, false, grouped_tokens)));
+    }
+    for (seat, draws) in board.draws.iter().enumerate() {
+        fields.push(field(&format!("draws[{seat}]"), plain_tokens(draws, false, grouped_tokens)));
+    }
+    fields.extend([
+        field("junme", number_tokens(&board.junme)),
+        field("chiicha", number_tokens(&board.chiicha)),
+        field("claim", number_tokens(&board.claim)),
+        field("fill_avoid", plain_tokens(&board.fill_avoid, false, grouped_tokens)),
+        field("fill_genbutsu", seat_tokens(&board.fill_genbutsu, '1')),
+        field("bots", seat_tokens(&board.bots, '1')),
+        field("players", number_tokens(&board.players)),
+        field("uradora", plain_tokens(&board.uradora, false, grouped_tokens)),
+        field("deposits", seat_tokens(&board.deposits, '0')),
+    ]);
+    fields
+}
+
+// Why a board could not be read. `field` is the field at fault and `offset` (a byte offset into it) the first
+// character there the notation rejects; a field that reads but cannot be has no offset, and an impossible board
+// no single field is to blame for only has the message.
+#[derive(Serialize, Debug)]
+pub struct BoardError {
+    pub field: Option<String>,
+    pub offset: Option<usize>,
+    // an error_kind of the token, or Inconsistent when no token is at fault
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.field, self.offset) {
+            (Some(field), Some(offset)) => write!(f, "{field} at {offset}: {}", self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+// The field of `fields` an error is about, from the "incorrect <field>" context the parser gives it: the seat
+// it names, or without one the first seat whose notation is rejected.
+fn failed_field(fields: Vec<FieldTokens>, error: &anyhow::Error) -> Option<FieldTokens> {
+    let name = error
+        .chain()
+        .find_map(|cause| cause.to_string().strip_prefix("incorrect ").map(str::to_owned))?;
+    let name = match name.as_str() {
+        "bakaze" => "kyoku",
+        name => name,
+    };
+    let seats = |field: &FieldTokens| field.field.split('[').next() == Some(name);
+    let rejected = |field: &FieldTokens| field.tokens.iter().any(|token| token.error.is_some());
+    let mut fields = fields.into_iter().filter(|field| field.field == name || seats(field));
+    let first = fields.next()?;
+    if first.field == name || rejected(&first) {
+        return Some(first);
+    }
+    Some(fields.find(rejected).unwrap_or(first))
+}
+
+impl BoardError {
+    pub(crate) fn locate(board: &RawBoard, error: &anyhow::Error) -> Self {
+        let Some(field) = failed_field(explain(board), error) else {
+            return BoardError {
+                field: None,
+                offset: None,
+                kind: "Inconsistent",
+                message: format!("{error:#}"),
+            };
+        };
+        match field.tokens.into_iter().find(|token| token.error.is_some()) {
+            Some(token) => BoardError {
+                field: Some(field.field),
+                offset: Some(token.start),
+                kind: token.error_kind.unwrap_or("Syntax"),
+                message: token.error.unwrap_or_default(),
+            },
+            // the notation reads but what it says cannot be
+            None => BoardError {
+                field: Some(field.field),
+                offset: None,
+                kind: "Inconsistent",
+                message: format!("{error:#}"),
+            },
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{ensure, Result};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+use serde::Serializer;
+use serde_json::{json, Value};
+
+// libriichi's mjai events have no kita, so a kita is kept as an Event::None right before the replacement draw
+// of the seat that set the N aside. The N it drew never enters its hand, which keeps every replay of the events
+// as it is; the outputs write the draw of the N and the kita in its place.
+
+// The seat setting a kita aside at `index`, when the event there is one.
+pub(crate) fn kita_seat(events: &[Event], index: usize) -> Option<u8> {
+    match (&events[index], events.get(index + 1)) {
+        (Event::None, Some(Event::Tsumo { actor, .. })) => Some(*actor),
+        _ => None,
+    }
+}
+
+// The index and seat of every kita of `events`.
+pub(crate) fn kitas(events: &[Event]) -> Vec<(usize, u8)> {
+    (0..events.len())
+        .filter_map(|index| kita_seat(events, index).map(|seat| (index, seat)))
+        .collect()
+}
+
+// `events` without the kita, for the conversions that do not know them.
+pub(crate) fn without_kita(events: &[Event]) -> Vec<Event> {
+    let kitas = kitas(events);
+    events
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !kitas.iter().any(|(kita, _)| kita == index))
+        .map(|(_, event)| event.clone())
+        .collect()
+}
+
+// Whether the draw at `index` replaces a kan.
+fn after_kan(events: &[Event], index: usize) -> bool {
+    events[..index]
+        .iter()
+        .rev()
+        .find(|event| !matches!(event, Event::Dora { .. }))
+        .is_some_and(|event| matches!(event, Event::Daiminkan { .. } | Event::Ankan { .. } | Event::Kakan { .. }))
+}
+
+// Sets `count` kita aside for the absolute `seat`, one on each of its first turns.
+pub(crate) fn add_kita(events: &mut Vec<Event>, seat: u8, count: u8) -> Result<()> {
+    let draws: Vec<usize> = (0..events.len())
+        .filter(|index| matches!(events[*index], Event::Tsumo { actor, .. } if actor == seat))
+        .filter(|index| !after_kan(events, *index))
+        .take(count as usize)
+        .collect();
+    ensure!(
+        draws.len() == count as usize,
+        "seat {seat} has {count} kita but draws only {} times",
+        draws.len()
+    );
+    for index in draws.into_iter().rev() {
+        events.insert(index, Event::None);
+    }
+    Ok(())
+}
+
+// The mjai json of `events`, a kita written as the draw of its N and a nukidora event.
+pub(crate) fn mjai_values(events: &[Event]) -> serde_json::Result<Vec<Value>> {
+    let mut values = vec![];
+    for (index, event) in events.iter().enumerate() {
+        let Some(actor) = kita_seat(events, index) else {
+            values.push(serde_json::to_value(event)?);
+            continue;
+        };
+        // a draw nobody else sees stays hidden, as in mortal's review input
+        let pai = match &events[index + 1] {
+            Event::Tsumo { pai, .. } if pai.is_unknown() => *pai,
+            _ => Tile::new_unchecked(tu8!(N)),
+        };
+        values.push(serde_json::to_value(Event::Tsumo { actor, pai })?);
+        values.push(json!({ "type": "nukidora", "actor": actor, "pai": "N" }));
+    }
+    Ok(values)
+}
+
+// GenerateResult.mjai_log as mjai_values writes it.
+pub(crate) fn serialize_events<S: Serializer>(events: &[Event], serializer: S) -> Result<S::Ok, S::Error> {
+    let values = mjai_values(events).map_err(serde::ser::Error::custom)?;
+    serializer.collect_seq(values)
+}
+
+// Writes each kita into the tenhou/6 json: the N among the seat's takes and "f44" among its discards.
+pub(crate) fn set_tenhou_kita(log: &mut Value, events: &[Event]) {
+    let mut kyoku = None;
+    let mut takes = [0; 4];
+    for (index, event) in events.iter().enumerate() {
+        match event {
+            Event::StartKyoku { .. } => {
+                kyoku = Some(kyoku.map_or(0, |kyoku| kyoku + 1));
+                takes = [0; 4];
+            }
+            Event::Tsumo { actor, .. } | Event::Chi { actor, .. } | Event::Pon { actor, .. } | Event::Daiminkan { actor, .. } => {
+                takes[*actor as usize] += 1
+            }
+            _ => {}
+        }
+        let (Some(seat), Some(kyoku)) = (kita_seat(events, index), kyoku) else {
+            continue;
+        };
+        let seat = seat as usize;
+        for (entry, value) in [(5, json!(44)), (6, json!("f44"))] {
+            if let Some(entries) = log["log"][kyoku][entry + seat * 3].as_array_mut() {
+                let at = takes[seat].min(entries.len());
+                entries.insert(at, value);
+            }
+        }
+        takes[seat] += 1;
+    }
+}
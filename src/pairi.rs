@@ -0,0 +1,42 @@
+use anyhow::{bail, Context, Result};
+use riichi::hand::parse_tiles;
+
+use crate::tiles_vec_to_string;
+
+// Hand strings from tenhou's hand analyzer, e.g. "q=23406m123p+555z" or the whole
+// "https://tenhou.net/2/?q=..." url: grouped digits, 1-7z honors, 0 for red fives and called sets after `+`.
+
+fn strip_query(raw: &str) -> &str {
+    match raw.split_once("q=") {
+        Some((_, query)) => query.split('&').next().unwrap_or_default(),
+        None => raw,
+    }
+}
+
+// Our fuuro strings alternate one digit and one suit, so two digits in a row or a `+` mean pairi.
+pub(crate) fn is_pairi(raw: &str) -> bool {
+    raw.contains("q=") || raw.contains('+') || raw.as_bytes().windows(2).any(|pair| pair.iter().all(u8::is_ascii_digit))
+}
+
+// "q=123m456p+555z+7777s" -> ("123m456p", ["555z", "7777s"])
+pub(crate) fn split_hand(raw: &str) -> (&str, Vec<&str>) {
+    let mut parts = strip_query(raw).split('+');
+    let hand = parts.next().unwrap_or_default();
+    (hand, parts.filter(|part| !part.is_empty()).collect())
+}
+
+// The analyzer does not say who a set was called from, so sets of three are written as called from kamicha
+// (first tile sideways) and sets of four as ankan.
+pub(crate) fn meld_to_fuuro(meld: &str) -> Result<String> {
+    let tiles = parse_tiles(meld).with_context(|| format!("incorrect meld {meld:?}"))?;
+    let tiles: Vec<String> = tiles.iter().map(|tile| tiles_vec_to_string(&[*tile])).collect();
+    match tiles.len() {
+        3 => Ok(format!("({}){}{}", tiles[0], tiles[1], tiles[2])),
+        4 => Ok(tiles.concat()),
+        _ => bail!("meld {meld:?} must have 3 or 4 tiles"),
+    }
+}
+
+pub(crate) fn melds_to_fuuro(melds: &[&str]) -> Result<String> {
+    melds.iter().map(|meld| meld_to_fuuro(meld)).collect()
+}
@@ -0,0 +1,46 @@
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+
+// The draws and discards of a log, counting where a generated log reaches the board's decision.
+pub(crate) fn moves(events: &[Event]) -> usize {
+    events
+        .iter()
+        .filter(|event| matches!(event, Event::Tsumo { .. } | Event::Dahai { .. }))
+        .count()
+}
+
+// The log as the player sees it at the decision, the `decision`-th draw or discard: the player's draw or the
+// discard they may call. Everything after it is cut, keeping the closing EndKyoku and EndGame, and the other
+// seats' haipai and draws are hidden like in a review input.
+pub(crate) fn truncate_at_decision(mut events: Vec<Event>, decision: usize, player_id: u8) -> Vec<Event> {
+    let mut seen = 0;
+    let Some(end) = events.iter().position(|event| {
+        seen += matches!(event, Event::Tsumo { .. } | Event::Dahai { .. }) as usize;
+        seen == decision
+    }) else {
+        return events;
+    };
+    let tail: Vec<Event> = events[end + 1..]
+        .iter()
+        .filter(|event| matches!(event, Event::EndKyoku | Event::EndGame))
+        .cloned()
+        .collect();
+    events.truncate(end + 1);
+    events.extend(tail);
+    let unknown = Tile::new_unchecked(tu8!(?));
+    for event in &mut events {
+        match event {
+            Event::StartKyoku { tehais, .. } => {
+                for (seat, tehai) in tehais.iter_mut().enumerate() {
+                    if seat != player_id as usize {
+                        tehai.fill(unknown);
+                    }
+                }
+            }
+            Event::Tsumo { actor, pai } if *actor != player_id => *pai = unknown,
+            _ => {}
+        }
+    }
+    events
+}
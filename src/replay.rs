@@ -0,0 +1,46 @@
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+
+fn remove_tile(hand: &mut Vec<Tile>, tile: Tile) {
+    if let Some(i) = hand.iter().position(|t| *t == tile) {
+        hand.swap_remove(i);
+    }
+}
+
+// Concealed tiles of every seat once the last kyoku in `events` is over.
+pub(crate) fn final_hands(events: &[Event]) -> [Vec<Tile>; 4] {
+    let mut hands: [Vec<Tile>; 4] = Default::default();
+    for event in events {
+        match event {
+            Event::StartKyoku { tehais, .. } => {
+                for (hand, tehai) in hands.iter_mut().zip(tehais) {
+                    *hand = tehai.to_vec();
+                    // the empty seat of a three player game holds nothing
+                    if tehai.iter().all(|tile| tile.is_unknown()) {
+                        hand.clear();
+                    }
+                }
+            }
+            Event::Tsumo { actor, pai } => hands[*actor as usize].push(*pai),
+            Event::Dahai { actor, pai, .. } => remove_tile(&mut hands[*actor as usize], *pai),
+            Event::Chi { actor, consumed, .. } | Event::Pon { actor, consumed, .. } => {
+                for tile in consumed {
+                    remove_tile(&mut hands[*actor as usize], *tile);
+                }
+            }
+            Event::Daiminkan { actor, consumed, .. } => {
+                for tile in consumed {
+                    remove_tile(&mut hands[*actor as usize], *tile);
+                }
+            }
+            Event::Ankan { actor, consumed } => {
+                for tile in consumed {
+                    remove_tile(&mut hands[*actor as usize], *tile);
+                }
+            }
+            Event::Kakan { actor, pai, .. } => remove_tile(&mut hands[*actor as usize], *pai),
+            _ => {}
+        }
+    }
+    hands
+}
@@ -0,0 +1,127 @@
+use riichi::mjai::Event;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::tiles_vec_to_string;
+
+#[derive(Serialize)]
+pub struct Yaku {
+    pub name: String,
+    pub han: u8,
+    pub yakuman: bool,
+}
+
+#[derive(Serialize)]
+pub struct AgariResult {
+    pub winner: u8,
+    // None for tsumo
+    pub loser: Option<u8>,
+    pub winning_tile: Option<String>,
+    pub yaku: Vec<Yaku>,
+    pub han: u8,
+    pub yakuman: u8,
+    pub fu: Option<u8>,
+    // tenhou's score line, e.g. 30符2飜2000点 or 満貫2000-4000点
+    pub points: String,
+    pub deltas: [i32; 4],
+}
+
+// Seats are absolute, like the mjai log.
+#[derive(Serialize)]
+pub struct KyokuResult {
+    pub hora: bool,
+    // tenhou's label for the ending, e.g. 和了, 流局 or 九種九牌
+    pub reason: String,
+    pub agari: Vec<AgariResult>,
+    pub deltas: [i32; 4],
+    pub scores: [i32; 4],
+}
+
+fn deltas_of(value: &Value) -> Option<[i32; 4]> {
+    let array = value.as_array()?;
+    let mut deltas = [0; 4];
+    for (delta, value) in deltas.iter_mut().zip(array) {
+        *delta = value.as_i64()? as i32;
+    }
+    Some(deltas)
+}
+
+// "立直(1飜)" or "国士無双(役満)"
+fn parse_yaku(line: &str) -> Option<Yaku> {
+    let (name, rest) = line.split_once('(')?;
+    let rest = rest.strip_suffix(')')?;
+    if rest.contains("役満") {
+        return Some(Yaku {
+            name: name.to_owned(),
+            han: 13,
+            yakuman: true,
+        });
+    }
+    Some(Yaku {
+        name: name.to_owned(),
+        han: rest.strip_suffix("飜")?.parse().ok()?,
+        yakuman: false,
+    })
+}
+
+// the tile that completed the hand: the draw for tsumo, the discard or added kan tile for ron
+fn winning_tile(events: &[Event]) -> Option<String> {
+    let hora = events.iter().position(|event| matches!(event, Event::Hora { .. }))?;
+    events[..hora].iter().rev().find_map(|event| match event {
+        Event::Tsumo { pai, .. } | Event::Dahai { pai, .. } | Event::Kakan { pai, .. } => Some(tiles_vec_to_string(&[*pai])),
+        _ => None,
+    })
+}
+
+pub(crate) fn kyoku_result(tenhou_log: &Value, events: &[Event]) -> Option<KyokuResult> {
+    let kyoku = tenhou_log["log"].as_array()?.last()?.as_array()?;
+    let start_scores = deltas_of(kyoku.get(1)?)?;
+    let result = kyoku.last()?.as_array()?;
+    let reason = result.first()?.as_str()?.to_owned();
+
+    let mut agari = vec![];
+    let mut deltas = [0; 4];
+    for entry in result[1..].chunks(2) {
+        let entry_deltas = deltas_of(&entry[0])?;
+        for (total, delta) in deltas.iter_mut().zip(entry_deltas) {
+            *total += delta;
+        }
+        let Some(info) = entry.get(1).and_then(Value::as_array) else {
+            continue;
+        };
+        let winner = info.first()?.as_u64()? as u8;
+        let from = info.get(1)?.as_u64()? as u8;
+        let points = info.get(3)?.as_str()?.to_owned();
+        let yaku: Vec<_> = info
+            .get(4..)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(Value::as_str)
+            .filter_map(parse_yaku)
+            .collect();
+        let fu = points.split_once('符').and_then(|(fu, _)| fu.parse().ok());
+        agari.push(AgariResult {
+            winner,
+            loser: (from != winner).then_some(from),
+            winning_tile: winning_tile(events),
+            han: yaku.iter().filter(|yaku| !yaku.yakuman).map(|yaku| yaku.han).sum(),
+            yakuman: yaku.iter().filter(|yaku| yaku.yakuman).count() as u8,
+            yaku,
+            fu,
+            points,
+            deltas: entry_deltas,
+        });
+    }
+
+    let mut scores = start_scores;
+    for (score, delta) in scores.iter_mut().zip(deltas) {
+        *score += delta;
+    }
+    Some(KyokuResult {
+        hora: !agari.is_empty(),
+        reason,
+        agari,
+        deltas,
+        scores,
+    })
+}
@@ -0,0 +1,342 @@
+use anyhow::{bail, ensure, Context, Result};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+use serde_json::Value;
+
+// Riichi City replays as the client downloads them: {"data": {"handRecord": [..]}}, one hand record per kyoku,
+// each {"handEventRecord": [{"eventType": .., "userId": .., "data": ".."}]} where data is json, often as a
+// string. eventType 1 deals the kyoku, 2 is a draw, 3 an action (discard, call or win) and 4 ends the kyoku.
+// Seats are the order of the deal's user_info, which starts from the first dealer of the game, and the names
+// are in data.players as {"userId": .., "nickname": ..}.
+
+// a card is suit << 4 | number, the suits man, pin, sou and honors (ESWN, haku, hatsu, chun), with 0x100 on
+// the red fives
+fn tile(card: &Value) -> Result<Tile> {
+    let card = card.as_u64().context("incorrect card")?;
+    let (red, suit, number) = (card & 0x100 != 0, (card >> 4) & 0xf, card & 0xf);
+    let id = match (suit, number) {
+        (0..=2, 1..=9) => suit * 9 + number - 1,
+        (3, 1..=7) => 27 + number - 1,
+        _ => bail!("incorrect card {card:#x}"),
+    } as u8;
+    match (red, id) {
+        (false, _) => Ok(Tile::new_unchecked(id)),
+        (true, tu8!(5m)) => Ok(Tile::new_unchecked(tu8!(5mr))),
+        (true, tu8!(5p)) => Ok(Tile::new_unchecked(tu8!(5pr))),
+        (true, tu8!(5s)) => Ok(Tile::new_unchecked(tu8!(5sr))),
+        (true, _) => bail!("card {card:#x} is marked red but is not a five"),
+    }
+}
+
+fn tiles(data: &Value, field: &str) -> Result<Vec<Tile>> {
+    match &data[field] {
+        Value::Null => Ok(vec![]),
+        Value::Array(cards) => cards.iter().map(tile).collect(),
+        _ => bail!("incorrect {field}"),
+    }
+}
+
+fn number(data: &Value, field: &str) -> i64 {
+    data[field].as_i64().unwrap_or_default()
+}
+
+fn flag(data: &Value, field: &str) -> bool {
+    data[field].as_bool().unwrap_or_default()
+}
+
+// (eventType, userId, data) of every event of a hand record, the data parsed when it is a json string
+fn hand_events(hand: &Value) -> Result<Vec<(i64, i64, Value)>> {
+    let records = hand["handEventRecord"].as_array().context("incorrect handEventRecord")?;
+    records
+        .iter()
+        .map(|record| {
+            let data = match &record["data"] {
+                Value::String(json) => serde_json::from_str(json).context("incorrect event data")?,
+                data => data.clone(),
+            };
+            Ok((number(record, "eventType"), number(record, "userId"), data))
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Hand {
+    // user id of each seat
+    users: Vec<i64>,
+    hands: [Vec<Tile>; 4],
+    pons: [Vec<[Tile; 3]>; 4],
+    dora_count: usize,
+    // the seat whose discard or added kan tile can be ronned, and that tile
+    last_tile: Option<(u8, Tile)>,
+    pending_reach: Option<u8>,
+}
+
+impl Hand {
+    fn seat(&self, user: i64) -> Result<u8> {
+        let seat = self
+            .users
+            .iter()
+            .position(|id| *id == user)
+            .with_context(|| format!("user {user} is not dealt in"))?;
+        Ok(seat as u8)
+    }
+
+    fn take(&mut self, seat: u8, tile: Tile) -> Result<()> {
+        let hand = &mut self.hands[seat as usize];
+        let i = hand
+            .iter()
+            .position(|held| *held == tile)
+            .with_context(|| format!("seat {seat} does not hold {tile}"))?;
+        hand.remove(i);
+        Ok(())
+    }
+
+    fn new_doras(&mut self, data: &Value, events: &mut Vec<Event>) -> Result<()> {
+        let doras = tiles(data, "dora_cards")?;
+        for dora_marker in doras.iter().skip(self.dora_count) {
+            events.push(Event::Dora {
+                dora_marker: *dora_marker,
+            });
+        }
+        self.dora_count = self.dora_count.max(doras.len());
+        Ok(())
+    }
+
+    fn accept_reach(&mut self, events: &mut Vec<Event>) {
+        if let Some(actor) = self.pending_reach.take() {
+            events.push(Event::ReachAccepted { actor });
+        }
+    }
+}
+
+// The deal events of a kyoku, one per seat with its own hand_cards and the shared round info.
+fn start_kyoku(deals: &[(i64, Value)], hand: &mut Hand, events: &mut Vec<Event>) -> Result<()> {
+    let (_, info) = deals.first().context("hand record without a deal")?;
+    let users = info["user_info"].as_array().context("incorrect user_info")?;
+    ensure!(users.len() == 4, "only four player replays are supported");
+    let oya = number(info, "dealer_pos");
+    ensure!((0..4).contains(&oya), "incorrect dealer_pos {oya}");
+    let bakaze = tile(&info["quan_feng"]).context("incorrect quan_feng")?;
+    ensure!((tu8!(E)..=tu8!(N)).contains(&bakaze.as_u8()), "incorrect quan_feng {bakaze}");
+    let doras = tiles(info, "dora_cards")?;
+    let dora_marker = *doras.first().context("deal without a dora indicator")?;
+
+    *hand = Hand {
+        users: users.iter().map(|user| number(user, "user_id")).collect(),
+        dora_count: doras.len(),
+        ..Default::default()
+    };
+    let mut scores = [0; 4];
+    for (score, user) in scores.iter_mut().zip(users) {
+        *score = number(user, "hand_points") as i32;
+    }
+    let mut tehais = [[Tile::default(); 13]; 4];
+    let mut first_draw = None;
+    for (user, deal) in deals {
+        let seat = hand.seat(*user)? as usize;
+        let mut cards = tiles(deal, "hand_cards")?;
+        // the dealer's fourteenth tile is their first draw
+        if seat == oya as usize && cards.len() == 14 {
+            first_draw = cards.pop();
+        }
+        ensure!(cards.len() == 13, "the deal of seat {seat} has {} tiles", cards.len());
+        tehais[seat].copy_from_slice(&cards);
+        hand.hands[seat] = cards;
+    }
+    ensure!(
+        hand.hands.iter().all(|tiles| !tiles.is_empty()),
+        "the replay does not show every seat's deal"
+    );
+
+    events.push(Event::StartKyoku {
+        bakaze,
+        dora_marker,
+        kyoku: (number(info, "chang_ci") - 1).rem_euclid(4) as u8 + 1,
+        honba: number(info, "ben_chang_num") as u8,
+        kyotaku: number(info, "li_zhi_bang_num") as u8,
+        oya: oya as u8,
+        scores,
+        tehais,
+    });
+    if let Some(pai) = first_draw {
+        hand.hands[oya as usize].push(pai);
+        events.push(Event::Tsumo { actor: oya as u8, pai });
+    }
+    Ok(())
+}
+
+// action 2 chi, 3 pon, 4 daiminkan, 5 ankan, 6 kakan, 9 ron, 10 tsumo and 11 a discard, with the card played
+// and group_cards the held tiles a call uses
+fn action(actor: u8, data: &Value, hand: &mut Hand, events: &mut Vec<Event>) -> Result<()> {
+    let kind = number(data, "action");
+    match kind {
+        11 => {
+            let pai = tile(&data["card"])?;
+            hand.take(actor, pai)?;
+            if flag(data, "is_li_zhi") {
+                events.push(Event::Reach { actor });
+                hand.pending_reach = Some(actor);
+            }
+            events.push(Event::Dahai {
+                actor,
+                pai,
+                tsumogiri: flag(data, "is_mo_qie"),
+            });
+            hand.last_tile = Some((actor, pai));
+        }
+        2..=4 => {
+            let (target, pai) = hand.last_tile.context("call without a discard")?;
+            let consumed = tiles(data, "group_cards")?;
+            for tile in &consumed {
+                hand.take(actor, *tile)?;
+            }
+            hand.accept_reach(events);
+            events.push(match (kind, consumed.as_slice()) {
+                (2, &[a, b]) => Event::Chi {
+                    actor,
+                    target,
+                    pai,
+                    consumed: [a, b],
+                },
+                (3, &[a, b]) => {
+                    hand.pons[actor as usize].push([pai, a, b]);
+                    Event::Pon {
+                        actor,
+                        target,
+                        pai,
+                        consumed: [a, b],
+                    }
+                }
+                (4, &[a, b, c]) => Event::Daiminkan {
+                    actor,
+                    target,
+                    pai,
+                    consumed: [a, b, c],
+                },
+                _ => bail!("incorrect call of action {kind} with {} tiles", consumed.len()),
+            });
+        }
+        5 => {
+            let kan = tile(&data["card"])?;
+            let held = &mut hand.hands[actor as usize];
+            let consumed: Vec<Tile> = held.iter().copied().filter(|tile| tile.deaka() == kan.deaka()).collect();
+            ensure!(consumed.len() == 4, "seat {actor} does not hold four {kan}");
+            held.retain(|tile| tile.deaka() != kan.deaka());
+            events.push(Event::Ankan {
+                actor,
+                consumed: [consumed[0], consumed[1], consumed[2], consumed[3]],
+            });
+        }
+        6 => {
+            let pai = tile(&data["card"])?;
+            let pons = &mut hand.pons[actor as usize];
+            let i = pons
+                .iter()
+                .position(|pon| pon[0].deaka() == pai.deaka())
+                .with_context(|| format!("seat {actor} has no pon of {pai}"))?;
+            let consumed = pons.remove(i);
+            hand.take(actor, pai)?;
+            hand.last_tile = Some((actor, pai));
+            events.push(Event::Kakan { actor, pai, consumed });
+        }
+        // a win, 9 or 10, is read from the end of the kyoku, which has the score
+        _ => {}
+    }
+    hand.new_doras(data, events)
+}
+
+// win_info lists the winners, user_profit the score changes of everyone; the first winner carries them.
+fn end_kyoku(data: &Value, hand: &mut Hand, events: &mut Vec<Event>) -> Result<()> {
+    let mut deltas = [0; 4];
+    for profit in data["user_profit"].as_array().into_iter().flatten() {
+        let seat = hand.seat(number(profit, "user_id"))?;
+        deltas[seat as usize] = number(profit, "point_profit") as i32;
+    }
+    let wins = data["win_info"].as_array().map(Vec::as_slice).unwrap_or_default();
+    if wins.is_empty() {
+        hand.accept_reach(events);
+        events.push(Event::Ryukyoku { deltas: Some(deltas) });
+        return Ok(());
+    }
+    let mut deltas = Some(deltas);
+    for win in wins {
+        let actor = hand.seat(number(win, "user_id"))?;
+        let target = if flag(win, "is_zi_mo") {
+            actor
+        } else {
+            hand.last_tile.context("ron without a discard")?.0
+        };
+        let ura_markers = tiles(win, "li_dora")?;
+        events.push(Event::Hora {
+            actor,
+            target,
+            deltas: Some(deltas.take().unwrap_or_default()),
+            ura_markers: (!ura_markers.is_empty()).then_some(ura_markers),
+        });
+    }
+    Ok(())
+}
+
+// One StartGame, then StartKyoku .. EndKyoku per hand record and an EndGame, the same shape tenhou_to_mjai gives.
+pub(crate) fn riichi_city_to_mjai(replay: &Value) -> Result<Vec<Event>> {
+    let records = [&replay["data"]["handRecord"], &replay["handRecord"], replay]
+        .into_iter()
+        .find_map(Value::as_array)
+        .context("incorrect replay, expected an array of hand records")?;
+    ensure!(!records.is_empty(), "replay has no hand records");
+    let mut events = vec![];
+    let mut hand = Hand::default();
+    for (k, record) in records.iter().enumerate() {
+        let record_events = hand_events(record).with_context(|| format!("hand record {k}"))?;
+        let deals: Vec<(i64, Value)> = record_events
+            .iter()
+            .filter(|(kind, _, _)| *kind == 1)
+            .map(|(_, user, data)| (*user, data.clone()))
+            .collect();
+        let mut kyoku = vec![];
+        start_kyoku(&deals, &mut hand, &mut kyoku).with_context(|| format!("hand record {k}"))?;
+        for (kind, user, data) in &record_events {
+            let step = match kind {
+                2 => {
+                    let actor = hand.seat(*user)?;
+                    let pai = tile(&data["in_card"]).context("incorrect in_card")?;
+                    hand.accept_reach(&mut kyoku);
+                    hand.new_doras(data, &mut kyoku)?;
+                    hand.hands[actor as usize].push(pai);
+                    kyoku.push(Event::Tsumo { actor, pai });
+                    Ok(())
+                }
+                3 => hand.seat(*user).and_then(|actor| action(actor, data, &mut hand, &mut kyoku)),
+                4 => end_kyoku(data, &mut hand, &mut kyoku),
+                _ => Ok(()),
+            };
+            step.with_context(|| format!("hand record {k}, event of type {kind}"))?;
+        }
+        if events.is_empty() {
+            let names = replay["data"]["players"]
+                .as_array()
+                .map(|players| {
+                    let mut names: [String; 4] = Default::default();
+                    for player in players {
+                        if let (Ok(seat), Some(name)) = (hand.seat(number(player, "userId")), player["nickname"].as_str()) {
+                            names[seat as usize] = name.to_owned();
+                        }
+                    }
+                    names
+                })
+                .unwrap_or_default();
+            events.push(Event::StartGame {
+                names,
+                kyoku_first: 0,
+                // every Riichi City ruleset has the red fives
+                aka_flag: true,
+                id: None,
+            });
+        }
+        events.extend(kyoku);
+        events.push(Event::EndKyoku);
+    }
+    events.push(Event::EndGame);
+    Ok(events)
+}
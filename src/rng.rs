@@ -0,0 +1,45 @@
+use anyhow::{anyhow, Result};
+
+// splitmix64: small, fast and reproducible from a seed across platforms
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub(crate) fn from_seed(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) fn from_entropy() -> Result<Self> {
+        Ok(Self::from_seed(
+            getrandom::u64().map_err(|e| anyhow!("no entropy source: {e}"))?,
+        ))
+    }
+
+    // seeded when `seed` is given, random otherwise
+    pub(crate) fn new(seed: Option<u64>) -> Result<Self> {
+        match seed {
+            Some(seed) => Ok(Self::from_seed(seed)),
+            None => Self::from_entropy(),
+        }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in 0..n, n > 0
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    pub(crate) fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
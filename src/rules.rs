@@ -0,0 +1,440 @@
+use std::cmp::Reverse;
+
+use anyhow::{bail, ensure, Result};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::result::KyokuResult;
+use crate::sanma::is_sanma;
+
+// the score that ends a game in its extension rounds
+const TARGET: i32 = 30000;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct RuleConfig {
+    // what each honba is worth: "standard" (300, the default), "real" (1500) or "none"
+    pub honba: String,
+    // red fives, 3 (the default) or 0; libriichi's tiles have one red five per suit at most
+    pub aka: Option<u8>,
+    // open tanyao, on by default
+    pub kuitan: Option<bool>,
+    // when an open kan turns its indicator over: "late", after the kan's discard as on tenhou, or
+    // "immediate", after the replacement draw. Closed kans always reveal immediately. Empty keeps the
+    // generator's order, except that no indicator is turned over before its kan.
+    pub kandora: String,
+    // "multiple" (the default) pays every ron on a discard, "atamahane" only the first in turn order
+    pub ron: String,
+    // who collects honba when several seats ron one discard: "first" (the default), the winner closest to
+    // the discarder as on tenhou, or "each" winner as on MahjongSoul. Kyotaku go to the first either way.
+    pub ron_honba: String,
+    // "tonpuu", "hanchan" or "west" for a hanchan that goes on into West and North while nobody is over the
+    // target. Shown in the rule header, and the board's round must be one the game plays: tonpuu reaches
+    // South at most as its extension, hanchan stops at South, and an extension round is only played while
+    // every score is under the target. The kyoku of a whole game also have to follow its oya rotation.
+    // Empty allows any round and order.
+    pub length: String,
+    // 4 or 3, the game type the rule header and the mjlog's type flags name; it has to be the log's own, which
+    // is the default
+    pub players: Option<u8>,
+    // the rule header's "disp", e.g. "般南喰赤", instead of the one these rules spell out
+    pub disp: String,
+    // the tenhou lobby the log claims to be from, 0 being the public one
+    pub lobby: Option<u32>,
+}
+
+impl RuleConfig {
+    // total a ron loser pays per honba; a tsumo splits it between the three others
+    pub(crate) fn honba_value(&self) -> Result<i32> {
+        match self.honba.as_str() {
+            "" | "standard" => Ok(300),
+            "real" => Ok(1500),
+            "none" => Ok(0),
+            other => bail!("unknown honba scheme {other:?}, expected standard, real or none"),
+        }
+    }
+
+    pub(crate) fn honba_each(&self) -> Result<bool> {
+        match self.ron_honba.as_str() {
+            "" | "first" => Ok(false),
+            "each" => Ok(true),
+            other => bail!("unknown ron_honba rule {other:?}, expected first or each"),
+        }
+    }
+
+    pub(crate) fn aka(&self) -> Result<bool> {
+        match self.aka {
+            None | Some(3) => Ok(true),
+            Some(0) => Ok(false),
+            Some(count) => bail!("{count} red fives cannot be played, it is 0 or 3"),
+        }
+    }
+
+    // the players of a log with `sanma` seats; a header naming other seats than the log has would not load
+    fn players(&self, sanma: bool) -> Result<u8> {
+        let seats = if sanma { 3 } else { 4 };
+        match self.players {
+            None => Ok(seats),
+            Some(count @ (3 | 4)) => {
+                ensure!(count == seats, "the rules are for {count} players, but the log has {seats}");
+                Ok(count)
+            }
+            Some(count) => bail!("a game of {count} players cannot be played, it is 3 or 4"),
+        }
+    }
+
+    // The tenhou/6 "rule" header for these rules and a log with `sanma` seats, None when they are all defaults
+    // of a four player game and the generator's header stands.
+    pub(crate) fn tenhou_rule(&self, sanma: bool) -> Result<Option<Value>> {
+        let defaults = self.aka.is_none() && self.kuitan.is_none() && self.length.is_empty() && self.disp.is_empty();
+        if defaults && self.players(sanma)? == 4 {
+            return Ok(None);
+        }
+        self.last_round()?;
+        let players = if self.players(sanma)? == 3 { "三" } else { "" };
+        let length = if self.length == "tonpuu" { "東" } else { "南" };
+        let aka = self.aka()?;
+        let kuitan = self.kuitan.unwrap_or(true);
+        let disp = if self.disp.is_empty() {
+            format!(
+                "{players}般{length}{}{}",
+                if kuitan { "喰" } else { "" },
+                if aka { "赤" } else { "" }
+            )
+        } else {
+            self.disp.clone()
+        };
+        let aka = aka as u8;
+        Ok(Some(json!({ "disp": disp, "aka53": aka, "aka52": aka, "aka51": aka })))
+    }
+
+    // The type flags of an mjlog's <GO> tag: 0x1 a game between people, 0x2 no red fives, 0x4 no kuitan, 0x8 a
+    // hanchan and 0x10 sanma, for a log with `sanma` seats. Viewers read the game length and who the next oya is
+    // from them.
+    pub(crate) fn mjlog_type(&self, sanma: bool) -> Result<u32> {
+        self.last_round()?;
+        let mut flags = 0x1;
+        if !self.aka()? {
+            flags |= 0x2;
+        }
+        if !self.kuitan.unwrap_or(true) {
+            flags |= 0x4;
+        }
+        if self.length != "tonpuu" {
+            flags |= 0x8;
+        }
+        if self.players(sanma)? == 3 {
+            flags |= 0x10;
+        }
+        Ok(flags)
+    }
+
+    // The last bakaze the game length reaches.
+    fn last_round(&self) -> Result<Tile> {
+        match self.length.as_str() {
+            "" | "west" => Ok(Tile::new_unchecked(tu8!(N))),
+            "hanchan" | "tonpuu" => Ok(Tile::new_unchecked(tu8!(S))),
+            other => bail!("unknown game length {other:?}, expected tonpuu, hanchan or west"),
+        }
+    }
+
+    // With a game length set, each kyoku of `events` after the first has to follow the one before by the
+    // oya rotation: the oya deals again with a honba more when they win or are tenpai at a draw, otherwise the
+    // next seat deals, with the honba reset after a win, and after the last seat the round moves on.
+    pub(crate) fn check_rotation(&self, events: &[Event]) -> Result<()> {
+        if self.length.is_empty() {
+            return Ok(());
+        }
+        let seats = if is_sanma(events) { 3 } else { 4 };
+        let mut expected: Vec<(u8, u8, u8, u8)> = vec![];
+        let mut current = None;
+        let (mut won, mut oya_won, mut oya_tenpai) = (false, false, None);
+        for event in events {
+            match event {
+                Event::StartKyoku {
+                    bakaze,
+                    kyoku,
+                    honba,
+                    oya,
+                    ..
+                } => {
+                    let start = (bakaze.as_u8(), *kyoku, *honba, *oya);
+                    if let (Some(previous), Some(next)) = (current, expected.first()) {
+                        ensure!(
+                            expected.contains(&start),
+                            "{} is followed by {}, the oya rotation deals {} next",
+                            kyoku_name(previous),
+                            kyoku_name(start),
+                            kyoku_name(*next)
+                        );
+                    }
+                    current = Some(start);
+                    (won, oya_won, oya_tenpai) = (false, false, None);
+                }
+                Event::Hora { actor, .. } => {
+                    won = true;
+                    oya_won |= current.is_some_and(|(.., oya)| oya == *actor);
+                }
+                // an oya paid for tenpai is tenpai; no payments leave it open, everyone or no one being tenpai
+                Event::Ryukyoku { deltas } => {
+                    let oya = current.map_or(0, |(.., oya)| oya as usize);
+                    oya_tenpai = deltas
+                        .map(|deltas| deltas[oya])
+                        .filter(|delta| *delta != 0)
+                        .map(|delta| delta > 0);
+                }
+                Event::EndKyoku => {
+                    let Some((bakaze, kyoku, honba, oya)) = current else {
+                        continue;
+                    };
+                    let renchan = match (won, oya_tenpai) {
+                        (true, _) => vec![oya_won],
+                        (false, Some(tenpai)) => vec![tenpai],
+                        (false, None) => vec![true, false],
+                    };
+                    expected = renchan
+                        .into_iter()
+                        .map(|renchan| match renchan {
+                            true => (bakaze, kyoku, honba + 1, oya),
+                            false if kyoku == seats => (bakaze + 1, 1, if won { 0 } else { honba + 1 }, (oya + 1) % seats),
+                            false => (bakaze, kyoku + 1, if won { 0 } else { honba + 1 }, (oya + 1) % seats),
+                        })
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // The first round a game only goes on into while nobody has reached the target.
+    fn extension(&self) -> Option<Tile> {
+        match self.length.as_str() {
+            "tonpuu" => Some(Tile::new_unchecked(tu8!(S))),
+            "west" => Some(Tile::new_unchecked(tu8!(W))),
+            _ => None,
+        }
+    }
+
+    // Everything besides honba these rules change in the generated events.
+    pub(crate) fn apply(&self, events: &mut Vec<Event>) -> Result<()> {
+        let last_round = self.last_round()?;
+        for event in events.iter() {
+            if let Event::StartKyoku { bakaze, scores, .. } = event {
+                ensure!(
+                    bakaze.as_u8() <= last_round.as_u8(),
+                    "a {} game never reaches the {bakaze} round",
+                    self.length
+                );
+                if self.extension().is_some_and(|extension| bakaze.as_u8() >= extension.as_u8()) {
+                    ensure!(
+                        scores.iter().all(|score| *score < TARGET),
+                        "a {} game is over once a seat has {TARGET} after its last regular round, it never reaches {bakaze}",
+                        self.length
+                    );
+                }
+            }
+        }
+        if !self.aka()? {
+            no_aka(events);
+        }
+        let late = match self.kandora.as_str() {
+            "" => None,
+            "late" => Some(true),
+            "immediate" => Some(false),
+            other => bail!("unknown kandora timing {other:?}, expected late or immediate"),
+        };
+        move_kan_dora(events, late)?;
+        match self.ron.as_str() {
+            "" | "multiple" => {}
+            "atamahane" => atamahane(events),
+            other => bail!("unknown ron rule {other:?}, expected multiple or atamahane"),
+        }
+        Ok(())
+    }
+
+    // Without kuitan an open hand needs a yaku besides tanyao and dora.
+    pub(crate) fn check_kuitan(&self, result: &KyokuResult, events: &[Event]) -> Result<()> {
+        if self.kuitan.unwrap_or(true) {
+            return Ok(());
+        }
+        for agari in &result.agari {
+            let open = events.iter().any(|event| match event {
+                Event::Chi { actor, .. } | Event::Pon { actor, .. } | Event::Daiminkan { actor, .. } => *actor == agari.winner,
+                _ => false,
+            });
+            let yaku = agari
+                .yaku
+                .iter()
+                .any(|yaku| !matches!(yaku.name.as_str(), "断幺九" | "ドラ" | "赤ドラ" | "裏ドラ"));
+            ensure!(
+                !open || yaku,
+                "seat {} wins with open tanyao, which has no yaku without kuitan",
+                agari.winner
+            );
+        }
+        Ok(())
+    }
+}
+
+// "E4 1 honba" for a StartKyoku's bakaze, kyoku, honba and oya.
+fn kyoku_name((bakaze, kyoku, honba, _): (u8, u8, u8, u8)) -> String {
+    format!("{}{kyoku} {honba} honba", Tile::new_unchecked(bakaze))
+}
+
+// Turns every red five of the generated events plain. The board's own red fives are rejected before
+// generation, so these are only ones the generator dealt.
+fn no_aka(events: &mut [Event]) {
+    let deaka = |tile: &mut Tile| *tile = tile.deaka();
+    for event in events {
+        match event {
+            Event::StartGame { aka_flag, .. } => *aka_flag = false,
+            Event::StartKyoku { tehais, dora_marker, .. } => {
+                tehais.iter_mut().flatten().for_each(deaka);
+                deaka(dora_marker);
+            }
+            Event::Tsumo { pai, .. } | Event::Dahai { pai, .. } => deaka(pai),
+            Event::Chi { pai, consumed, .. } | Event::Pon { pai, consumed, .. } => {
+                deaka(pai);
+                consumed.iter_mut().for_each(deaka);
+            }
+            Event::Daiminkan { pai, consumed, .. } | Event::Kakan { pai, consumed, .. } => {
+                deaka(pai);
+                consumed.iter_mut().for_each(deaka);
+            }
+            Event::Ankan { consumed, .. } => consumed.iter_mut().for_each(deaka),
+            Event::Dora { dora_marker } => deaka(dora_marker),
+            Event::Hora {
+                ura_markers: Some(markers),
+                ..
+            } => markers.iter_mut().for_each(deaka),
+            _ => {}
+        }
+    }
+}
+
+// Moves the n-th kan dora to where the `late` or immediate timing turns it over, after the n-th kan. Without a
+// timing an indicator that already follows its kan stays, one turned over before it moves right after it.
+fn move_kan_dora(events: &mut Vec<Event>, late: Option<bool>) -> Result<()> {
+    let mut kans = vec![];
+    let mut doras = vec![];
+    let mut rest = vec![];
+    for event in events.drain(..) {
+        match event {
+            Event::Dora { .. } => doras.push((rest.len(), event)),
+            _ => {
+                if matches!(event, Event::Daiminkan { .. } | Event::Kakan { .. } | Event::Ankan { .. }) {
+                    kans.push(rest.len());
+                }
+                rest.push(event);
+            }
+        }
+    }
+    // the last kan's indicator may still be face down when the log stops
+    ensure!(
+        doras.len() <= kans.len(),
+        "the log turns over {} kan dora indicators but has only {} kans",
+        doras.len(),
+        kans.len()
+    );
+    let mut targets: Vec<(usize, usize, Event)> = doras
+        .into_iter()
+        .zip(kans)
+        .enumerate()
+        .map(|(order, ((position, dora), kan))| match late {
+            Some(late) => (reveal_index(&rest, kan, late), order, dora),
+            None if position > kan => (position, order, dora),
+            None => (reveal_index(&rest, kan, false), order, dora),
+        })
+        .collect();
+    // inserting the later ones first keeps the earlier targets valid
+    targets.sort_by_key(|(target, order, _)| Reverse((*target, *order)));
+    *events = rest;
+    for (target, _, dora) in targets {
+        events.insert(target, dora);
+    }
+    Ok(())
+}
+
+// Where the indicator of the kan at `kan` is turned over in `events`; Dora events already there are passed over.
+pub(crate) fn reveal_index(events: &[Event], kan: usize, late: bool) -> usize {
+    let (actor, open) = match &events[kan] {
+        Event::Daiminkan { actor, .. } | Event::Kakan { actor, .. } => (*actor, true),
+        Event::Ankan { actor, .. } => (*actor, false),
+        _ => unreachable!(),
+    };
+    for (i, event) in events.iter().enumerate().skip(kan + 1) {
+        match event {
+            Event::Tsumo { actor: drawer, .. } if *drawer == actor && !(late && open) => return i + 1,
+            Event::Dahai { actor: discarder, .. } if *discarder == actor => return i + 1,
+            // a second kan or the end of the kyoku turns it over too
+            Event::Daiminkan { .. }
+            | Event::Kakan { .. }
+            | Event::Ankan { .. }
+            | Event::Hora { .. }
+            | Event::Ryukyoku { .. }
+            | Event::EndKyoku => return i,
+            _ => {}
+        }
+    }
+    events.len()
+}
+
+// Keeps only the first ron on each discard, the one closest to the discarder in turn order.
+fn atamahane(events: &mut Vec<Event>) {
+    let mut last_target = None;
+    events.retain(|event| match event {
+        Event::Hora { actor, target, .. } if actor != target => {
+            let keep = last_target != Some(*target);
+            last_target = Some(*target);
+            keep
+        }
+        _ => {
+            last_target = None;
+            true
+        }
+    });
+}
+
+// The generator pays honba the standard way, to the first winner only like tenhou; this swaps that part of
+// each kyoku's first win for `honba_value` and, when `each` winner of a multiple ron collects, has the loser
+// pay the others too. Wins the generator left unscored are paid by the tenhou conversion.
+pub(crate) fn apply_honba(events: &mut [Event], honba_value: i32, each: bool) {
+    let mut honba = 0;
+    let mut paid = false;
+    for event in events {
+        match event {
+            Event::StartKyoku { honba: kyoku_honba, .. } => {
+                honba = *kyoku_honba as i32;
+                paid = false;
+            }
+            Event::Hora {
+                actor,
+                target,
+                deltas: Some(deltas),
+                ..
+            } => {
+                let (actor, target) = (*actor as usize, *target as usize);
+                if !paid && actor == target {
+                    for (seat, delta) in deltas.iter_mut().enumerate() {
+                        if seat != actor {
+                            *delta += (100 - honba_value / 3) * honba;
+                        }
+                    }
+                    deltas[actor] += (honba_value / 3 * 3 - 300) * honba;
+                } else if !paid {
+                    deltas[target] += (300 - honba_value) * honba;
+                    deltas[actor] += (honba_value - 300) * honba;
+                } else if each && actor != target {
+                    deltas[target] -= honba_value * honba;
+                    deltas[actor] += honba_value * honba;
+                }
+                paid = true;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::{bail, ensure, Result};
+use riichi::convlog::generate::{Board, Fuurohai, Sutehai};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+
+use crate::events::rotate_seats;
+use crate::fuuro::{meld_sources, place_sideways, split_melds, MeldKind};
+
+// Three player boards are generated by the four seat generator with an empty seat in the place of North, as
+// tenhou numbers the seats of sanma: absolute seat 3 draws and throws out 2m to 8m, which the game is played
+// without, after every discard of seat 2 nobody calls, and is then taken out of the log again.
+
+// Where a three player board's seats sit among four: the relative seat of each seat of the board, starting
+// from the player, and the relative seat left empty. `kyoku` is from 1 and `jikaze` is 0 for East.
+pub(crate) fn four_seats(kyoku: u8, jikaze: u8) -> Result<([usize; 3], usize)> {
+    ensure!((1..=3).contains(&kyoku), "three player mahjong has kyoku 1 to 3");
+    ensure!(jikaze < 3, "three player mahjong has no North seat");
+    let player = (kyoku as usize - 1 + jikaze as usize) % 3;
+    let empty = 3 - player;
+    let mut seats = [0; 3];
+    for (seat, relative) in seats.iter_mut().zip((0..4).filter(|relative| *relative != empty)) {
+        *seat = relative;
+    }
+    Ok((seats, empty))
+}
+
+// The wind the four seat generator gives the player of a three player board, 0 for East.
+pub(crate) fn four_seat_jikaze(kyoku: u8, jikaze: u8) -> u8 {
+    let player = (kyoku - 1 + jikaze) % 3;
+    (player + 4 - (kyoku - 1)) % 4
+}
+
+// The relative seat a call of the seat at relative `seat` is from among four, `from` being the kamicha (3) or
+// shimocha (1) of the three.
+fn four_seat_source(seat: usize, empty: usize, from: u8) -> u8 {
+    match from {
+        3 if (seat + 3) % 4 == empty => 2,
+        1 if (seat + 1) % 4 == empty => 2,
+        from => from,
+    }
+}
+
+// Turns the sideways tiles of one seat's calls, written left for its kamicha and right for its shimocha among
+// the three, to where a call from that seat lies among four.
+pub(crate) fn place_calls(tiles: &mut [Fuurohai], seat: usize, empty: usize) -> Result<()> {
+    if split_melds(tiles)?.iter().any(|meld| meld.kind == MeldKind::Chi) {
+        bail!("three player mahjong has no chi");
+    }
+    for (start, from) in meld_sources(tiles)? {
+        ensure!(from != 2, "a three player call is from the kamicha or the shimocha");
+        let four = four_seat_source(seat, empty, from);
+        if four != from {
+            place_sideways(tiles, start, four)?;
+        }
+    }
+    Ok(())
+}
+
+// Gives the empty seat a turn after every discard of the seat before it, each a tsumogiri of 2m to 8m.
+pub(crate) fn fill_empty_seat(board: &mut Board, empty: usize) -> Result<()> {
+    let turns = board.kawa[(empty + 3) % 4].len();
+    ensure!(
+        turns <= 28,
+        "the kamicha of the empty seat has more discards than a three player kyoku has turns"
+    );
+    board.kawa[empty] = (0..turns)
+        .map(|turn| Sutehai {
+            pai: Tile::new_unchecked(tu8!(2m) + (turn % 7) as u8),
+            tedashi: false,
+            riichi: false,
+        })
+        .collect();
+    Ok(())
+}
+
+// Whether `events` are of a three player game, seat 3 being empty.
+pub(crate) fn is_sanma(events: &[Event]) -> bool {
+    let empty = events.iter().any(|event| {
+        matches!(event, Event::StartKyoku { tehais, scores, .. } if scores[3] == 0 && tehais[3].iter().all(|tile| tile.is_unknown()))
+    });
+    empty
+        && !events.iter().any(|event| match event {
+            Event::Tsumo { actor, .. } | Event::Dahai { actor, .. } => *actor == 3,
+            _ => false,
+        })
+}
+
+// Makes the generated seat at relative `empty` absolute seat 3 and takes it out of the log.
+pub(crate) fn leave_seat_empty(events: &mut Vec<Event>, player_id: u8, empty: usize) {
+    let seat = (player_id as usize + empty) % 4;
+    rotate_seats(events, ((3 + 4 - seat) % 4) as u8);
+    events.retain(|event| match event {
+        Event::Tsumo { actor, .. } | Event::Dahai { actor, .. } => *actor != 3,
+        _ => true,
+    });
+    let unknown = Tile::new_unchecked(tu8!(?));
+    for event in events {
+        match event {
+            Event::StartGame { names, .. } => names[3].clear(),
+            Event::StartKyoku { tehais, scores, .. } => {
+                tehais[3].fill(unknown);
+                scores[3] = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+// Fills the empty seat's haipai with 2m to 8m, which the tenhou conversion needs to deal; tenhou::empty_seat
+// takes it out of the converted log again.
+pub(crate) fn seated(events: &[Event]) -> Vec<Event> {
+    let mut events = events.to_vec();
+    for event in &mut events {
+        if let Event::StartKyoku { tehais, .. } = event {
+            for (i, tile) in tehais[3].iter_mut().enumerate() {
+                *tile = Tile::new_unchecked(tu8!(2m) + (i % 7) as u8);
+            }
+        }
+    }
+    events
+}
+
+// The absolute seat of the player whose wind is `jikaze` in the kyoku of `events` dealt by `oya`, the empty
+// seat of three player games left out.
+pub(crate) fn player_seat(events: &[Event], oya: u8, jikaze: Tile) -> Result<u8> {
+    let wind = jikaze.as_u8().wrapping_sub(tu8!(E));
+    ensure!(wind < 4, "jikaze must be one of E, S, W or N");
+    if !is_sanma(events) {
+        return Ok((oya + wind) % 4);
+    }
+    ensure!(wind < 3, "a three player game has no North seat");
+    Ok((oya + wind) % 3)
+}
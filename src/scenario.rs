@@ -0,0 +1,1183 @@
+use anyhow::{bail, ensure, Context, Result};
+use riichi::algo::agari::{Agari, AgariCalculator};
+use riichi::algo::shanten::calc_all;
+use riichi::hand::{parse_tile, parse_tiles};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+use serde::Deserialize;
+
+use crate::notation::plain_notation;
+use crate::rng::Rng;
+use crate::sanma::is_sanma;
+use crate::tiles_vec_to_string;
+use crate::ura::used_counts;
+use crate::RawBoard;
+
+// How the generated kyoku should end, e.g. {"type": "ron", "winner": 2, "loser": 0, "winning_tile": "6s"}.
+// Seats are relative to the focused player like RawBoard.scores. A double or triple ron gives `winners`
+// instead of `winner`, e.g. {"type": "ron", "winners": [0, 2], "loser": 3, "winning_tile": "6s"}.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Outcome {
+    // "ron", "tsumo", "ryukyoku" for an exhaustive draw, or an abortive draw: "kyuushu_kyuuhai" on the
+    // player's first draw, "suufon_renda", "suucha_riichi" or "suukaikan"
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub winner: Option<u8>,
+    // every seat that rons the same discard
+    pub winners: Vec<u8>,
+    // the seat that deals in to a ron
+    pub loser: Option<u8>,
+    pub winning_tile: String,
+    // the opponents' concealed hands at an exhaustive draw by relative seat, e.g. ["", "123m456p789s1122z"],
+    // which decide who is tenpai and the noten payments; the player's is the board's and empty ones are dealt
+    pub hands: Vec<String>,
+}
+
+// An Outcome that was checked, `loser` being the winner for a tsumo.
+pub(crate) enum Ending {
+    Win { winners: Vec<u8>, loser: u8, tile: Tile },
+    // the concealed hands an exhaustive draw ends with, by relative seat
+    Ryukyoku { hands: [Option<Vec<Tile>>; 4] },
+    Abortive(Abortive),
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Abortive {
+    KyuushuKyuuhai,
+    SuufonRenda,
+    SuuchaRiichi,
+    Suukaikan,
+}
+
+impl Abortive {
+    // the `type` of its mjlog RYUUKYOKU
+    pub(crate) fn mjlog_type(self) -> &'static str {
+        match self {
+            Abortive::KyuushuKyuuhai => "yao9",
+            Abortive::SuufonRenda => "kaze4",
+            Abortive::SuuchaRiichi => "reach4",
+            Abortive::Suukaikan => "kan4",
+        }
+    }
+
+    // how tenhou labels the result
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Abortive::KyuushuKyuuhai => "九種九牌",
+            Abortive::SuufonRenda => "四風連打",
+            Abortive::SuuchaRiichi => "四家立直",
+            Abortive::Suukaikan => "四槓散了",
+        }
+    }
+}
+
+impl Outcome {
+    pub(crate) fn ending(&self) -> Result<Ending> {
+        let seat = |seat: Option<u8>, role: &str| -> Result<u8> {
+            let seat = seat.with_context(|| format!("a {} needs its {role}", self.kind))?;
+            ensure!(seat < 4, "the {role} must be a seat from 0 to 3");
+            Ok(seat)
+        };
+        let tile = || parse_tile(&plain_notation(&self.winning_tile, false).0).context("incorrect winning_tile");
+        ensure!(
+            self.hands.iter().all(String::is_empty) || self.kind == "ryukyoku",
+            "hands are only given for an exhaustive draw"
+        );
+        match self.kind.as_str() {
+            "ron" => {
+                let winners = if self.winners.is_empty() {
+                    vec![seat(self.winner, "winner")?]
+                } else {
+                    ensure!(self.winner.is_none(), "a ron gives either winner or winners");
+                    ensure!(self.winners.len() <= 3, "at most three seats ron one discard");
+                    for (i, winner) in self.winners.iter().enumerate() {
+                        seat(Some(*winner), "winner")?;
+                        ensure!(!self.winners[..i].contains(winner), "seat {winner} is among the winners twice");
+                    }
+                    self.winners.clone()
+                };
+                let loser = seat(self.loser, "loser")?;
+                ensure!(!winners.contains(&loser), "a ron is off another seat's discard");
+                Ok(Ending::Win {
+                    winners,
+                    loser,
+                    tile: tile()?,
+                })
+            }
+            "tsumo" => {
+                ensure!(self.winners.is_empty(), "a tsumo has a single winner");
+                let winner = seat(self.winner, "winner")?;
+                ensure!(self.loser.is_none_or(|loser| loser == winner), "a tsumo has no loser");
+                Ok(Ending::Win {
+                    winners: vec![winner],
+                    loser: winner,
+                    tile: tile()?,
+                })
+            }
+            "ryukyoku" => {
+                ensure!(
+                    self.winner.is_none() && self.winners.is_empty() && self.loser.is_none(),
+                    "an exhaustive draw has no winner or loser"
+                );
+                ensure!(self.hands.len() <= 4, "hands has a hand per seat, not {}", self.hands.len());
+                ensure!(
+                    self.hands.first().is_none_or(String::is_empty),
+                    "the player's hand at the draw is the board's"
+                );
+                let mut hands: [Option<Vec<Tile>>; 4] = Default::default();
+                for (seat, hand) in self.hands.iter().enumerate().filter(|(_, hand)| !hand.is_empty()) {
+                    let tiles = parse_tiles(&plain_notation(hand, false).0).with_context(|| format!("incorrect hands[{seat}]"))?;
+                    hands[seat] = Some(tiles);
+                }
+                Ok(Ending::Ryukyoku { hands })
+            }
+            kind @ ("kyuushu_kyuuhai" | "suufon_renda" | "suucha_riichi" | "suukaikan") => {
+                ensure!(
+                    self.winner.is_none() && self.winners.is_empty() && self.loser.is_none(),
+                    "an abortive draw has no winner or loser"
+                );
+                Ok(Ending::Abortive(match kind {
+                    "kyuushu_kyuuhai" => Abortive::KyuushuKyuuhai,
+                    "suufon_renda" => Abortive::SuufonRenda,
+                    "suucha_riichi" => Abortive::SuuchaRiichi,
+                    _ => Abortive::Suukaikan,
+                }))
+            }
+            other => bail!(
+                "unknown outcome {other:?}, expected ron, tsumo, ryukyoku, kyuushu_kyuuhai, suufon_renda, suucha_riichi or suukaikan"
+            ),
+        }
+    }
+
+    // the abortive draw this outcome is, which the log conversion writes as a plain ryukyoku
+    pub(crate) fn abortive(&self) -> Option<Abortive> {
+        match self.ending() {
+            Ok(Ending::Abortive(abortive)) => Some(abortive),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ScenarioOptions {
+    // same seed, same scenario
+    pub seed: Option<u64>,
+    // seat wind of the focused player, random when empty
+    pub jikaze: String,
+    // 1 when unset
+    pub junme: Option<usize>,
+    // target shanten per seat, relative to the focused player like RawBoard.scores; 0 is tenpai
+    pub shanten: Vec<Option<i8>>,
+}
+
+fn shanten_of(hand: &[u8; 34], len: usize) -> i8 {
+    calc_all(hand, (len / 3) as u8)
+}
+
+// Draws `len` tiles out of `available` whose shanten is `target`, by random restarts of a local search
+// that swaps one tile at a time and never moves away from the target.
+fn hand_with_shanten(rng: &mut Rng, available: &mut [u8; 34], len: usize, target: i8) -> Result<[u8; 34]> {
+    for _ in 0..64 {
+        let mut wall: Vec<usize> = (0..34).flat_map(|kind| vec![kind; available[kind] as usize]).collect();
+        ensure!(wall.len() >= len, "not enough tiles left for a hand of {len}");
+        rng.shuffle(&mut wall);
+        let (hand_tiles, rest) = wall.split_at_mut(len);
+        let mut hand = [0; 34];
+        for kind in hand_tiles.iter() {
+            hand[*kind] += 1;
+        }
+        let mut distance = (shanten_of(&hand, len) - target).abs();
+        for _ in 0..400 {
+            if distance == 0 {
+                break;
+            }
+            let (i, j) = (rng.below(len), rng.below(rest.len()));
+            let (out, into) = (hand_tiles[i], rest[j]);
+            if out == into {
+                continue;
+            }
+            hand[out] -= 1;
+            hand[into] += 1;
+            let swapped = (shanten_of(&hand, len) - target).abs();
+            if swapped <= distance {
+                distance = swapped;
+                hand_tiles[i] = into;
+                rest[j] = out;
+            } else {
+                hand[into] -= 1;
+                hand[out] += 1;
+            }
+        }
+        if distance == 0 {
+            for (count, taken) in available.iter_mut().zip(hand) {
+                *count -= taken;
+            }
+            return Ok(hand);
+        }
+    }
+    bail!("could not find a hand of {len} tiles at {target} shanten")
+}
+
+fn random_hand(rng: &mut Rng, available: &mut [u8; 34], len: usize) -> Result<[u8; 34]> {
+    let mut wall: Vec<usize> = (0..34).flat_map(|kind| vec![kind; available[kind] as usize]).collect();
+    ensure!(wall.len() >= len, "not enough tiles left for a hand of {len}");
+    rng.shuffle(&mut wall);
+    let mut hand = [0; 34];
+    for kind in &wall[..len] {
+        hand[*kind] += 1;
+        available[*kind] -= 1;
+    }
+    Ok(hand)
+}
+
+fn hand_tiles(hand: &[u8; 34]) -> Vec<Tile> {
+    (0..34)
+        .flat_map(|kind| vec![Tile::new_unchecked(kind as u8); hand[kind] as usize])
+        .collect()
+}
+
+// A random board where the focused player holds a hand at the target shanten after their draw, with the
+// kawa padded up to the junme. The other seats' targets are applied to the generated log.
+pub(crate) fn random_board(rng: &mut Rng, options: &ScenarioOptions) -> Result<RawBoard> {
+    let jikaze = match options.jikaze.as_str() {
+        "" => ["E", "S", "W", "N"][rng.below(4)].to_owned(),
+        jikaze => jikaze.to_owned(),
+    };
+    let kyoku = format!("E{}", rng.below(4) + 1);
+    let junme = options.junme.unwrap_or(1);
+    ensure!(junme >= 1, "junme starts at 1");
+
+    let mut available = [4; 34];
+    let dora = rng.below(34);
+    available[dora] -= 1;
+    let target = options.shanten.first().copied().flatten();
+    let tehai = match target {
+        Some(target) => {
+            ensure!((0..=6).contains(&target), "the focused player's shanten must be from 0 to 6");
+            hand_with_shanten(rng, &mut available, 14, target)?
+        }
+        None => random_hand(rng, &mut available, 14)?,
+    };
+
+    Ok(RawBoard {
+        kyoku,
+        jikaze,
+        kyotaku: "0".to_owned(),
+        honba: "0".to_owned(),
+        dora: tiles_vec_to_string(&[Tile::new_unchecked(dora as u8)]),
+        scores: vec![String::new(); 4],
+        tehai: tiles_vec_to_string(&hand_tiles(&tehai)),
+        kawa: vec![String::new(); 4],
+        fuuro: vec![String::new(); 4],
+        junme: junme.to_string(),
+        ..Default::default()
+    })
+}
+
+// Where a concealed tile came from: a slot of the haipai or the Tsumo event at an index.
+#[derive(Clone, Copy)]
+enum Source {
+    Haipai(usize, usize),
+    Tsumo(usize),
+}
+
+// The concealed tiles `seat` still holds at the end of the log, with the event each one entered through.
+fn kept_sources(events: &[Event], seat: u8) -> Vec<(Tile, Source)> {
+    let mut hand: Vec<(Tile, Source)> = vec![];
+    let remove = |hand: &mut Vec<(Tile, Source)>, tile: Tile, latest: bool| {
+        let mut matching = hand.iter().enumerate().filter(|(_, (held, _))| *held == tile).map(|(i, _)| i);
+        let i = if latest { matching.next_back() } else { matching.next() };
+        if let Some(i) = i {
+            hand.remove(i);
+        }
+    };
+    for (index, event) in events.iter().enumerate() {
+        match event {
+            Event::StartKyoku { tehais, .. } => {
+                hand = tehais[seat as usize]
+                    .iter()
+                    .enumerate()
+                    .map(|(slot, tile)| (*tile, Source::Haipai(index, slot)))
+                    .collect();
+            }
+            Event::Tsumo { actor, pai } if *actor == seat => hand.push((*pai, Source::Tsumo(index))),
+            Event::Dahai { actor, pai, tsumogiri } if *actor == seat => remove(&mut hand, *pai, *tsumogiri),
+            Event::Chi { actor, consumed, .. } | Event::Pon { actor, consumed, .. } if *actor == seat => {
+                consumed.iter().for_each(|tile| remove(&mut hand, *tile, false));
+            }
+            Event::Daiminkan { actor, consumed, .. } if *actor == seat => {
+                consumed.iter().for_each(|tile| remove(&mut hand, *tile, false));
+            }
+            Event::Ankan { actor, consumed } if *actor == seat => {
+                consumed.iter().for_each(|tile| remove(&mut hand, *tile, false));
+            }
+            Event::Kakan { actor, pai, .. } if *actor == seat => remove(&mut hand, *pai, false),
+            _ => {}
+        }
+    }
+    hand
+}
+
+fn five_index(tile: Tile) -> Option<usize> {
+    match tile.as_u8() {
+        tu8!(5m) => Some(0),
+        tu8!(5p) => Some(1),
+        tu8!(5s) => Some(2),
+        _ => None,
+    }
+}
+
+// plain (not red) fives of each suit the log commits to
+fn plain_fives(events: &[Event]) -> [u8; 3] {
+    let mut fives = [0; 3];
+    let mut add = |tile: &Tile| {
+        if let Some(i) = five_index(*tile) {
+            fives[i] += 1;
+        }
+    };
+    for event in events {
+        match event {
+            Event::StartKyoku { tehais, dora_marker, .. } => tehais.iter().flatten().chain([dora_marker]).for_each(&mut add),
+            Event::Tsumo { pai, .. } => add(pai),
+            Event::Dora { dora_marker } => add(dora_marker),
+            _ => {}
+        }
+    }
+    fives
+}
+
+fn set_source(events: &mut [Event], seat: u8, source: Source, tile: Tile) {
+    match source {
+        Source::Haipai(index, slot) => {
+            if let Event::StartKyoku { tehais, .. } = &mut events[index] {
+                tehais[seat as usize][slot] = tile;
+            }
+        }
+        Source::Tsumo(index) => {
+            if let Event::Tsumo { pai, .. } = &mut events[index] {
+                *pai = tile;
+            }
+        }
+    }
+}
+
+// Unseen tiles plus the concealed tiles `seats` keep to the end of the log, and the plain fives per suit the
+// log commits to besides those.
+fn hidden_pool(events: &[Event], seats: &[u8]) -> ([u8; 34], [u8; 3]) {
+    let mut used = used_counts(events);
+    // ura indicators a board already chose are spoken for too
+    for event in events {
+        if let Event::Hora {
+            ura_markers: Some(markers),
+            ..
+        } = event
+        {
+            for marker in markers {
+                used[marker.deaka().as_usize()] += 1;
+            }
+        }
+    }
+    let mut fives = plain_fives(events);
+    for seat in seats {
+        for (tile, _) in kept_sources(events, *seat) {
+            used[tile.deaka().as_usize()] -= 1;
+            if let Some(i) = five_index(tile) {
+                fives[i] -= 1;
+            }
+        }
+    }
+    let mut available = used.map(|used| 4u8.saturating_sub(used));
+    // and three player mahjong has no 2m to 8m to deal
+    if is_sanma(events) {
+        available[tu8!(2m) as usize..=tu8!(8m) as usize].fill(0);
+    }
+    (available, fives)
+}
+
+fn aka_game(events: &[Event]) -> bool {
+    events
+        .iter()
+        .any(|event| matches!(event, Event::StartGame { aka_flag: true, .. }))
+}
+
+// Deals the tiles `seat` keeps hidden afresh out of the pool, at `target` shanten or any hand without one.
+fn deal_kept(
+    rng: &mut Rng,
+    events: &mut [Event],
+    seat: u8,
+    target: Option<i8>,
+    (available, fives): &mut ([u8; 34], [u8; 3]),
+) -> Result<()> {
+    let aka = aka_game(events);
+    let kept = kept_sources(events, seat);
+    let hand = match target {
+        Some(target) => hand_with_shanten(rng, available, kept.len(), target).with_context(|| format!("seat {seat}"))?,
+        None => random_hand(rng, available, kept.len()).with_context(|| format!("seat {seat}"))?,
+    };
+
+    for ((_, source), tile) in kept.iter().zip(hand_tiles(&hand)) {
+        set_source(events, seat, *source, pool_tile(tile, aka, fives));
+    }
+    Ok(())
+}
+
+// Gives `seat` exactly `tiles` as the tiles it keeps hidden, taken out of the pool.
+fn set_kept(
+    events: &mut [Event],
+    seat: u8,
+    tiles: &[Tile],
+    aka: bool,
+    (available, fives): &mut ([u8; 34], [u8; 3]),
+) -> Result<()> {
+    let kept = kept_sources(events, seat);
+    ensure!(
+        tiles.len() == kept.len(),
+        "the seat keeps {} concealed tiles, not {}",
+        kept.len(),
+        tiles.len()
+    );
+    if events
+        .iter()
+        .any(|event| matches!(event, Event::ReachAccepted { actor } if *actor == seat))
+    {
+        let mut hand = [0; 34];
+        tiles.iter().for_each(|tile| hand[tile.deaka().as_usize()] += 1);
+        ensure!(
+            calc_all(&hand, (tiles.len() / 3) as u8) == 0,
+            "the seat is in riichi, its hand has to be tenpai"
+        );
+    }
+    for ((_, source), tile) in kept.iter().zip(tiles) {
+        let kind = tile.deaka().as_usize();
+        ensure!(available[kind] > 0, "every {} is already seen", tiles_vec_to_string(&[*tile]));
+        // the tile as given: the red five while the pool still holds it, a plain five while it holds one of those
+        if let Some(i) = five_index(tile.deaka()) {
+            let five = tiles_vec_to_string(&[*tile]);
+            if tile.is_aka() {
+                ensure!(aka, "the game plays without red fives, there is no {five}");
+                ensure!(
+                    available[kind] > 3u8.saturating_sub(fives[i]),
+                    "the red {five} is already seen"
+                );
+            } else {
+                ensure!(!aka || fives[i] < 3, "every plain {five} is already seen");
+                fives[i] += 1;
+            }
+        }
+        available[kind] -= 1;
+        set_source(events, seat, *source, *tile);
+    }
+    Ok(())
+}
+
+// `tile` as dealt from the pool: an aka game has three plain fives per suit, the fourth copy is red.
+fn pool_tile(tile: Tile, aka: bool, fives: &mut [u8; 3]) -> Tile {
+    match five_index(tile) {
+        Some(i) if aka && fives[i] >= 3 => tile.akaize(),
+        Some(i) => {
+            fives[i] += 1;
+            tile
+        }
+        None => tile,
+    }
+}
+
+fn ryukyoku_payments(events: &[Event]) -> bool {
+    events
+        .iter()
+        .any(|event| matches!(event, Event::Ryukyoku { deltas: Some(deltas) } if deltas.iter().any(|d| *d != 0)))
+}
+
+// Swaps the tiles `seat` keeps hidden until the end of the log for unseen ones, so that their final
+// concealed hand is at `target` shanten. Nothing that was shown to the table changes.
+pub(crate) fn set_shanten(rng: &mut Rng, events: &mut [Event], seat: u8, target: i8) -> Result<()> {
+    ensure!(
+        !events
+            .iter()
+            .any(|event| matches!(event, Event::Hora { actor, .. } if *actor == seat)),
+        "the hand of seat {seat} cannot change, it wins the kyoku"
+    );
+    ensure!(
+        !ryukyoku_payments(events),
+        "tenpai payments of an exhaustive draw would no longer match the hands"
+    );
+    if events
+        .iter()
+        .any(|event| matches!(event, Event::ReachAccepted { actor } if *actor == seat))
+    {
+        ensure!(target == 0, "seat {seat} is in riichi, so it can only be tenpai");
+    }
+    let mut pool = hidden_pool(events, &[seat]);
+    deal_kept(rng, events, seat, Some(target), &mut pool)
+}
+
+// Deals every tile the generator made up for the opponents' hidden hands again from `rng`, so the same board
+// and seed always give the same log. Seats in riichi stay tenpai. An opponent's win or tenpai payments are
+// scored from the generator's hands, which a redeal would not match, so those logs cannot be seeded.
+pub(crate) fn redeal_hidden(rng: &mut Rng, events: &mut [Event], player_id: u8) -> Result<()> {
+    ensure!(
+        !ryukyoku_payments(events),
+        "the seed cannot redeal hands whose exhaustive draw already pays for tenpai"
+    );
+    let seats: Vec<u8> = (0..players(events)).filter(|seat| *seat != player_id).collect();
+    if let Some(winner) = seats.iter().find(|seat| {
+        events
+            .iter()
+            .any(|event| matches!(event, Event::Hora { actor, .. } if actor == *seat))
+    }) {
+        bail!("the seed cannot redeal the hand of seat {winner}, which already wins the kyoku");
+    }
+    redeal_seats(rng, events, &seats)
+}
+
+// Deals the hidden tiles of the opponents of a three player game afresh when they keep a tile the game does
+// not have: any of 2m to 8m, or an N past the four with the kita.
+pub(crate) fn redeal_sanma(rng: &mut Rng, events: &mut [Event], player_id: u8) -> Result<()> {
+    let north = used_counts(events)[tu8!(N) as usize] > 4;
+    let seats: Vec<u8> = (0..3)
+        .filter(|seat| *seat != player_id)
+        .filter(|seat| {
+            kept_sources(events, *seat).iter().any(|(tile, _)| {
+                let kind = tile.deaka().as_u8();
+                (tu8!(2m)..=tu8!(8m)).contains(&kind) || (north && kind == tu8!(N))
+            })
+        })
+        .collect();
+    redeal_seats(rng, events, &seats)
+}
+
+fn players(events: &[Event]) -> u8 {
+    if is_sanma(events) {
+        3
+    } else {
+        4
+    }
+}
+
+fn redeal_seats(rng: &mut Rng, events: &mut [Event], seats: &[u8]) -> Result<()> {
+    let mut pool = hidden_pool(events, seats);
+    for seat in seats {
+        let riichi = events
+            .iter()
+            .any(|event| matches!(event, Event::ReachAccepted { actor } if actor == seat));
+        deal_kept(rng, events, *seat, riichi.then_some(0), &mut pool)?;
+    }
+    Ok(())
+}
+
+fn concealed_counts(events: &[Event], seat: u8) -> ([u8; 34], usize) {
+    let kept = kept_sources(events, seat);
+    let mut hand = [0; 34];
+    for (tile, _) in &kept {
+        hand[tile.deaka().as_usize()] += 1;
+    }
+    (hand, kept.len())
+}
+
+fn is_simple(kind: u8) -> bool {
+    kind < 27 && (1..8).contains(&(kind % 9))
+}
+
+// The yaku `seat` has winning on `kind` with its concealed tiles and calls, dora left out, or None without
+// one. Without `kuitan` an open hand does not count tanyao.
+fn win_yaku(events: &[Event], seat: u8, kind: usize, ron: bool, kuitan: bool) -> Option<Agari> {
+    let (mut hand, _) = concealed_counts(events, seat);
+    hand[kind] += 1;
+    let (mut chis, mut pons, mut minkans, mut ankans) = (vec![], vec![], vec![], vec![]);
+    let (mut bakaze, mut oya) = (tu8!(E), 0);
+    for event in events {
+        match event {
+            Event::StartKyoku {
+                bakaze: wind,
+                oya: dealer,
+                ..
+            } => {
+                (bakaze, oya) = (wind.as_u8(), *dealer);
+                (chis, pons, minkans, ankans) = (vec![], vec![], vec![], vec![]);
+            }
+            Event::Chi {
+                actor, pai, consumed, ..
+            } if *actor == seat => {
+                chis.extend(consumed.iter().chain([pai]).map(|tile| tile.deaka().as_u8()).min());
+            }
+            Event::Pon { actor, pai, .. } if *actor == seat => pons.push(pai.deaka().as_u8()),
+            Event::Daiminkan { actor, pai, .. } if *actor == seat => minkans.push(pai.deaka().as_u8()),
+            Event::Kakan { actor, pai, .. } if *actor == seat => {
+                let kind = pai.deaka().as_u8();
+                pons.retain(|pon| *pon != kind);
+                minkans.push(kind);
+            }
+            Event::Ankan { actor, consumed } if *actor == seat => ankans.push(consumed[0].deaka().as_u8()),
+            _ => {}
+        }
+    }
+    let is_menzen = chis.is_empty() && pons.is_empty() && minkans.is_empty();
+    let agari = AgariCalculator {
+        tehai: &hand,
+        is_menzen,
+        chis: &chis,
+        pons: &pons,
+        minkans: &minkans,
+        ankans: &ankans,
+        bakaze,
+        jikaze: tu8!(E) + (seat + 4 - oya) % 4,
+        winning_tile: kind as u8,
+        is_ron: ron,
+    }
+    .search_yakus()?;
+    let tanyao = (0..34).all(|kind| hand[kind] == 0 || is_simple(kind as u8))
+        && chis.iter().all(|chi| is_simple(*chi) && is_simple(chi + 2))
+        && pons.iter().chain(&minkans).chain(&ankans).all(|kind| is_simple(*kind));
+    match agari {
+        Agari::Normal { fu, han } if !kuitan && !is_menzen && tanyao => (han > 1).then_some(Agari::Normal { fu, han: han - 1 }),
+        agari => Some(agari),
+    }
+}
+
+// Deals `seat` a fresh hidden hand that `kind` completes with a yaku; riichi is one, and without `kuitan` an
+// open seat's tanyao is not.
+fn deal_waiting(
+    rng: &mut Rng,
+    events: &mut [Event],
+    seat: u8,
+    kind: usize,
+    ron: bool,
+    kuitan: bool,
+    pool: &mut ([u8; 34], [u8; 3]),
+) -> Result<()> {
+    let riichi = events
+        .iter()
+        .any(|event| matches!(event, Event::ReachAccepted { actor } if *actor == seat));
+    for _ in 0..256 {
+        let mut attempt = *pool;
+        deal_kept(rng, events, seat, Some(0), &mut attempt)?;
+        let (mut hand, len) = concealed_counts(events, seat);
+        hand[kind] += 1;
+        if calc_all(&hand, (len / 3 + 1) as u8) == -1 && (riichi || win_yaku(events, seat, kind, ron, kuitan).is_some()) {
+            *pool = attempt;
+            return Ok(());
+        }
+    }
+    bail!(
+        "could not deal seat {seat} a hand that wins on {} with a yaku",
+        tiles_vec_to_string(&[Tile::new_unchecked(kind as u8)])
+    )
+}
+
+// A random tile of the pool that `allowed` lets through.
+fn draw_pool(
+    rng: &mut Rng,
+    (available, fives): &mut ([u8; 34], [u8; 3]),
+    aka: bool,
+    allowed: impl Fn(usize) -> bool,
+) -> Result<Tile> {
+    let wall: Vec<usize> = (0..34)
+        .filter(|kind| allowed(*kind))
+        .flat_map(|kind| vec![kind; available[kind] as usize])
+        .collect();
+    ensure!(!wall.is_empty(), "no unseen tiles are left to draw");
+    let kind = wall[rng.below(wall.len())];
+    available[kind] -= 1;
+    Ok(pool_tile(Tile::new_unchecked(kind as u8), aka, fives))
+}
+
+// A kyoku has 70 draws, the replacement draws of kans included.
+const DRAWS: usize = 70;
+
+// Plays a log generated up to the player's draw on to `ending`. Every seat after the player draws unseen
+// tiles and throws them away, each winner gets a hidden hand waiting on the winning tile dealt from `rng`, and
+// an exhaustive draw pays for the tenpai hands at the end. The player's own wins are added to the board
+// instead, as score_win does.
+pub(crate) fn play_outcome(rng: &mut Rng, events: &mut Vec<Event>, player_id: u8, ending: &Ending, kuitan: bool) -> Result<()> {
+    ensure!(
+        !events
+            .iter()
+            .any(|event| matches!(event, Event::Hora { .. } | Event::Ryukyoku { .. })),
+        "the kyoku already ends in the generated log"
+    );
+    let tail = split_tail(events);
+    let drawn = match events.last() {
+        Some(Event::Tsumo { actor, pai }) if *actor == player_id => *pai,
+        _ => bail!("an outcome needs a board on the player's turn, not a claim snapshot"),
+    };
+    let aka = aka_game(events);
+    let mut draws = events.iter().filter(|event| matches!(event, Event::Tsumo { .. })).count();
+    let absolute = |seat: u8| (player_id + seat) % 4;
+
+    match ending {
+        Ending::Win { winners, loser, tile } => {
+            ensure!(!winners.contains(&0), "the player's own wins are made from the board");
+            let winners: Vec<u8> = winners.iter().map(|winner| absolute(*winner)).collect();
+            let (loser, tile) = (absolute(*loser), *tile);
+            let kind = tile.deaka().as_usize();
+            let mut pool = hidden_pool(events, &winners);
+            // the player deals in from their own hand, or with their draw once in riichi
+            let riichi = events
+                .iter()
+                .any(|event| matches!(event, Event::ReachAccepted { actor } if *actor == player_id));
+            if loser == player_id && riichi {
+                ensure!(
+                    drawn.deaka().as_usize() == kind,
+                    "the player is in riichi, they can only deal in with their draw {drawn}"
+                );
+            }
+            let held = match (loser == player_id, riichi) {
+                (true, true) => Some(drawn),
+                (true, false) => kept_sources(events, player_id)
+                    .into_iter()
+                    .map(|(held, _)| held)
+                    .find(|held| held.deaka().as_usize() == kind),
+                (false, _) => None,
+            };
+            let win_tile = match held {
+                Some(held) => held,
+                None => {
+                    ensure!(loser != player_id, "the player does not hold {tile} to deal in with");
+                    ensure!(pool.0[kind] > 0, "every {tile} is already seen");
+                    pool.0[kind] -= 1;
+                    pool_tile(tile.deaka(), aka, &mut pool.1)
+                }
+            };
+            deal_winners(rng, events, &winners, loser, tile, kuitan, &mut pool)?;
+            let hands: Vec<([u8; 34], usize)> = winners.iter().map(|winner| concealed_counts(events, *winner)).collect();
+            let wins = |kind: usize| {
+                hands.iter().any(|(hand, len)| {
+                    let mut hand = *hand;
+                    hand[kind] += 1;
+                    calc_all(&hand, (len / 3 + 1) as u8) == -1
+                })
+            };
+
+            if loser == player_id {
+                events.push(Event::Dahai {
+                    actor: player_id,
+                    pai: win_tile,
+                    tsumogiri: win_tile == drawn,
+                });
+                push_horas(events, &winners, player_id);
+            } else {
+                events.push(Event::Dahai {
+                    actor: player_id,
+                    pai: drawn,
+                    tsumogiri: true,
+                });
+                for seat in (1..4).map(absolute) {
+                    ensure!(draws < DRAWS, "the wall runs out before the win");
+                    draws += 1;
+                    if seat == loser {
+                        events.push(Event::Tsumo {
+                            actor: seat,
+                            pai: win_tile,
+                        });
+                        if !winners.contains(&seat) {
+                            events.push(Event::Dahai {
+                                actor: seat,
+                                pai: win_tile,
+                                tsumogiri: true,
+                            });
+                        }
+                        push_horas(events, &winners, seat);
+                        break;
+                    }
+                    // the tiles thrown on the way neither win nor leave a winner furiten
+                    let pai = draw_pool(rng, &mut pool, aka, |kind| !wins(kind))?;
+                    events.push(Event::Tsumo { actor: seat, pai });
+                    events.push(Event::Dahai {
+                        actor: seat,
+                        pai,
+                        tsumogiri: true,
+                    });
+                }
+            }
+        }
+        Ending::Ryukyoku { hands } => {
+            let given: Vec<u8> = (1..4).filter(|seat| hands[*seat as usize].is_some()).collect();
+            let mut pool = hidden_pool(events, &given.iter().map(|seat| absolute(*seat)).collect::<Vec<_>>());
+            for seat in given {
+                let tiles = hands[seat as usize].as_deref().unwrap_or_default();
+                set_kept(events, absolute(seat), tiles, aka, &mut pool).with_context(|| format!("hands[{seat}]"))?;
+            }
+            events.push(Event::Dahai {
+                actor: player_id,
+                pai: drawn,
+                tsumogiri: true,
+            });
+            let mut seat = player_id;
+            while draws < DRAWS {
+                seat = (seat + 1) % 4;
+                draws += 1;
+                let pai = draw_pool(rng, &mut pool, aka, |_| true)?;
+                events.push(Event::Tsumo { actor: seat, pai });
+                events.push(Event::Dahai {
+                    actor: seat,
+                    pai,
+                    tsumogiri: true,
+                });
+            }
+            let tenpai: Vec<bool> = (0..4)
+                .map(|seat| {
+                    let (hand, len) = concealed_counts(events, seat);
+                    calc_all(&hand, (len / 3) as u8) == 0
+                })
+                .collect();
+            let count = tenpai.iter().filter(|tenpai| **tenpai).count() as i32;
+            let mut deltas = [0; 4];
+            if (1..4).contains(&count) {
+                for (delta, tenpai) in deltas.iter_mut().zip(&tenpai) {
+                    *delta = if *tenpai { 3000 / count } else { -3000 / (4 - count) };
+                }
+            }
+            events.push(Event::Ryukyoku { deltas: Some(deltas) });
+        }
+        Ending::Abortive(abortive) => {
+            play_abortive(rng, events, player_id, drawn, *abortive, draws)?;
+            // libriichi's Ryukyoku has no reason, the tenhou label tells it apart
+            events.push(Event::Ryukyoku { deltas: Some([0; 4]) });
+        }
+    }
+    events.extend(tail);
+    Ok(())
+}
+
+// Takes the closing EndKyoku and EndGame off `events`.
+fn split_tail(events: &mut Vec<Event>) -> Vec<Event> {
+    let tail_start = events.len()
+        - events
+            .iter()
+            .rev()
+            .take_while(|event| matches!(event, Event::EndKyoku | Event::EndGame))
+            .count();
+    events.split_off(tail_start)
+}
+
+// Deals each of `winners` a hidden hand waiting on `tile`, making sure a ron off `loser` is not furiten.
+fn deal_winners(
+    rng: &mut Rng,
+    events: &mut [Event],
+    winners: &[u8],
+    loser: u8,
+    tile: Tile,
+    kuitan: bool,
+    pool: &mut ([u8; 34], [u8; 3]),
+) -> Result<()> {
+    let kind = tile.deaka().as_usize();
+    for &winner in winners {
+        if winner != loser {
+            let furiten = events.iter().any(
+                |event| matches!(event, Event::Dahai { actor, pai, .. } if *actor == winner && pai.deaka().as_usize() == kind),
+            );
+            ensure!(!furiten, "seat {winner} discarded {tile} before, it cannot ron on it");
+        }
+        deal_waiting(rng, events, winner, kind, winner != loser, kuitan, pool)?;
+    }
+    Ok(())
+}
+
+// The wins off `loser` in turn order from it, the order tenhou lists a multiple ron in and gives the
+// kyotaku to the first of.
+fn push_horas(events: &mut Vec<Event>, winners: &[u8], loser: u8) {
+    let mut winners = winners.to_vec();
+    winners.sort_by_key(|winner| (winner + 4 - loser) % 4);
+    events.extend(winners.into_iter().map(|actor| Event::Hora {
+        actor,
+        target: loser,
+        deltas: None,
+        ura_markers: None,
+    }));
+}
+
+// Makes `tile` the winning tile of a log ending in a win, in place of the one drawn for it and, for a ron,
+// thrown out. An opponent keeping a hidden copy that would be a fifth in play keeps the replaced tile instead.
+pub(crate) fn swap_win_tile(events: &mut [Event], tile: Tile) -> Result<()> {
+    let (end, winner, target) = events
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(index, event)| match event {
+            Event::Hora { actor, target, .. } => Some((index, *actor, *target)),
+            _ => None,
+        })
+        .context("the log does not end in a win")?;
+    let draw = events[..end]
+        .iter()
+        .rposition(|event| matches!(event, Event::Tsumo { actor, .. } if *actor == target))
+        .context("the winning tile is never drawn")?;
+    let Event::Tsumo { pai, .. } = &mut events[draw] else {
+        unreachable!()
+    };
+    let replaced = std::mem::replace(pai, tile);
+    for event in &mut events[draw + 1..end] {
+        if let Event::Dahai { actor, pai, .. } = event {
+            if *actor == target {
+                *pai = tile;
+            }
+        }
+    }
+    if used_counts(events)[tile.deaka().as_usize()] > 4 {
+        let (seat, source) = (0..4)
+            .filter(|seat| *seat != winner)
+            .find_map(|seat| {
+                kept_sources(events, seat)
+                    .into_iter()
+                    .find(|(kept, _)| kept.deaka() == tile.deaka())
+                    .map(|(_, source)| (seat, source))
+            })
+            .with_context(|| format!("every {tile} is already in play"))?;
+        set_source(events, seat, source, replaced);
+    }
+    Ok(())
+}
+
+// Adds the rons of the opponents `winners` to a log that ends in the player's ron off `loser`, dealing them
+// hidden hands waiting on the same discard. Seats are relative to the player.
+pub(crate) fn add_rons(
+    rng: &mut Rng,
+    events: &mut Vec<Event>,
+    player_id: u8,
+    winners: &[u8],
+    loser: u8,
+    kuitan: bool,
+) -> Result<()> {
+    let tail = split_tail(events);
+    ensure!(
+        matches!(events.pop(), Some(Event::Hora { actor, .. }) if actor == player_id),
+        "the log does not end in the player's ron"
+    );
+    let Some(Event::Dahai { pai: tile, .. }) = events.last() else {
+        bail!("the player's ron is not on a discard");
+    };
+    let tile = *tile;
+    let loser = (player_id + loser) % 4;
+    let mut winners: Vec<u8> = winners.iter().map(|winner| (player_id + winner) % 4).collect();
+    let mut pool = hidden_pool(events, &winners);
+    deal_winners(rng, events, &winners, loser, tile, kuitan, &mut pool)?;
+    winners.push(player_id);
+    push_horas(events, &winners, loser);
+    events.extend(tail);
+    Ok(())
+}
+
+fn is_call(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Chi { .. } | Event::Pon { .. } | Event::Daiminkan { .. } | Event::Kakan { .. } | Event::Ankan { .. }
+    )
+}
+
+fn is_open_call_by(event: &Event, seat: u8) -> bool {
+    matches!(event, Event::Chi { actor, .. } | Event::Pon { actor, .. } | Event::Daiminkan { actor, .. } if *actor == seat)
+}
+
+// The concealed tile of `seat` of this kind.
+fn held_tile(events: &[Event], seat: u8, kind: usize) -> Option<Tile> {
+    kept_sources(events, seat)
+        .into_iter()
+        .map(|(tile, _)| tile)
+        .find(|tile| tile.deaka().as_usize() == kind)
+}
+
+// The events from the player's draw up to the abortive draw, which the caller adds.
+fn play_abortive(
+    rng: &mut Rng,
+    events: &mut Vec<Event>,
+    player_id: u8,
+    drawn: Tile,
+    abortive: Abortive,
+    mut draws: usize,
+) -> Result<()> {
+    let aka = aka_game(events);
+    let next = |seat: u8| (seat + 1) % 4;
+    match abortive {
+        Abortive::KyuushuKyuuhai => {
+            ensure!(
+                !events
+                    .iter()
+                    .any(|event| is_call(event) || matches!(event, Event::Dahai { actor, .. } if *actor == player_id)),
+                "nine terminals and honors only abort on the player's first draw, before any call"
+            );
+            let (hand, _) = concealed_counts(events, player_id);
+            let kinds = (0..34)
+                .filter(|kind| hand[*kind] > 0 && Tile::new_unchecked(*kind as u8).is_yaokyuu())
+                .count();
+            ensure!(kinds >= 9, "the player holds {kinds} kinds of terminals and honors, not nine");
+        }
+        Abortive::SuufonRenda => {
+            ensure!(!events.iter().any(is_call), "four winds only abort before any call");
+            let discards: Vec<Tile> = events
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Dahai { pai, .. } => Some(*pai),
+                    _ => None,
+                })
+                .collect();
+            ensure!(
+                !events
+                    .iter()
+                    .any(|event| matches!(event, Event::Dahai { actor, .. } if *actor == player_id)),
+                "four winds only abort on the first go-around, before the player's first discard"
+            );
+            let wind = match discards.first() {
+                Some(first) => first.deaka().as_usize(),
+                None => (tu8!(E) as usize..=tu8!(N) as usize)
+                    .find(|kind| held_tile(events, player_id, *kind).is_some())
+                    .context("the player holds no wind to open with")?,
+            };
+            ensure!(
+                (tu8!(E) as usize..=tu8!(N) as usize).contains(&wind)
+                    && discards.iter().all(|discard| discard.deaka().as_usize() == wind),
+                "the first discards are not all the same wind"
+            );
+            let pai = held_tile(events, player_id, wind).context("the player does not hold the wind to discard")?;
+            events.push(Event::Dahai {
+                actor: player_id,
+                pai,
+                tsumogiri: pai == drawn,
+            });
+            let mut pool = hidden_pool(events, &[]);
+            let mut seat = player_id;
+            for _ in discards.len() + 1..4 {
+                seat = next(seat);
+                ensure!(pool.0[wind] > 0, "every copy of the wind is already seen");
+                pool.0[wind] -= 1;
+                let pai = Tile::new_unchecked(wind as u8);
+                events.push(Event::Tsumo { actor: seat, pai });
+                events.push(Event::Dahai {
+                    actor: seat,
+                    pai,
+                    tsumogiri: true,
+                });
+            }
+        }
+        Abortive::SuuchaRiichi => {
+            let mut riichi = [false; 4];
+            for event in events.iter() {
+                if let Event::ReachAccepted { actor } = event {
+                    riichi[*actor as usize] = true;
+                }
+            }
+            let declaring: Vec<u8> = (0..4).filter(|seat| !riichi[*seat as usize]).collect();
+            let scores = events.iter().rev().find_map(|event| match event {
+                Event::StartKyoku { scores, .. } => Some(*scores),
+                _ => None,
+            });
+            for seat in &declaring {
+                ensure!(
+                    !events.iter().any(|event| is_open_call_by(event, *seat)),
+                    "seat {seat} has called, it cannot declare riichi"
+                );
+                let score = scores.map_or(0, |scores| scores[*seat as usize]);
+                ensure!(
+                    score >= 1000,
+                    "seat {seat} has {score} points, a riichi needs 1000 for the deposit"
+                );
+            }
+            let others: Vec<u8> = declaring.iter().copied().filter(|seat| *seat != player_id).collect();
+            let mut pool = hidden_pool(events, &others);
+            for seat in &others {
+                deal_kept(rng, events, *seat, Some(0), &mut pool)?;
+            }
+
+            if riichi[player_id as usize] {
+                events.push(Event::Dahai {
+                    actor: player_id,
+                    pai: drawn,
+                    tsumogiri: true,
+                });
+            } else {
+                let (mut hand, len) = concealed_counts(events, player_id);
+                let mut tenpai_after = |kind: usize| {
+                    hand[kind] -= 1;
+                    let tenpai = calc_all(&hand, ((len - 1) / 3) as u8) == 0;
+                    hand[kind] += 1;
+                    tenpai
+                };
+                let kind = std::iter::once(drawn.deaka().as_usize())
+                    .chain(0..34)
+                    .filter(|kind| held_tile(events, player_id, *kind).is_some())
+                    .find(|kind| tenpai_after(*kind))
+                    .context("the player has no discard that leaves them tenpai for riichi")?;
+                let pai = if kind == drawn.deaka().as_usize() {
+                    drawn
+                } else {
+                    held_tile(events, player_id, kind).context("the player does not hold the riichi discard")?
+                };
+                events.push(Event::Reach { actor: player_id });
+                events.push(Event::Dahai {
+                    actor: player_id,
+                    pai,
+                    tsumogiri: pai == drawn,
+                });
+                events.push(Event::ReachAccepted { actor: player_id });
+            }
+            // every seat is tenpai now or after its riichi, the closing draws neither win nor pass a ron
+            let hands: Vec<([u8; 34], usize)> = (0..players(events)).map(|seat| concealed_counts(events, seat)).collect();
+            let wins = |kind: usize| {
+                hands.iter().any(|(hand, len)| {
+                    let mut hand = *hand;
+                    hand[kind] += 1;
+                    calc_all(&hand, (len / 3 + 1) as u8) == -1
+                })
+            };
+            let mut seat = player_id;
+            for _ in 0..3 {
+                seat = next(seat);
+                ensure!(draws < DRAWS, "the wall runs out before the fourth riichi");
+                draws += 1;
+                let pai = draw_pool(rng, &mut pool, aka, |kind| !wins(kind))?;
+                events.push(Event::Tsumo { actor: seat, pai });
+                let declares = others.contains(&seat);
+                if declares {
+                    events.push(Event::Reach { actor: seat });
+                }
+                events.push(Event::Dahai {
+                    actor: seat,
+                    pai,
+                    tsumogiri: true,
+                });
+                if declares {
+                    events.push(Event::ReachAccepted { actor: seat });
+                }
+            }
+        }
+        Abortive::Suukaikan => {
+            let kanners: Vec<u8> = events
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Daiminkan { actor, .. } | Event::Kakan { actor, .. } | Event::Ankan { actor, .. } => Some(*actor),
+                    _ => None,
+                })
+                .collect();
+            ensure!(
+                kanners.len() == 3 && kanners.iter().any(|seat| *seat != player_id),
+                "four kans abort when three are on the table, not all the player's, and the player makes the fourth"
+            );
+            let (hand, _) = concealed_counts(events, player_id);
+            let kind = (0..34)
+                .find(|kind| hand[*kind] == 4)
+                .context("the player holds no four of a kind for the fourth kan")?;
+            let consumed: Vec<Tile> = kept_sources(events, player_id)
+                .into_iter()
+                .map(|(tile, _)| tile)
+                .filter(|tile| tile.deaka().as_usize() == kind)
+                .collect();
+            let mut pool = hidden_pool(events, &[]);
+            events.push(Event::Ankan {
+                actor: player_id,
+                consumed: [consumed[0], consumed[1], consumed[2], consumed[3]],
+            });
+            let dora_marker = draw_pool(rng, &mut pool, aka, |_| true)?;
+            events.push(Event::Dora { dora_marker });
+            let pai = draw_pool(rng, &mut pool, aka, |_| true)?;
+            events.push(Event::Tsumo { actor: player_id, pai });
+            events.push(Event::Dahai {
+                actor: player_id,
+                pai,
+                tsumogiri: true,
+            });
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use riichi::convlog::generate::Board;
+use riichi::tile::Tile;
+use riichi::tu8;
+
+use crate::fuuro::split_melds;
+use crate::html::glyph;
+
+// The board as a standalone svg image, seated like the tenhou viewer: the player at the bottom and the others
+// counterclockwise from the right, each with their kawa around the center and their hand and calls at the
+// edge. Only the player's hand is face up; riichi discards lie sideways and tsumogiri ones are shaded.
+
+const SIZE: i32 = 600;
+const CENTER: i32 = SIZE / 2;
+const W: i32 = 24;
+const H: i32 = 32;
+
+fn tile_svg(x: i32, y: i32, tile: Option<Tile>, sideways: bool, shaded: bool) -> String {
+    let (w, h) = if sideways { (H, W) } else { (W, H) };
+    // sideways tiles share the baseline of the upright ones
+    let y = y + H - h;
+    let fill = match tile {
+        None => "#2f6f4f",
+        Some(_) if shaded => "#ddd",
+        Some(_) => "#fff",
+    };
+    let mut svg = format!("<rect x=\"{x}\" y=\"{y}\" width=\"{w}\" height=\"{h}\" rx=\"3\" fill=\"{fill}\" stroke=\"#555\"/>");
+    if let Some(tile) = tile {
+        let color = if tile.is_aka() { "#c00" } else { "#222" };
+        let (cx, cy) = (x + w / 2, y + h / 2);
+        let rotate = if sideways {
+            format!(" transform=\"rotate(90 {cx} {cy})\"")
+        } else {
+            String::new()
+        };
+        svg.push_str(&format!(
+            "<text x=\"{cx}\" y=\"{cy}\" font-size=\"26\" text-anchor=\"middle\" dominant-baseline=\"central\" \
+             fill=\"{color}\"{rotate}>{}</text>",
+            glyph(tile)
+        ));
+    }
+    svg
+}
+
+fn text_svg(x: i32, y: i32, size: i32, text: &str) -> String {
+    format!("<text x=\"{x}\" y=\"{y}\" font-size=\"{size}\" text-anchor=\"middle\" fill=\"#222\">{text}</text>")
+}
+
+// One seat drawn as if it were the player's, at the bottom; the caller rotates it into place.
+fn seat_svg(board: &Board, seat: usize, wind: &str) -> String {
+    let mut svg = text_svg(CENTER, CENTER + 56, 14, &format!("{wind} {}", board.scores[seat]));
+
+    let (mut x, mut y) = (CENTER - 3 * W, CENTER + 72);
+    for (i, sutehai) in board.kawa[seat].iter().enumerate() {
+        // six to a row, the last row running on
+        if i > 0 && i % 6 == 0 && i < 18 {
+            x = CENTER - 3 * W;
+            y += H;
+        }
+        svg.push_str(&tile_svg(x, y, Some(sutehai.pai), sutehai.riichi, !sutehai.tedashi));
+        x += if sutehai.riichi { H } else { W };
+    }
+
+    let fuuro = &board.fuuro[seat].tiles;
+    let melds = split_melds(fuuro).map_or(0, |melds| melds.len());
+    let y = SIZE - H - 12;
+    if seat == 0 {
+        for (i, tile) in board.tehai.iter().enumerate() {
+            svg.push_str(&tile_svg(60 + i as i32 * W, y, Some(*tile), false, false));
+        }
+    } else {
+        for i in 0..13usize.saturating_sub(3 * melds) {
+            svg.push_str(&tile_svg(60 + i as i32 * W, y, None, false, false));
+        }
+    }
+    let mut x = SIZE - 20;
+    for fuurohai in fuuro.iter().rev() {
+        x -= if fuurohai.sideways { H } else { W };
+        svg.push_str(&tile_svg(x, y, Some(fuurohai.tile), fuurohai.sideways, false));
+    }
+    svg
+}
+
+pub(crate) fn board_to_svg(board: &Board) -> String {
+    let jikaze = (board.jikaze.as_u8() - tu8!(E)) as usize;
+    let winds = ["E", "S", "W", "N"];
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{SIZE}\" height=\"{SIZE}\" viewBox=\"0 0 {SIZE} {SIZE}\" \
+         font-family=\"sans-serif\"><rect width=\"{SIZE}\" height=\"{SIZE}\" fill=\"#3a7d5c\"/>\
+         <rect x=\"{}\" y=\"{}\" width=\"132\" height=\"132\" rx=\"6\" fill=\"#fafaf5\"/>",
+        CENTER - 66,
+        CENTER - 66
+    );
+    svg.push_str(&text_svg(
+        CENTER,
+        CENTER - 36,
+        16,
+        &format!("{}{}", board.bakaze, board.kyoku),
+    ));
+    svg.push_str(&text_svg(
+        CENTER,
+        CENTER - 18,
+        12,
+        &format!("honba {} · kyotaku {}", board.honba, board.kyotaku),
+    ));
+    let dora_x = CENTER - board.dora_indicators.len() as i32 * W / 2;
+    for (i, tile) in board.dora_indicators.iter().enumerate() {
+        svg.push_str(&tile_svg(dora_x + i as i32 * W, CENTER - 8, Some(*tile), false, false));
+    }
+    for seat in 0..4 {
+        svg.push_str(&format!(
+            "<g transform=\"rotate({} {CENTER} {CENTER})\">{}</g>",
+            -90 * seat as i32,
+            seat_svg(board, seat, winds[(jikaze + seat) % 4])
+        ));
+    }
+    svg.push_str("</svg>");
+    svg
+}
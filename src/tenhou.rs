@@ -0,0 +1,414 @@
+use anyhow::{bail, ensure, Context, Result};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+use serde_json::{json, Value};
+
+use crate::cache::fnv1a;
+use crate::events::kyoku_range;
+use crate::replay::final_hands;
+
+// Edits on the emitted tenhou/6 json: {"title": [..], "name": [..], "rule": {..}, "log": [..]}.
+
+pub(crate) fn set_title(log: &mut Value, title: &[String]) {
+    log["title"] = json!(title);
+}
+
+// `names` are relative to `player_id`, like the seats of a RawBoard.
+pub(crate) fn set_names(log: &mut Value, names: &[String], player_id: u8) {
+    let names: Vec<Value> = names.iter().map(|name| json!(name)).collect();
+    set_seats(log, "name", &names, player_id, json!(""));
+}
+
+// A per-seat header array such as "dan", "rate" or "sx", keeping what `values` leaves out.
+pub(crate) fn set_seats(log: &mut Value, key: &str, values: &[Value], player_id: u8, default: Value) {
+    let mut absolute: Vec<Value> = log[key].as_array().cloned().unwrap_or_default();
+    absolute.resize(4, default);
+    for (seat, value) in values.iter().enumerate().take(4) {
+        absolute[(player_id as usize + seat) % 4] = value.clone();
+    }
+    log[key] = json!(absolute);
+}
+
+// Labels the result of the last kyoku as the abortive draw `label`, e.g. 九種九牌, which pays nothing.
+pub(crate) fn set_abortive(log: &mut Value, label: &str) {
+    if let Some(result) = log["log"]
+        .as_array_mut()
+        .and_then(|kyokus| kyokus.last_mut())
+        .and_then(Value::as_array_mut)
+        .and_then(|kyoku| kyoku.last_mut())
+    {
+        *result = json!([label]);
+    }
+}
+
+// Has the loser of every multiple ron also pay `honba_value` per honba to the winners after the first, which
+// the conversion leaves out like tenhou does. rules::apply_honba already paid the wins `events` score, whose
+// deltas are taken as they are.
+pub(crate) fn pay_honba_each(log: &mut Value, events: &[Event], honba_value: i32) {
+    let mut horas: Vec<Vec<Option<[i32; 4]>>> = vec![];
+    for event in events {
+        match event {
+            Event::StartKyoku { .. } => horas.push(vec![]),
+            Event::Hora { deltas, .. } => horas.last_mut().into_iter().for_each(|kyoku| kyoku.push(*deltas)),
+            _ => {}
+        }
+    }
+    let Some(kyokus) = log["log"].as_array_mut() else {
+        return;
+    };
+    for (kyoku, horas) in kyokus.iter_mut().zip(horas) {
+        let honba = kyoku[0][1].as_i64().unwrap_or(0) as i32;
+        let Some(result) = kyoku
+            .as_array_mut()
+            .and_then(|kyoku| kyoku.last_mut())
+            .and_then(Value::as_array_mut)
+        else {
+            continue;
+        };
+        if honba == 0 || result.first().and_then(Value::as_str) != Some("和了") {
+            continue;
+        }
+        // [label, deltas, info, deltas, info, ..], info starting with the winner and the loser
+        for (win, deltas) in result[1..].chunks_exact_mut(2).zip(horas).skip(1) {
+            if let Some(deltas) = deltas {
+                win[0] = json!(deltas);
+                continue;
+            }
+            let (Some(winner), Some(loser)) = (win[1][0].as_u64(), win[1][1].as_u64()) else {
+                continue;
+            };
+            if winner == loser {
+                continue;
+            }
+            for (seat, sign) in [(winner, 1), (loser, -1)] {
+                if let Some(delta) = win[0][seat as usize].as_i64() {
+                    win[0][seat as usize] = json!(delta as i32 + sign * honba_value * honba);
+                }
+            }
+        }
+    }
+}
+
+// One header field per line and one line per kyoku entry (meta, scores, dora, haipai, takes, ...).
+pub(crate) fn to_string_pretty(log: &Value) -> String {
+    let Some(object) = log.as_object() else {
+        return log.to_string();
+    };
+    let fields: Vec<String> = object
+        .iter()
+        .map(|(key, value)| match (key.as_str(), value.as_array()) {
+            ("log", Some(kyokus)) => {
+                let kyokus: Vec<String> = kyokus
+                    .iter()
+                    .map(|kyoku| match kyoku.as_array() {
+                        Some(entries) => {
+                            let entries: Vec<String> = entries.iter().map(|entry| format!("      {entry}")).collect();
+                            format!("    [\n{}\n    ]", entries.join(",\n"))
+                        }
+                        None => format!("    {kyoku}"),
+                    })
+                    .collect();
+                format!("  \"log\": [\n{}\n  ]", kyokus.join(",\n"))
+            }
+            _ => format!("  {}: {value}", Value::String(key.clone())),
+        })
+        .collect();
+    format!("{{\n{}\n}}", fields.join(",\n"))
+}
+
+// The tenhou code of a red five, 51 to 53.
+fn aka_code(tile: Tile) -> Option<&'static str> {
+    match tile.as_u8() {
+        tu8!(5mr) => Some("51"),
+        tu8!(5pr) => Some("52"),
+        tu8!(5sr) => Some("53"),
+        _ => None,
+    }
+}
+
+// Writes the red five of `call`, e.g. "p252525", back in as 52. `called` is where the called or added
+// tile's code starts when that tile is the red one.
+fn with_aka(call: &str, aka: &str, called: bool) -> String {
+    let plain = format!("{}5", &aka[1..]);
+    let codes = call_codes(call);
+    let position = if called {
+        codes
+            .iter()
+            .position(|(letter, code)| *letter && code.ends_with(plain.as_str()))
+    } else {
+        codes.iter().position(|(letter, code)| !*letter && *code == plain)
+    };
+    let Some(position) = position else {
+        return call.to_owned();
+    };
+    codes
+        .iter()
+        .enumerate()
+        .map(|(i, (letter, code))| match (i == position, letter) {
+            (true, true) => format!("{}{aka}", &code[..1]),
+            (true, false) => aka.to_owned(),
+            _ => code.to_string(),
+        })
+        .collect()
+}
+
+// The tile codes of a call string, each with whether a letter marking the called or added tile leads it, e.g.
+// "c141516" as (true, "c14"), (false, "15"), (false, "16").
+fn call_codes(call: &str) -> Vec<(bool, &str)> {
+    let mut codes = vec![];
+    let mut rest = call;
+    while !rest.is_empty() {
+        let letter = rest.starts_with(|c: char| c.is_ascii_lowercase());
+        let len = if letter { 3 } else { 2 }.min(rest.len());
+        codes.push((letter, &rest[..len]));
+        rest = &rest[len..];
+    }
+    codes
+}
+
+// Whether one of the tiles of `call` is the red five `aka`.
+fn has_aka(call: &str, aka: &str) -> bool {
+    call_codes(call)
+        .iter()
+        .any(|(letter, code)| if *letter { &code[1..] == aka } else { *code == aka })
+}
+
+// A call of one seat: whether tenhou writes it in the takes rather than the discards, and its red five's
+// code with whether the red five is the called or added tile.
+struct MeldAka {
+    take: bool,
+    aka: Option<(&'static str, bool)>,
+}
+
+// Puts the red fives of the calls in `events` back into the call strings of `log` when the conversion wrote
+// them as plain fives, so the viewer shows the red tile in the meld.
+pub(crate) fn restore_meld_aka(log: &mut Value, events: &[Event]) {
+    let Some(kyokus) = log["log"].as_array_mut() else {
+        return;
+    };
+    let starts: Vec<usize> = events
+        .iter()
+        .enumerate()
+        .filter(|(_, event)| matches!(event, Event::StartKyoku { .. }))
+        .map(|(i, _)| i)
+        .collect();
+    for (k, kyoku) in kyokus.iter_mut().enumerate() {
+        let Some(&start) = starts.get(k) else {
+            break;
+        };
+        let end = starts.get(k + 1).copied().unwrap_or(events.len());
+        let mut calls: [Vec<MeldAka>; 4] = Default::default();
+        for event in &events[start..end] {
+            let (actor, take, called, consumed) = match event {
+                Event::Chi {
+                    actor, pai, consumed, ..
+                }
+                | Event::Pon {
+                    actor, pai, consumed, ..
+                } => (*actor, true, Some(*pai), consumed.to_vec()),
+                Event::Daiminkan {
+                    actor, pai, consumed, ..
+                } => (*actor, true, Some(*pai), consumed.to_vec()),
+                Event::Kakan { actor, pai, consumed } => (*actor, false, Some(*pai), consumed.to_vec()),
+                Event::Ankan { actor, consumed } => (*actor, false, None, consumed.to_vec()),
+                _ => continue,
+            };
+            let aka = match called.and_then(aka_code) {
+                Some(aka) => Some((aka, true)),
+                None => consumed.iter().find_map(|tile| aka_code(*tile)).map(|aka| (aka, false)),
+            };
+            calls[actor as usize].push(MeldAka { take, aka });
+        }
+        let Some(entries) = kyoku.as_array_mut() else {
+            continue;
+        };
+        for (seat, calls) in calls.iter().enumerate() {
+            for take in [true, false] {
+                let index = if take { 5 } else { 6 } + seat * 3;
+                let Some(strings) = entries.get_mut(index).and_then(Value::as_array_mut) else {
+                    continue;
+                };
+                let strings = strings
+                    .iter_mut()
+                    .filter(|entry| entry.as_str().is_some_and(|call| !call.starts_with('r')));
+                for (entry, meld) in strings.zip(calls.iter().filter(|meld| meld.take == take)) {
+                    let (Some(call), Some((aka, called))) = (entry.as_str(), meld.aka) else {
+                        continue;
+                    };
+                    if !has_aka(call, aka) {
+                        *entry = json!(with_aka(call, aka, called));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The tenhou code of any tile, 11 to 47 with red fives as 51 to 53.
+fn tile_code(tile: Tile) -> u64 {
+    if let Some(aka) = aka_code(tile) {
+        return aka.parse().unwrap_or_default();
+    }
+    let kind = tile.as_u8() as u64;
+    match kind {
+        0..27 => kind / 9 * 10 + 11 + kind % 9,
+        _ => 41 + kind - 27,
+    }
+}
+
+// Adds "final_hands", per kyoku the concealed tiles every seat holds once it is over, so a viewer of the log
+// can show what the opponents were holding.
+pub(crate) fn set_final_hands(log: &mut Value, events: &[Event]) {
+    let kyokus: Vec<Value> = (0..)
+        .map_while(|index| kyoku_range(events, index))
+        .map(|range| {
+            let hands: Vec<Vec<u64>> = final_hands(&events[range])
+                .iter()
+                .map(|hand| {
+                    let mut codes: Vec<u64> = hand.iter().map(|tile| tile_code(*tile)).collect();
+                    codes.sort_unstable_by_key(|code| if *code > 50 { (code - 50) * 10 + 5 } else { *code });
+                    codes
+                })
+                .collect();
+            json!(hands)
+        })
+        .collect();
+    log["final_hands"] = json!(kyokus);
+}
+
+// Takes the haipai, takes and discards out of seat 3 of every kyoku, which three player logs leave empty.
+pub(crate) fn empty_seat(log: &mut Value) {
+    let Some(kyokus) = log["log"].as_array_mut() else {
+        return;
+    };
+    for kyoku in kyokus {
+        for entry in 13..16 {
+            kyoku[entry] = json!([]);
+        }
+    }
+}
+
+fn is_tile_code(code: u64) -> bool {
+    matches!(code, 11..=19 | 21..=29 | 31..=39 | 41..=47 | 51..=53)
+}
+
+// Plain tiles or call strings ("c121314", "p151515", "m15151515", "r15", "a15151515", "k15151515", ...).
+fn check_entries(entries: &[Value], allow_tsumogiri: bool, what: &str) -> Result<()> {
+    for (i, entry) in entries.iter().enumerate() {
+        match entry {
+            Value::Number(code) => {
+                let code = code.as_u64().unwrap_or_default();
+                ensure!(
+                    is_tile_code(code) || (allow_tsumogiri && (code == 60 || code == 0)),
+                    "{what}[{i}] is not a tile code: {code}"
+                );
+            }
+            Value::String(call) => ensure!(
+                call.chars().any(|c| c.is_ascii_lowercase()),
+                "{what}[{i}] is not a call: {call:?}"
+            ),
+            _ => bail!("{what}[{i}] must be a tile code or a call string"),
+        }
+    }
+    Ok(())
+}
+
+// Checks the constraints the tenhou viewer silently relies on; it renders a blank page when any of them is broken.
+pub(crate) fn validate(log: &Value) -> Result<()> {
+    let kyokus = log["log"].as_array().context("log must be an array of kyokus")?;
+    ensure!(!kyokus.is_empty(), "log has no kyokus");
+    for (k, kyoku) in kyokus.iter().enumerate() {
+        let entries = kyoku.as_array().with_context(|| format!("log[{k}] must be an array"))?;
+        ensure!(
+            entries.len() == 17,
+            "log[{k}] has {} entries, the viewer expects 17 (meta, scores, dora, ura, 3 per seat, result)",
+            entries.len()
+        );
+        let meta = entries[0]
+            .as_array()
+            .with_context(|| format!("log[{k}] meta must be an array"))?;
+        ensure!(
+            meta.len() == 3 && meta.iter().all(Value::is_u64),
+            "log[{k}] meta must be [kyoku, honba, kyotaku]"
+        );
+        let scores = entries[1]
+            .as_array()
+            .with_context(|| format!("log[{k}] scores must be an array"))?;
+        ensure!(
+            scores.len() == 4 && scores.iter().all(Value::is_i64),
+            "log[{k}] needs 4 integer scores"
+        );
+        let dora = entries[2]
+            .as_array()
+            .with_context(|| format!("log[{k}] dora must be an array"))?;
+        let ura = entries[3]
+            .as_array()
+            .with_context(|| format!("log[{k}] ura must be an array"))?;
+        ensure!(
+            (1..=5).contains(&dora.len()),
+            "log[{k}] has {} dora indicators, expected 1 to 5",
+            dora.len()
+        );
+        ensure!(
+            ura.len() <= dora.len(),
+            "log[{k}] has {} ura indicators but only {} dora indicators",
+            ura.len(),
+            dora.len()
+        );
+        check_entries(dora, false, &format!("log[{k}] dora"))?;
+        check_entries(ura, false, &format!("log[{k}] ura"))?;
+
+        let mut extra_draws = 0;
+        for seat in 0..4 {
+            let haipai = entries[4 + seat * 3]
+                .as_array()
+                .with_context(|| format!("log[{k}] haipai of seat {seat} must be an array"))?;
+            let takes = entries[5 + seat * 3]
+                .as_array()
+                .with_context(|| format!("log[{k}] takes of seat {seat} must be an array"))?;
+            let discards = entries[6 + seat * 3]
+                .as_array()
+                .with_context(|| format!("log[{k}] discards of seat {seat} must be an array"))?;
+            // nobody sits at seat 3 of a three player game
+            if seat == 3 && haipai.is_empty() && takes.is_empty() && discards.is_empty() && scores[3] == 0 {
+                continue;
+            }
+            ensure!(
+                haipai.len() == 13,
+                "log[{k}] haipai of seat {seat} has {} tiles, expected 13",
+                haipai.len()
+            );
+            check_entries(haipai, false, &format!("log[{k}] haipai of seat {seat}"))?;
+            check_entries(takes, false, &format!("log[{k}] takes of seat {seat}"))?;
+            check_entries(discards, true, &format!("log[{k}] discards of seat {seat}"))?;
+            ensure!(
+                discards.len() <= takes.len() && takes.len() <= discards.len() + 1,
+                "log[{k}] seat {seat} has {} takes and {} discards, every take but the last needs a discard",
+                takes.len(),
+                discards.len()
+            );
+            extra_draws += takes.len() - discards.len();
+        }
+        ensure!(
+            extra_draws <= 1,
+            "log[{k}] has {extra_draws} seats holding an undiscarded take, at most one seat can"
+        );
+
+        let result = entries[16]
+            .as_array()
+            .with_context(|| format!("log[{k}] result must be an array"))?;
+        ensure!(
+            result.first().is_some_and(Value::is_string),
+            "log[{k}] result must start with its label, e.g. 和了 or 流局"
+        );
+    }
+    Ok(())
+}
+
+// Identifies a game by what was played, so regenerating it from the same board gives the same id whatever
+// title or names it got. Only the kyokus and the rule count; 64-bit fnv-1a of their compact json.
+pub(crate) fn log_id(log: &Value) -> String {
+    let content = json!({ "log": log["log"], "rule": log["rule"] });
+    format!("paifu-{:016x}", fnv1a(content.to_string().as_bytes()))
+}
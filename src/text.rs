@@ -0,0 +1,213 @@
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Context, Result};
+use riichi::tile::Tile;
+use riichi::tu8;
+
+use crate::fuuro::{meld_to_string, parse_fuuro, split_melds};
+use crate::RawBoard;
+
+// Labeled text form of a RawBoard, one field per line:
+//
+//     kyoku: S3
+//     jikaze: W
+//     dora: 1m
+//     tehai: 123m456p789s11z
+//     scores e: 25000
+//     kawa e: 1p 9s. 5m-
+//     fuuro s: (1p)2p3p 5z5z5z5z
+//
+// Per-seat fields are labeled with the seat wind (e, s, w, n) instead of RawBoard's relative order, and
+// their tokens are joined back together. Blank lines and lines starting with # are ignored.
+
+fn seat_wind(label: &str) -> Result<u8> {
+    match label {
+        "e" | "E" => Ok(0),
+        "s" | "S" => Ok(1),
+        "w" | "W" => Ok(2),
+        "n" | "N" => Ok(3),
+        _ => bail!("unknown seat wind {label:?}, expected e, s, w or n"),
+    }
+}
+
+fn set_once(field: &mut Option<String>, label: &str, value: String) -> Result<()> {
+    ensure!(field.is_none(), "{label} is given twice");
+    *field = Some(value);
+    Ok(())
+}
+
+pub(crate) fn parse_board(text: &str) -> Result<RawBoard> {
+    let mut fields: [Option<String>; 16] = Default::default();
+    let mut seats: [[Option<String>; 4]; 5] = Default::default();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (label, value) = line
+            .split_once(':')
+            .with_context(|| format!("line {}: expected <label>: <value>", number + 1))?;
+        let value = value.split_whitespace().collect::<Vec<_>>().join("");
+        let mut label_parts = label.split_whitespace();
+        let name = label_parts.next().unwrap_or_default();
+        let seat = label_parts.next();
+        ensure!(label_parts.next().is_none(), "line {}: unknown label {label:?}", number + 1);
+        let field = match (name, seat) {
+            ("kyoku", None) => &mut fields[0],
+            ("jikaze", None) => &mut fields[1],
+            ("kyotaku", None) => &mut fields[2],
+            ("honba", None) => &mut fields[3],
+            ("dora", None) => &mut fields[4],
+            ("tehai", None) => &mut fields[5],
+            ("junme", None) => &mut fields[6],
+            ("chiicha", None) => &mut fields[7],
+            ("claim", None) => &mut fields[8],
+            ("fill_avoid", None) => &mut fields[9],
+            ("fill_genbutsu", None) => &mut fields[10],
+            ("bots", None) => &mut fields[11],
+            ("players", None) => &mut fields[12],
+            ("uradora", None) => &mut fields[13],
+            ("tsumo", None) => &mut fields[14],
+            ("deposits", None) => &mut fields[15],
+            ("scores" | "kawa" | "fuuro" | "nuki" | "draws", Some(seat)) => {
+                let wind = seat_wind(seat).with_context(|| format!("line {}", number + 1))?;
+                let kind = match name {
+                    "scores" => 0,
+                    "kawa" => 1,
+                    "fuuro" => 2,
+                    "nuki" => 3,
+                    _ => 4,
+                };
+                &mut seats[kind][wind as usize]
+            }
+            _ => bail!("line {}: unknown label {label:?}", number + 1),
+        };
+        set_once(field, label.trim(), value).with_context(|| format!("line {}", number + 1))?;
+    }
+
+    let [kyoku, jikaze, kyotaku, honba, dora, tehai, junme, chiicha, claim, fill_avoid, fill_genbutsu, bots, players, uradora, tsumo, deposits] =
+        fields.map(Option::unwrap_or_default);
+    let jikaze_wind = match Tile::from_str(&jikaze) {
+        Ok(tile) if (tu8!(E)..=tu8!(N)).contains(&tile.as_u8()) => tile.as_u8() - tu8!(E),
+        _ => bail!("jikaze must be one of E, S, W or N"),
+    };
+    // three players sit at E, S and W
+    let count = if players == "3" { 3 } else { 4 };
+    ensure!((jikaze_wind as usize) < count, "a three player board has no North seat");
+    ensure!(
+        seats.iter().all(|by_wind| by_wind[count..].iter().all(Option::is_none)),
+        "a three player board has no North seat"
+    );
+    // RawBoard seats start at the focused player and go counterclockwise
+    let relative = |by_wind: [Option<String>; 4]| -> Vec<String> {
+        let by_wind = by_wind.map(Option::unwrap_or_default);
+        (0..count)
+            .map(|seat| by_wind[(jikaze_wind as usize + seat) % count].clone())
+            .collect()
+    };
+    let [scores, kawa, fuuro, nuki, draws] = seats.map(relative);
+    Ok(RawBoard {
+        kyoku,
+        jikaze,
+        kyotaku,
+        honba,
+        dora,
+        scores,
+        tehai,
+        kawa,
+        fuuro,
+        junme,
+        chiicha,
+        claim,
+        fill_avoid,
+        fill_genbutsu,
+        bots,
+        players,
+        nuki,
+        uradora,
+        tsumo,
+        deposits,
+        draws,
+    })
+}
+
+// "1p9s.5m-2s!" -> ["1p", "9s.", "5m-", "2s!"]
+fn kawa_tokens(raw_kawa: &str) -> Option<Vec<String>> {
+    let mut tokens = vec![];
+    let mut chars = raw_kawa.chars().peekable();
+    while let Some(first) = chars.next() {
+        let mut token = format!("{first}{}", chars.next()?);
+        if let Some(marker) = chars.next_if(|c| matches!(c, '.' | '-' | '!')) {
+            token.push(marker);
+        }
+        tokens.push(token);
+    }
+    Some(tokens)
+}
+
+// one token per meld, e.g. "(1p)2p3p 5z5z5z5z"
+fn fuuro_tokens(raw_fuuro: &str) -> Option<Vec<String>> {
+    let tiles = parse_fuuro(raw_fuuro).ok()?;
+    let melds = split_melds(&tiles).ok()?;
+    Some(melds.iter().map(|meld| meld_to_string(meld.tiles)).collect())
+}
+
+// Renders `board` in the format read by `parse_board`, labels aligned and empty fields left out.
+// Fields that do not tokenize are written as given, so parsing the text back yields an equivalent board.
+pub(crate) fn board_to_text(board: &RawBoard) -> Result<String> {
+    let jikaze_wind = match Tile::from_str(&board.jikaze) {
+        Ok(tile) if (tu8!(E)..=tu8!(N)).contains(&tile.as_u8()) => (tile.as_u8() - tu8!(E)) as usize,
+        _ => bail!("jikaze must be one of E, S, W or N"),
+    };
+    let count = if board.players == "3" { 3 } else { 4 };
+    ensure!(jikaze_wind < count, "a three player board has no North seat");
+    let mut lines: Vec<(String, String)> = [
+        ("kyoku", &board.kyoku),
+        ("jikaze", &board.jikaze),
+        ("kyotaku", &board.kyotaku),
+        ("honba", &board.honba),
+        ("dora", &board.dora),
+        ("tehai", &board.tehai),
+        ("tsumo", &board.tsumo),
+        ("junme", &board.junme),
+        ("chiicha", &board.chiicha),
+        ("claim", &board.claim),
+        ("fill_avoid", &board.fill_avoid),
+        ("fill_genbutsu", &board.fill_genbutsu),
+        ("bots", &board.bots),
+        ("players", &board.players),
+        ("uradora", &board.uradora),
+        ("deposits", &board.deposits),
+    ]
+    .into_iter()
+    .map(|(label, value)| (label.to_owned(), value.clone()))
+    .collect();
+    for (name, values) in [
+        ("scores", &board.scores),
+        ("kawa", &board.kawa),
+        ("fuuro", &board.fuuro),
+        ("nuki", &board.nuki),
+        ("draws", &board.draws),
+    ] {
+        for (wind, wind_label) in ["e", "s", "w", "n"].into_iter().enumerate().take(count) {
+            let Some(value) = values.get((wind + count - jikaze_wind) % count) else {
+                continue;
+            };
+            let tokens = match name {
+                "kawa" => kawa_tokens(value),
+                "fuuro" => fuuro_tokens(value),
+                _ => None,
+            };
+            let value = tokens.map_or_else(|| value.clone(), |tokens| tokens.join(" "));
+            lines.push((format!("{name} {wind_label}"), value));
+        }
+    }
+    lines.retain(|(_, value)| !value.is_empty());
+
+    let width = lines.iter().map(|(label, _)| label.len() + 1).max().unwrap_or_default();
+    let text: Vec<String> = lines
+        .iter()
+        .map(|(label, value)| format!("{:width$} {value}", format!("{label}:")))
+        .collect();
+    Ok(text.join("\n") + "\n")
+}
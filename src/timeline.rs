@@ -0,0 +1,108 @@
+use riichi::mjai::Event;
+use serde::Serialize;
+
+use crate::tiles_vec_to_string;
+
+#[derive(Serialize)]
+pub(crate) struct TimelineDiscard {
+    pub tile: String,
+    // draws the seat had made by this discard, so discards right after a call share the turn before it
+    pub junme: usize,
+    pub tsumogiri: bool,
+    pub riichi: bool,
+    // taken by a chi, pon or daiminkan
+    pub called: bool,
+    // index of the Dahai among the kyoku's events
+    pub event_index: usize,
+}
+
+// Every seat's discards in one kyoku, by absolute seat.
+pub(crate) fn discard_timelines(events: &[Event]) -> [Vec<TimelineDiscard>; 4] {
+    let mut timelines: [Vec<TimelineDiscard>; 4] = Default::default();
+    let mut draws = [0; 4];
+    let mut declared = [false; 4];
+    for (index, event) in events.iter().enumerate() {
+        match event {
+            Event::Tsumo { actor, .. } => draws[*actor as usize] += 1,
+            Event::Reach { actor } => declared[*actor as usize] = true,
+            Event::Dahai { actor, pai, tsumogiri } => {
+                let seat = *actor as usize;
+                let called = matches!(
+                    events.get(index + 1),
+                    Some(Event::Chi { target, .. } | Event::Pon { target, .. } | Event::Daiminkan { target, .. })
+                        if target == actor
+                );
+                timelines[seat].push(TimelineDiscard {
+                    tile: tiles_vec_to_string(&[*pai]),
+                    junme: draws[seat],
+                    tsumogiri: *tsumogiri,
+                    riichi: std::mem::take(&mut declared[seat]),
+                    called,
+                    event_index: index,
+                });
+            }
+            _ => {}
+        }
+    }
+    timelines
+}
+
+#[derive(Serialize)]
+pub struct KeyMoment {
+    // index into the mjai log
+    pub event_index: usize,
+    // riichi, chi, pon, daiminkan, ankan, kakan, dora, hora or ryukyoku
+    pub kind: &'static str,
+    // absolute seat; for dora and ryukyoku the seat whose turn it was
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor: Option<u8>,
+    pub junme: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tile: Option<String>,
+}
+
+// Notable events of the log, for scrubber markers.
+pub(crate) fn key_moments(events: &[Event]) -> Vec<KeyMoment> {
+    let mut moments = vec![];
+    let mut draws = [0; 4];
+    let mut last_actor = None;
+    for (event_index, event) in events.iter().enumerate() {
+        let (kind, actor, pai) = match event {
+            Event::StartKyoku { .. } => {
+                draws = [0; 4];
+                last_actor = None;
+                continue;
+            }
+            Event::Tsumo { actor, .. } => {
+                draws[*actor as usize] += 1;
+                last_actor = Some(*actor);
+                continue;
+            }
+            Event::Dahai { actor, .. } => {
+                last_actor = Some(*actor);
+                continue;
+            }
+            Event::Reach { actor } => ("riichi", Some(*actor), None),
+            Event::Chi { actor, pai, .. } => ("chi", Some(*actor), Some(*pai)),
+            Event::Pon { actor, pai, .. } => ("pon", Some(*actor), Some(*pai)),
+            Event::Daiminkan { actor, pai, .. } => ("daiminkan", Some(*actor), Some(*pai)),
+            Event::Ankan { actor, consumed } => ("ankan", Some(*actor), Some(consumed[0])),
+            Event::Kakan { actor, pai, .. } => ("kakan", Some(*actor), Some(*pai)),
+            Event::Dora { dora_marker } => ("dora", last_actor, Some(*dora_marker)),
+            Event::Hora { actor, .. } => ("hora", Some(*actor), None),
+            Event::Ryukyoku { .. } => ("ryukyoku", last_actor, None),
+            _ => continue,
+        };
+        if !matches!(kind, "dora" | "ryukyoku") {
+            last_actor = actor;
+        }
+        moments.push(KeyMoment {
+            event_index,
+            kind,
+            actor,
+            junme: actor.map_or(0, |actor| draws[actor as usize]),
+            tile: pai.map(|pai| tiles_vec_to_string(&[pai])),
+        });
+    }
+    moments
+}
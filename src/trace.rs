@@ -0,0 +1,151 @@
+use std::cell::RefCell;
+
+use riichi::mjai::Event;
+use serde::Serialize;
+
+use crate::replay::final_hands;
+use crate::tiles_vec_to_string;
+
+// One step of the trace of a generated log: a choice taken around the generator, noted as it is made, or an
+// event of the finished log told against the board. Seats are relative to the player, like the seats of a
+// RawBoard, so "seat 2" is the toimen and kawa[2] its discards.
+#[derive(Serialize, Clone)]
+pub struct TraceStep {
+    // the mjai event the step explains, none for what was decided around the generator
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<usize>,
+    // "board" for the noted choices; "deal", "draw" or "call" for the narration
+    pub kind: &'static str,
+    pub message: String,
+}
+
+thread_local! {
+    static TRACE: RefCell<Option<Vec<TraceStep>>> = const { RefCell::new(None) };
+}
+
+// Records notes until finish, for one generation at a time.
+pub(crate) fn start() {
+    TRACE.set(Some(vec![]));
+}
+
+pub(crate) fn finish() -> Vec<TraceStep> {
+    TRACE.take().unwrap_or_default()
+}
+
+// A decision taken outside of the generator, formatted only while a trace is recorded.
+pub(crate) fn note(message: impl FnOnce() -> String) {
+    TRACE.with_borrow_mut(|trace| {
+        if let Some(trace) = trace {
+            trace.push(TraceStep {
+                event: None,
+                kind: "board",
+                message: message(),
+            });
+        }
+    });
+}
+
+// A narration of the log the generator wrote, read back from its events once it is done rather than recorded
+// while it searched: the tiles it dealt that the board never shows, where each draw went and what each call
+// stands for. It tells what the log is, not the alternatives the generator went through to get there.
+pub(crate) fn trace_events(events: &[Event], player_id: u8) -> Vec<TraceStep> {
+    let seat = |actor: u8| (actor + 4 - player_id) % 4;
+    let step = |event: usize, kind: &'static str, message: String| TraceStep {
+        event: Some(event),
+        kind,
+        message,
+    };
+    let mut steps = vec![];
+    for (i, event) in events.iter().enumerate() {
+        match event {
+            Event::StartKyoku { .. } => {
+                let end = events[i..]
+                    .iter()
+                    .position(|event| matches!(event, Event::EndKyoku))
+                    .map_or(events.len(), |end| i + end);
+                let hands = final_hands(&events[..end]);
+                for actor in (0..4u8).filter(|actor| *actor != player_id) {
+                    steps.push(step(
+                        i,
+                        "deal",
+                        format!(
+                            "seat {} ends holding {}, tiles the board does not show and that were made up to fill its hand",
+                            seat(actor),
+                            tiles_vec_to_string(&hands[actor as usize])
+                        ),
+                    ));
+                }
+                steps.push(step(
+                    i,
+                    "deal",
+                    "draws follow the kawa in turn order, each seat drawing before its discard unless a call gave it the turn"
+                        .to_owned(),
+                ));
+            }
+            Event::Tsumo { actor, pai } => {
+                let action = events[i + 1..].iter().find(|event| match event {
+                    Event::Dahai { actor: a, .. } | Event::Ankan { actor: a, .. } | Event::Kakan { actor: a, .. } => a == actor,
+                    Event::Hora { actor: a, target, .. } => a == actor && target == actor,
+                    _ => false,
+                });
+                let drawn = tiles_vec_to_string(&[*pai]);
+                let s = seat(*actor);
+                let message = match action {
+                    Some(Event::Dahai { tsumogiri: true, .. }) => {
+                        format!("seat {s} draws {drawn} to discard it, the tsumogiri in kawa[{s}]")
+                    }
+                    Some(Event::Dahai { pai: discard, .. }) => format!(
+                        "seat {s} draws {drawn} and keeps it, discarding {} from the hand as kawa[{s}] has it",
+                        tiles_vec_to_string(&[*discard])
+                    ),
+                    Some(Event::Hora { .. }) => format!("seat {s} draws {drawn}, its winning tile"),
+                    Some(_) => format!("seat {s} draws {drawn} and declares a kan of fuuro[{s}]"),
+                    None => format!("seat {s} draws {drawn}, the last draw of the log"),
+                };
+                steps.push(step(i, "draw", message));
+            }
+            Event::Chi { actor, target, pai, .. }
+            | Event::Pon { actor, target, pai, .. }
+            | Event::Daiminkan { actor, target, pai, .. } => {
+                let call = match event {
+                    Event::Chi { .. } => "chi",
+                    Event::Pon { .. } => "pon",
+                    _ => "kan",
+                };
+                steps.push(step(
+                    i,
+                    "call",
+                    format!(
+                        "seat {} calls {call} on the {} of seat {} for the meld in fuuro[{}], so the turn passes to it",
+                        seat(*actor),
+                        tiles_vec_to_string(&[*pai]),
+                        seat(*target),
+                        seat(*actor)
+                    ),
+                ));
+            }
+            Event::Ankan { actor, consumed } => steps.push(step(
+                i,
+                "call",
+                format!(
+                    "seat {} declares the closed kan {} of fuuro[{}] and draws a replacement",
+                    seat(*actor),
+                    tiles_vec_to_string(consumed),
+                    seat(*actor)
+                ),
+            )),
+            Event::Kakan { actor, pai, .. } => steps.push(step(
+                i,
+                "call",
+                format!(
+                    "seat {} adds {} to its pon for the kan of fuuro[{}] and draws a replacement",
+                    seat(*actor),
+                    tiles_vec_to_string(&[*pai]),
+                    seat(*actor)
+                ),
+            )),
+            _ => {}
+        }
+    }
+    steps
+}
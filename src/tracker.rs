@@ -0,0 +1,157 @@
+use anyhow::{bail, Result};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+
+// A discard as extraction writes it: the tile, whether it was tsumogiri and whether it declared riichi.
+pub(crate) type Discard = (Tile, bool, bool);
+
+// A call as extraction writes it, each tile with whether it lies sideways.
+pub(crate) type Naki = Vec<(Tile, bool)>;
+
+// What the table shows after each mjai event of a kyoku, by absolute seat. Hands hold whatever the log
+// reveals of them, unknown tiles included.
+#[derive(Default, Clone)]
+pub(crate) struct Tracker {
+    pub(crate) bakaze: Tile,
+    // from 1, as in mjai
+    pub(crate) kyoku: u8,
+    pub(crate) honba: u8,
+    pub(crate) kyotaku: u8,
+    pub(crate) oya: u8,
+    pub(crate) scores: [i32; 4],
+    pub(crate) dora_indicators: Vec<Tile>,
+    pub(crate) tehais: [Vec<Tile>; 4],
+    // kawa the way they look, without the tiles that were called
+    pub(crate) kawa: [Vec<Discard>; 4],
+    // calls in the order they were made
+    pub(crate) fuuro: [Vec<Naki>; 4],
+    // whose riichi stick is on the table
+    pub(crate) deposited: [bool; 4],
+    // whose next discard declares riichi
+    declaring: [bool; 4],
+    // a three player game, whose calls lie sideways towards the kamicha or the shimocha among the three
+    pub(crate) sanma: bool,
+    // kita set aside, which the callers count since a kita is no event of its own, see nuki.rs
+    pub(crate) nuki: [u8; 4],
+}
+
+// Takes `tile` out of `hand`, or an unknown tile standing in for it.
+fn take(hand: &mut Vec<Tile>, tile: Tile, actor: u8) -> Result<()> {
+    let position = hand
+        .iter()
+        .position(|held| *held == tile)
+        .or_else(|| hand.iter().position(|held| held.is_unknown()));
+    let Some(position) = position else {
+        bail!("seat {actor} gives up {tile}, which it does not hold");
+    };
+    hand.remove(position);
+    Ok(())
+}
+
+// Where the sideways tile of a call from `target` goes among the `actor`'s tiles: left for the kamicha,
+// middle for the toimen and right for the shimocha.
+fn sideways_index(actor: u8, target: u8, len: usize, sanma: bool) -> usize {
+    match (sanma, (4 + actor - target) % 4) {
+        (true, _) if (3 + actor - target) % 3 == 1 => 0,
+        (true, _) => len,
+        (false, 1) => 0,
+        (false, 2) => 1,
+        (false, _) => len,
+    }
+}
+
+impl Tracker {
+    pub(crate) fn update(&mut self, event: &Event) -> Result<()> {
+        match event {
+            Event::StartKyoku {
+                bakaze,
+                dora_marker,
+                kyoku,
+                honba,
+                kyotaku,
+                oya,
+                scores,
+                tehais,
+            } => {
+                *self = Tracker {
+                    bakaze: *bakaze,
+                    kyoku: *kyoku,
+                    honba: *honba,
+                    kyotaku: *kyotaku,
+                    oya: *oya,
+                    scores: *scores,
+                    dora_indicators: vec![*dora_marker],
+                    tehais: tehais.map(|tehai| tehai.to_vec()),
+                    sanma: self.sanma,
+                    ..Default::default()
+                };
+            }
+            Event::Tsumo { actor, pai } => self.tehais[*actor as usize].push(*pai),
+            Event::Dahai { actor, pai, tsumogiri } => {
+                let seat = *actor as usize;
+                take(&mut self.tehais[seat], *pai, *actor)?;
+                let declaring = std::mem::take(&mut self.declaring[seat]);
+                self.kawa[seat].push((*pai, *tsumogiri, declaring));
+            }
+            Event::Chi {
+                actor,
+                target,
+                pai,
+                consumed,
+            }
+            | Event::Pon {
+                actor,
+                target,
+                pai,
+                consumed,
+            } => self.call(*actor, *target, *pai, consumed)?,
+            Event::Daiminkan {
+                actor,
+                target,
+                pai,
+                consumed,
+            } => self.call(*actor, *target, *pai, consumed)?,
+            Event::Ankan { actor, consumed } => {
+                for tile in consumed {
+                    take(&mut self.tehais[*actor as usize], *tile, *actor)?;
+                }
+                self.fuuro[*actor as usize].push(consumed.map(|tile| (tile, false)).to_vec());
+            }
+            Event::Kakan { actor, pai, .. } => {
+                take(&mut self.tehais[*actor as usize], *pai, *actor)?;
+                // the added tile lies on top of the pon's sideways one
+                let pon = self.fuuro[*actor as usize].iter_mut().find_map(|naki| {
+                    naki.iter()
+                        .position(|(tile, sideways)| *sideways && tile.deaka() == pai.deaka())
+                        .map(|i| (naki, i))
+                });
+                let Some((naki, i)) = pon else {
+                    bail!("seat {actor} adds {pai} to a pon it does not have");
+                };
+                naki.insert(i + 1, (*pai, true));
+            }
+            Event::Dora { dora_marker } => self.dora_indicators.push(*dora_marker),
+            Event::Reach { actor } => self.declaring[*actor as usize] = true,
+            Event::ReachAccepted { actor } => {
+                self.scores[*actor as usize] -= 1000;
+                self.kyotaku += 1;
+                self.deposited[*actor as usize] = true;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, actor: u8, target: u8, pai: Tile, consumed: &[Tile]) -> Result<()> {
+        for tile in consumed {
+            take(&mut self.tehais[actor as usize], *tile, actor)?;
+        }
+        if self.kawa[target as usize].pop().is_none_or(|(discard, ..)| discard != pai) {
+            bail!("seat {actor} calls {pai}, which seat {target} did not just discard");
+        }
+        let mut naki: Naki = consumed.iter().map(|tile| (*tile, false)).collect();
+        naki.insert(sideways_index(actor, target, consumed.len(), self.sanma), (pai, true));
+        self.fuuro[actor as usize].push(naki);
+        Ok(())
+    }
+}
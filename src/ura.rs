@@ -0,0 +1,253 @@
+use anyhow::{bail, ensure, Context, Result};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+
+use crate::nuki::kitas;
+use crate::replay::final_hands;
+use crate::rng::Rng;
+use crate::rules::reveal_index;
+
+pub(crate) enum UraSpec {
+    Indicators(Vec<Tile>),
+    Hits(u8),
+}
+
+// tile kind made dora by the indicator `kind`
+pub(crate) fn dora_of(kind: usize) -> usize {
+    match kind {
+        0..27 => kind / 9 * 9 + (kind % 9 + 1) % 9,
+        27..31 => 27 + (kind - 27 + 1) % 4,
+        _ => 31 + (kind - 31 + 1) % 3,
+    }
+}
+
+// Every physical tile the log commits to: haipai, draws and revealed indicators.
+pub(crate) fn used_counts(events: &[Event]) -> [u8; 34] {
+    let mut counts = [0; 34];
+    for event in events {
+        match event {
+            // the empty seat of a three player game is dealt no tiles
+            Event::StartKyoku { tehais, dora_marker, .. } => {
+                for tile in tehais.iter().flatten().chain([dora_marker]).filter(|tile| !tile.is_unknown()) {
+                    counts[tile.deaka().as_usize()] += 1;
+                }
+            }
+            Event::Tsumo { pai, .. } => counts[pai.deaka().as_usize()] += 1,
+            Event::Dora { dora_marker } => counts[dora_marker.deaka().as_usize()] += 1,
+            _ => {}
+        }
+    }
+    // the N of a kita never enters the hand of the seat that draws it
+    counts[tu8!(N) as usize] += kitas(events).len() as u8;
+    counts
+}
+
+// The winner's closed tiles and melds at the end of the kyoku.
+fn winner_counts(events: &[Event], winner: u8) -> [u8; 34] {
+    let mut counts = [0; 34];
+    let mut add = |tile: &Tile| counts[tile.deaka().as_usize()] += 1;
+    final_hands(events)[winner as usize].iter().for_each(&mut add);
+    for event in events {
+        match event {
+            Event::Chi {
+                actor, pai, consumed, ..
+            }
+            | Event::Pon {
+                actor, pai, consumed, ..
+            } if *actor == winner => {
+                add(pai);
+                consumed.iter().for_each(&mut add);
+            }
+            Event::Daiminkan {
+                actor, pai, consumed, ..
+            } if *actor == winner => {
+                add(pai);
+                consumed.iter().for_each(&mut add);
+            }
+            Event::Ankan { actor, consumed } if *actor == winner => consumed.iter().for_each(&mut add),
+            Event::Kakan { actor, pai, .. } if *actor == winner => add(pai),
+            _ => {}
+        }
+    }
+    counts
+}
+
+fn search_hits(
+    slots: usize,
+    target: u8,
+    from: usize,
+    available: &mut [u8; 34],
+    hits: &[u8; 34],
+    chosen: &mut Vec<usize>,
+) -> bool {
+    let left = slots - chosen.len();
+    if left == 0 {
+        return target == 0;
+    }
+    if target as usize > left * 4 {
+        return false;
+    }
+    for kind in from..34 {
+        if available[kind] == 0 || hits[kind] > target {
+            continue;
+        }
+        available[kind] -= 1;
+        chosen.push(kind);
+        if search_hits(slots, target - hits[kind], kind, available, hits, chosen) {
+            return true;
+        }
+        chosen.pop();
+        available[kind] += 1;
+    }
+    false
+}
+
+// Picks the ura indicators for a riichi tsumo, so that they only use tiles the log has not dealt yet.
+pub(crate) fn choose_ura(events: &[Event], winner: u8, spec: &UraSpec) -> Result<Vec<Tile>> {
+    let slots = 1 + events.iter().filter(|event| matches!(event, Event::Dora { .. })).count();
+    let used = used_counts(events);
+    let mut available = used.map(|count| 4u8.saturating_sub(count));
+    match spec {
+        UraSpec::Indicators(indicators) => {
+            ensure!(
+                indicators.len() == slots,
+                "{} ura indicators given but {slots} dora indicators are revealed",
+                indicators.len()
+            );
+            for tile in indicators {
+                let count = &mut available[tile.deaka().as_usize()];
+                ensure!(
+                    *count > 0,
+                    "ura indicator {tile} is not available, every copy is already in the log"
+                );
+                *count -= 1;
+            }
+            Ok(indicators.clone())
+        }
+        UraSpec::Hits(target) => {
+            let winner_tiles = winner_counts(events, winner);
+            let hits: [u8; 34] = std::array::from_fn(|kind| winner_tiles[dora_of(kind)]);
+            let mut chosen = vec![];
+            ensure!(
+                search_hits(slots, *target, 0, &mut available, &hits, &mut chosen),
+                "no ura indicators give {target} hits with the tiles left"
+            );
+            Ok(chosen.into_iter().map(|kind| Tile::new_unchecked(kind as u8)).collect())
+        }
+    }
+}
+
+// Sets the ura indicators on the riichi tsumo that ends the log.
+pub(crate) fn set_ura(events: &mut [Event], spec: &UraSpec) -> Result<()> {
+    let (index, winner) = events
+        .iter()
+        .enumerate()
+        .find_map(|(i, event)| match event {
+            Event::Hora { actor, target, .. } if actor == target => Some((i, *actor)),
+            _ => None,
+        })
+        .context("ura dora can only be set when the kyoku ends in a tsumo")?;
+    ensure!(
+        events[..index]
+            .iter()
+            .any(|event| matches!(event, Event::ReachAccepted { actor } if *actor == winner)),
+        "ura dora can only be set when the tsumo winner is in riichi"
+    );
+    let ura = choose_ura(&events[..index], winner, spec)?;
+    if let Event::Hora { ura_markers, .. } = &mut events[index] {
+        *ura_markers = Some(ura);
+    }
+    Ok(())
+}
+
+// How dora and ura indicators nobody specified are written to the log.
+pub(crate) enum Placeholder {
+    // leave them out, so kans without an indicator and riichi wins without ura show none
+    Omit,
+    Fixed(Tile),
+    // tiles the log has not dealt yet
+    Random(Rng),
+}
+
+impl Placeholder {
+    fn next(&mut self, available: &mut [u8; 34]) -> Result<Tile> {
+        match self {
+            Placeholder::Omit => bail!("placeholder indicators are left out"),
+            Placeholder::Fixed(tile) => Ok(*tile),
+            Placeholder::Random(rng) => {
+                let left: u32 = available.iter().map(|count| *count as u32).sum();
+                ensure!(left > 0, "no tiles left for placeholder indicators");
+                let mut pick = rng.below(left as usize) as u32;
+                let kind = available
+                    .iter()
+                    .position(|count| {
+                        if pick < *count as u32 {
+                            return true;
+                        }
+                        pick -= *count as u32;
+                        false
+                    })
+                    .unwrap_or_default();
+                available[kind] -= 1;
+                Ok(Tile::new_unchecked(kind as u8))
+            }
+        }
+    }
+}
+
+fn ends_kan_window(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Daiminkan { .. }
+            | Event::Ankan { .. }
+            | Event::Kakan { .. }
+            | Event::Hora { .. }
+            | Event::Ryukyoku { .. }
+            | Event::EndKyoku
+    )
+}
+
+// Adds a dora indicator to every kan that reveals none before the next kan or the end of the kyoku, turned
+// over when tenhou does, after the replacement draw of an ankan and after the discard of an open kan, and
+// ura indicators to riichi wins that have none.
+pub(crate) fn fill_placeholders(events: &mut Vec<Event>, mut placeholder: Placeholder) -> Result<()> {
+    if matches!(placeholder, Placeholder::Omit) {
+        return Ok(());
+    }
+    let mut available = used_counts(events).map(|count| 4u8.saturating_sub(count));
+    let mut i = 0;
+    while i < events.len() {
+        if matches!(events[i], Event::Daiminkan { .. } | Event::Ankan { .. } | Event::Kakan { .. }) {
+            let revealed = events[i + 1..]
+                .iter()
+                .take_while(|event| !ends_kan_window(event))
+                .any(|event| matches!(event, Event::Dora { .. }));
+            if !revealed {
+                let dora_marker = placeholder.next(&mut available)?;
+                let target = reveal_index(events, i, true);
+                events.insert(target, Event::Dora { dora_marker });
+            }
+        }
+        i += 1;
+    }
+
+    let mut riichi = [false; 4];
+    let mut slots = 0;
+    for event in events.iter_mut() {
+        match event {
+            Event::StartKyoku { .. } => {
+                riichi = [false; 4];
+                slots = 1;
+            }
+            Event::Dora { .. } => slots += 1,
+            Event::ReachAccepted { actor } => riichi[*actor as usize] = true,
+            Event::Hora { actor, ura_markers, .. } if riichi[*actor as usize] && ura_markers.is_none() => {
+                let ura = (0..slots).map(|_| placeholder.next(&mut available)).collect::<Result<_>>()?;
+                *ura_markers = Some(ura);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use anyhow::{bail, ensure, Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::RawBoard;
+
+// Percent-encodes everything but the unreserved characters of RFC 3986, utf-8 byte by byte.
+pub(crate) fn encode_component(raw: &str) -> String {
+    let mut encoded = String::with_capacity(raw.len());
+    for byte in raw.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+// `+` stays a plus, since pairi hands use it between melds; spaces have to come as %20.
+fn decode_component(encoded: &str) -> Result<String> {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .with_context(|| format!("incorrect percent escape at {i}"))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).context("query is not utf-8")
+}
+
+impl RawBoard {
+    // "kyoku=S3&jikaze=W&tehai=123m...&kawa0=...": the field names of RawBoard, with the per-seat fields
+    // numbered in RawBoard's relative order. A whole url works too, everything up to the `?` is skipped.
+    pub(crate) fn from_query_string(query: &str) -> Result<RawBoard> {
+        // only the `?` ending a url, a bare query may hold unencoded ?? discards
+        let query = match query.split_once('?') {
+            Some((url, rest)) if !url.contains(['=', '&']) => rest,
+            _ => query,
+        };
+        let query = query.split_once('#').map_or(query, |(query, _)| query);
+        let mut board = RawBoard {
+            scores: vec![String::new(); 4],
+            kawa: vec![String::new(); 4],
+            fuuro: vec![String::new(); 4],
+            nuki: vec![String::new(); 4],
+            draws: vec![String::new(); 4],
+            ..Default::default()
+        };
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = decode_component(value).with_context(|| format!("incorrect {key}"))?;
+            let seat = |list: &str| -> Result<usize> {
+                let seat: usize = key[list.len()..].parse().with_context(|| format!("incorrect key {key:?}"))?;
+                ensure!(seat < 4, "{key} must be numbered 0 to 3");
+                Ok(seat)
+            };
+            let field = match key {
+                "kyoku" => &mut board.kyoku,
+                "jikaze" => &mut board.jikaze,
+                "kyotaku" => &mut board.kyotaku,
+                "honba" => &mut board.honba,
+                "dora" => &mut board.dora,
+                "tehai" => &mut board.tehai,
+                "tsumo" => &mut board.tsumo,
+                "junme" => &mut board.junme,
+                "chiicha" => &mut board.chiicha,
+                "claim" => &mut board.claim,
+                "fill_avoid" => &mut board.fill_avoid,
+                "fill_genbutsu" => &mut board.fill_genbutsu,
+                "bots" => &mut board.bots,
+                "players" => &mut board.players,
+                "uradora" => &mut board.uradora,
+                "deposits" => &mut board.deposits,
+                _ if key.starts_with("scores") => &mut board.scores[seat("scores")?],
+                _ if key.starts_with("kawa") => &mut board.kawa[seat("kawa")?],
+                _ if key.starts_with("fuuro") => &mut board.fuuro[seat("fuuro")?],
+                _ if key.starts_with("nuki") => &mut board.nuki[seat("nuki")?],
+                _ if key.starts_with("draws") => &mut board.draws[seat("draws")?],
+                _ => bail!("unknown key {key:?}"),
+            };
+            ensure!(field.is_empty(), "{key} is given twice");
+            *field = value;
+        }
+        // a three player board has seats 0 to 2 only
+        if board.players == "3" {
+            for (list, values) in [
+                ("scores", &mut board.scores),
+                ("kawa", &mut board.kawa),
+                ("fuuro", &mut board.fuuro),
+                ("nuki", &mut board.nuki),
+                ("draws", &mut board.draws),
+            ] {
+                ensure!(
+                    values[3].is_empty(),
+                    "{list}3 is given, but a three player board has seats 0 to 2"
+                );
+                values.truncate(3);
+            }
+        }
+        Ok(board)
+    }
+
+    // The inverse of from_query_string, leaving out empty fields.
+    pub(crate) fn to_query_string(&self) -> String {
+        let mut pairs = vec![
+            ("kyoku".to_owned(), &self.kyoku),
+            ("jikaze".to_owned(), &self.jikaze),
+            ("kyotaku".to_owned(), &self.kyotaku),
+            ("honba".to_owned(), &self.honba),
+            ("dora".to_owned(), &self.dora),
+            ("tehai".to_owned(), &self.tehai),
+            ("tsumo".to_owned(), &self.tsumo),
+        ];
+        for (list, values) in [
+            ("scores", &self.scores),
+            ("kawa", &self.kawa),
+            ("fuuro", &self.fuuro),
+            ("nuki", &self.nuki),
+            ("draws", &self.draws),
+        ] {
+            pairs.extend(
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(seat, value)| (format!("{list}{seat}"), value)),
+            );
+        }
+        pairs.extend([
+            ("junme".to_owned(), &self.junme),
+            ("chiicha".to_owned(), &self.chiicha),
+            ("claim".to_owned(), &self.claim),
+            ("fill_avoid".to_owned(), &self.fill_avoid),
+            ("fill_genbutsu".to_owned(), &self.fill_genbutsu),
+            ("bots".to_owned(), &self.bots),
+            ("players".to_owned(), &self.players),
+            ("uradora".to_owned(), &self.uradora),
+            ("deposits".to_owned(), &self.deposits),
+        ]);
+        pairs
+            .into_iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| format!("{key}={}", encode_component(value)))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct PlayerLinks {
+    pub seat: u8,
+    pub name: String,
+    pub nodocchi: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct LogLinks {
+    pub viewer: String,
+    pub players: Vec<PlayerLinks>,
+}
+
+// Links for an imported tenhou log, from its "ref" (the log id) and "name" fields. `seat` is who the
+// viewer link is from the point of view of.
+pub(crate) fn log_links(log: &Value, seat: u8) -> Result<LogLinks> {
+    let id = log["ref"].as_str().context("the log has no ref, the id tenhou gave it")?;
+    let players = log["name"]
+        .as_array()
+        .context("the log has no names")?
+        .iter()
+        .enumerate()
+        .filter_map(|(seat, name)| Some((seat, name.as_str()?)))
+        .filter(|(_, name)| !name.is_empty())
+        .map(|(seat, name)| PlayerLinks {
+            seat: seat as u8,
+            name: name.to_owned(),
+            nodocchi: format!("https://nodocchi.moe/tenhoulog/#!&name={}", encode_component(name)),
+        })
+        .collect();
+    Ok(LogLinks {
+        viewer: format!("https://tenhou.net/6/?log={}&tw={seat}", encode_component(id)),
+        players,
+    })
+}
+
+// Opens a log that was never uploaded: the whole json goes into the fragment, which is why it has to be
+// encoded completely, `&` and `#` included.
+pub(crate) fn viewer_url(tenhou_log: &str, seat: u8) -> String {
+    format!("https://tenhou.net/6/?tw={seat}#json={}", encode_component(tenhou_log))
+}
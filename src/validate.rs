@@ -0,0 +1,395 @@
+use std::fmt;
+
+use riichi::algo::agari::AgariCalculator;
+use riichi::algo::shanten::calc_all;
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+use serde::Serialize;
+
+use crate::nuki::kita_seat;
+use crate::sanma::is_sanma;
+use crate::tracker::Tracker;
+
+// The first event of an mjai log that cannot happen, by its index in the log.
+#[derive(Serialize, Debug)]
+pub struct EventError {
+    pub index: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for EventError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "event {}: {}", self.index, self.reason)
+    }
+}
+
+impl std::error::Error for EventError {}
+
+// Whose move the log waits for inside a kyoku.
+#[derive(Default)]
+struct Turn {
+    // the seat holding 14 tiles, which discards, kans or wins next
+    to_move: Option<u8>,
+    // the seat that draws next when nobody calls
+    next_draw: Option<u8>,
+    // the discard, or added kan, the other seats may still call or ron
+    claimable: Option<u8>,
+    // and its tile
+    claimed_tile: Option<Tile>,
+    // the tile the seat to move drew, none after a call
+    drawn: Option<Tile>,
+    // whether that draw replaced a kan, for rinshan kaihou
+    rinshan: bool,
+    // the seat whose next discard declares riichi
+    declaring: Option<u8>,
+    riichi: [bool; 4],
+    // every kind each seat discarded, called ones included, for furiten
+    discarded: [Vec<usize>; 4],
+    // who made each kan
+    kans: Vec<u8>,
+    draws: usize,
+    // 3 when seat 3 is empty
+    seats: u8,
+}
+
+impl Turn {
+    // the draws of the live wall, kan and kita replacements included: 136 tiles or the 108 of three player
+    // mahjong, less the dead wall and the haipai
+    fn wall(&self) -> usize {
+        if self.seats == 3 {
+            55
+        } else {
+            70
+        }
+    }
+}
+
+// Replays `events` from start_game to end_game, checking turn order, calls and hands as the table would.
+pub fn validate_mjai(events: &[Event]) -> Result<(), EventError> {
+    let mut tracker = Tracker::default();
+    tracker.sanma = is_sanma(events);
+    let seats = if tracker.sanma { 3 } else { 4 };
+    let mut turn: Option<Turn> = None;
+    let mut started = false;
+    let mut ended = false;
+    for (index, event) in events.iter().enumerate() {
+        let error = |reason: String| EventError { index, reason };
+        if ended {
+            return Err(error("the game already ended".to_owned()));
+        }
+        match event {
+            Event::StartGame { .. } => {
+                if started {
+                    return Err(error("the game already started".to_owned()));
+                }
+                started = true;
+                continue;
+            }
+            _ if !started => return Err(error("the log does not open with start_game".to_owned())),
+            Event::StartKyoku { oya, .. } => {
+                if turn.is_some() {
+                    return Err(error("the previous kyoku has no end_kyoku".to_owned()));
+                }
+                turn = Some(Turn {
+                    next_draw: Some(*oya),
+                    seats,
+                    ..Default::default()
+                });
+            }
+            Event::EndGame => {
+                if turn.is_some() {
+                    return Err(error("the kyoku has no end_kyoku".to_owned()));
+                }
+                ended = true;
+                continue;
+            }
+            _ => {}
+        }
+        let Some(state) = &mut turn else {
+            return Err(error("outside of a kyoku".to_owned()));
+        };
+        match kita_seat(events, index) {
+            Some(actor) => check_kita(state, actor).map_err(error)?,
+            None => check_turn(state, &tracker, event).map_err(error)?,
+        }
+        tracker.update(event).map_err(|e| error(e.to_string()))?;
+        if matches!(event, Event::EndKyoku) {
+            turn = None;
+        }
+    }
+    Ok(())
+}
+
+// The concealed tiles of `seat` by kind, None while the log hides any of them.
+fn known_hand(tracker: &Tracker, seat: u8) -> Option<[u8; 34]> {
+    let mut hand = [0; 34];
+    for tile in &tracker.tehais[seat as usize] {
+        if tile.is_unknown() {
+            return None;
+        }
+        hand[tile.deaka().as_usize()] += 1;
+    }
+    Some(hand)
+}
+
+fn is_open(tracker: &Tracker, seat: u8) -> bool {
+    tracker.fuuro[seat as usize]
+        .iter()
+        .any(|naki| naki.iter().any(|(_, sideways)| *sideways))
+}
+
+// Whether `seat` has a yaku winning with `hand`, its concealed tiles and the winning one, going by its hand
+// and calls. The yaku of the moment, haitei, houtei, rinshan, chankan and the first turn's, are the caller's.
+fn has_yaku(tracker: &Tracker, seat: u8, hand: &[u8; 34], tile: Tile, ron: bool) -> bool {
+    let (mut chis, mut pons, mut minkans, mut ankans) = (vec![], vec![], vec![], vec![]);
+    for naki in &tracker.fuuro[seat as usize] {
+        let kinds: Vec<u8> = naki.iter().map(|(tile, _)| tile.deaka().as_u8()).collect();
+        let low = kinds.iter().copied().min().unwrap_or_default();
+        match (kinds.len(), naki.iter().any(|(_, sideways)| *sideways)) {
+            (4, false) => ankans.push(low),
+            (4, true) => minkans.push(low),
+            _ if kinds.iter().all(|kind| *kind == low) => pons.push(low),
+            _ => chis.push(low),
+        }
+    }
+    let seats = if tracker.sanma { 3 } else { 4 };
+    AgariCalculator {
+        tehai: hand,
+        is_menzen: !is_open(tracker, seat),
+        chis: &chis,
+        pons: &pons,
+        minkans: &minkans,
+        ankans: &ankans,
+        bakaze: tracker.bakaze.as_u8(),
+        jikaze: tu8!(E) + (seat + seats - tracker.oya) % seats,
+        winning_tile: tile.deaka().as_u8(),
+        is_ron: ron,
+    }
+    .search_yakus()
+    .is_some()
+}
+
+// The kinds that complete the 13 concealed tiles `hand`.
+fn waits(hand: &[u8; 34]) -> Vec<usize> {
+    let len: u8 = hand.iter().sum();
+    (0..34)
+        .filter(|kind| {
+            let mut hand = *hand;
+            hand[*kind] += 1;
+            hand[*kind] <= 4 && calc_all(&hand, len / 3 + 1) == -1
+        })
+        .collect()
+}
+
+// Whether the table allows a draw before the wall runs out: nine kinds of terminals and honors on a first
+// draw, four of the same wind, four riichi or four kans of more than one seat.
+fn abortive(state: &Turn, tracker: &Tracker) -> bool {
+    let calls = tracker.fuuro.iter().any(|fuuro| !fuuro.is_empty());
+    let kyuushu = state.to_move.is_some_and(|seat| {
+        let hand = known_hand(tracker, seat).unwrap_or([0; 34]);
+        let kinds = (0..34)
+            .filter(|kind| hand[*kind] > 0 && Tile::new_unchecked(*kind as u8).is_yaokyuu())
+            .count();
+        !calls && state.discarded[seat as usize].is_empty() && kinds >= 9
+    });
+    let first: Vec<usize> = state
+        .discarded
+        .iter()
+        .filter_map(|discarded| discarded.first().copied())
+        .collect();
+    let suufon = !calls
+        && state.discarded.iter().all(|discarded| discarded.len() == 1)
+        && (tu8!(E) as usize..=tu8!(N) as usize).contains(&first[0])
+        && first.iter().all(|kind| *kind == first[0]);
+    let suucha = state.riichi.iter().all(|riichi| *riichi);
+    let suukaikan = state.kans.len() == 4 && state.kans.iter().any(|seat| *seat != state.kans[0]);
+    kyuushu || suufon || suucha || suukaikan
+}
+
+// A kita of `actor`: the N it draws, or holds after its draw, is set aside and the replacement draw follows. The
+// events keep neither the N nor the setting aside, see nuki.rs.
+fn check_kita(state: &mut Turn, actor: u8) -> Result<(), String> {
+    if state.seats != 3 {
+        return Err(format!("seat {actor} sets a kita aside in a four player game"));
+    }
+    if state.next_draw == Some(actor) {
+        // the N it drew came off the live wall
+        if state.draws == state.wall() {
+            return Err("the wall is empty".to_owned());
+        }
+        state.draws += 1;
+    } else if state.to_move != Some(actor) {
+        return Err(format!("seat {actor} sets a kita aside out of turn"));
+    }
+    state.to_move = None;
+    state.next_draw = Some(actor);
+    state.claimable = None;
+    state.rinshan = true;
+    Ok(())
+}
+
+fn check_turn(state: &mut Turn, tracker: &Tracker, event: &Event) -> Result<(), String> {
+    let held = |seat: u8| tracker.tehais[seat as usize].len() + 3 * tracker.fuuro[seat as usize].len();
+    match event {
+        Event::Tsumo { actor, pai } => {
+            if state.next_draw != Some(*actor) {
+                return Err(format!("seat {actor} draws out of turn"));
+            }
+            // the replacement draws of kans come out of the live wall too
+            if state.draws == state.wall() {
+                return Err("the wall is empty".to_owned());
+            }
+            state.draws += 1;
+            state.rinshan = state.next_draw == Some(*actor) && std::mem::take(&mut state.rinshan);
+            state.to_move = Some(*actor);
+            state.next_draw = None;
+            state.claimable = None;
+            state.drawn = Some(*pai);
+        }
+        Event::Dahai { actor, pai, tsumogiri } => {
+            if state.to_move != Some(*actor) {
+                return Err(format!("seat {actor} discards out of turn"));
+            }
+            if held(*actor) != 14 {
+                return Err(format!("seat {actor} discards holding {} tiles", held(*actor)));
+            }
+            let declares = state.declaring.take() == Some(*actor);
+            if state.riichi[*actor as usize] && !declares && (!tsumogiri || state.drawn != Some(*pai)) {
+                return Err(format!("seat {actor} is in riichi, it can only discard the tile it drew"));
+            }
+            state.to_move = None;
+            state.next_draw = Some((actor + 1) % state.seats);
+            state.claimable = Some(*actor);
+            state.claimed_tile = Some(*pai);
+            state.discarded[*actor as usize].push(pai.deaka().as_usize());
+            state.rinshan = false;
+        }
+        Event::Chi { actor, target, .. } | Event::Pon { actor, target, .. } | Event::Daiminkan { actor, target, .. } => {
+            if state.claimable != Some(*target) || actor == target {
+                return Err(format!("seat {actor} calls a tile seat {target} did not just discard"));
+            }
+            if matches!(event, Event::Chi { .. }) && state.seats == 3 {
+                return Err("three player mahjong has no chi".to_owned());
+            }
+            if matches!(event, Event::Chi { .. }) && *actor != (target + 1) % 4 {
+                return Err(format!("seat {actor} chis off seat {target}, which is not its kamicha"));
+            }
+            if state.riichi[*actor as usize] {
+                return Err(format!("seat {actor} calls in riichi"));
+            }
+            state.claimable = None;
+            state.drawn = None;
+            if matches!(event, Event::Daiminkan { .. }) {
+                state.to_move = None;
+                state.next_draw = Some(*actor);
+                state.rinshan = true;
+                state.kans.push(*actor);
+            } else {
+                state.to_move = Some(*actor);
+                state.next_draw = None;
+            }
+        }
+        Event::Ankan { actor, .. } | Event::Kakan { actor, .. } => {
+            if state.to_move != Some(*actor) {
+                return Err(format!("seat {actor} kans out of turn"));
+            }
+            state.to_move = None;
+            state.next_draw = Some(*actor);
+            state.rinshan = true;
+            state.kans.push(*actor);
+            // a chankan robs the added tile
+            (state.claimable, state.claimed_tile) = match event {
+                Event::Kakan { pai, .. } => (Some(*actor), Some(*pai)),
+                _ => (None, None),
+            };
+        }
+        Event::Reach { actor } => {
+            if state.to_move != Some(*actor) {
+                return Err(format!("seat {actor} declares riichi out of turn"));
+            }
+            if state.riichi[*actor as usize] {
+                return Err(format!("seat {actor} is already in riichi"));
+            }
+            if is_open(tracker, *actor) {
+                return Err(format!("seat {actor} declares riichi with an open hand"));
+            }
+            if tracker.scores[*actor as usize] < 1000 {
+                return Err(format!(
+                    "seat {actor} declares riichi with {} points, short of the 1000 deposit",
+                    tracker.scores[*actor as usize]
+                ));
+            }
+            if let Some(hand) = known_hand(tracker, *actor) {
+                let len: u8 = hand.iter().sum();
+                if calc_all(&hand, len / 3) > 0 {
+                    return Err(format!(
+                        "seat {actor} declares riichi without a discard that leaves it tenpai"
+                    ));
+                }
+            }
+            state.riichi[*actor as usize] = true;
+            state.declaring = Some(*actor);
+        }
+        Event::ReachAccepted { actor } => {
+            if !state.riichi[*actor as usize] || tracker.deposited[*actor as usize] {
+                return Err(format!("seat {actor} has no riichi to accept"));
+            }
+            if state.claimable != Some(*actor) {
+                return Err(format!("seat {actor}'s riichi is accepted before its discard"));
+            }
+        }
+        Event::Hora { actor, target, .. } => {
+            let allowed = if actor == target {
+                state.to_move == Some(*actor)
+            } else {
+                state.claimable == Some(*target)
+            };
+            if !allowed {
+                return Err(format!("seat {actor} wins off seat {target} out of turn"));
+            }
+            let ron = actor != target;
+            let tile = if ron { state.claimed_tile } else { state.drawn };
+            if let (Some(mut hand), Some(tile)) = (known_hand(tracker, *actor), tile) {
+                let mut closed = hand;
+                if ron {
+                    hand[tile.deaka().as_usize()] += 1;
+                } else {
+                    closed[tile.deaka().as_usize()] = closed[tile.deaka().as_usize()].saturating_sub(1);
+                }
+                let len: u8 = hand.iter().sum();
+                if calc_all(&hand, len / 3) != -1 {
+                    return Err(format!("seat {actor} wins on {tile} without a complete hand"));
+                }
+                let waits = waits(&closed);
+                if ron && state.discarded[*actor as usize].iter().any(|kind| waits.contains(kind)) {
+                    return Err(format!(
+                        "seat {actor} rons on {tile} in furiten, it discarded one of its waits"
+                    ));
+                }
+                let situational = state.draws == state.wall()
+                    || (!ron && (state.rinshan || state.discarded[*actor as usize].is_empty()))
+                    // a chankan, the seat that added the tile would draw next
+                    || (ron && state.next_draw == Some(*target));
+                if !state.riichi[*actor as usize] && !situational && !has_yaku(tracker, *actor, &hand, tile, ron) {
+                    return Err(format!("seat {actor} wins on {tile} without a yaku"));
+                }
+            }
+            state.to_move = None;
+            state.next_draw = None;
+        }
+        Event::Ryukyoku { .. } => {
+            if state.draws < state.wall() && !abortive(state, tracker) {
+                return Err(format!(
+                    "the kyoku is drawn with {} tiles left in the wall and no abortive draw",
+                    state.wall() - state.draws
+                ));
+            }
+            state.to_move = None;
+            state.next_draw = None;
+            state.claimable = None;
+        }
+        _ => {}
+    }
+    Ok(())
+}
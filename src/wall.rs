@@ -0,0 +1,105 @@
+use std::ops::Range;
+
+use anyhow::{bail, ensure, Context, Result};
+use riichi::mjai::Event;
+use riichi::tile::Tile;
+use riichi::tu8;
+
+use crate::events::kyoku_range;
+use crate::sanma::is_sanma;
+
+fn place(wall: &mut [Option<Tile>], slots: &mut Range<usize>, tile: Tile, part: &str) -> Result<()> {
+    let slot = slots
+        .next()
+        .with_context(|| format!("the log has more {part} than the wall"))?;
+    wall[slot] = Some(tile);
+    Ok(())
+}
+
+// The 136 tiles of the first kyoku's wall as the log commits to them, in the order they leave it:
+//   0..52    haipai, dealt four at a time for three rounds from the oya, then one each
+//   52..122  the draws from the live wall, in turn order
+//   122..126 the replacement draws of kans
+//   126..131 the dora indicators, kan dora in the order they are turned over
+//   131..136 the ura indicators
+// Tiles the log never gets to, the rest of the live wall or ura nobody looked at, are the leftover tiles of
+// the set in tile order, so every kind still comes four times and each red five once.
+pub(crate) fn wall(events: &[Event]) -> Result<Vec<Tile>> {
+    // 108 tiles with kita and a longer dead wall are laid out differently
+    ensure!(!is_sanma(events), "only the wall of a four player game is laid out");
+    let aka = events
+        .iter()
+        .any(|event| matches!(event, Event::StartGame { aka_flag: true, .. }));
+    let range = kyoku_range(events, 0).context("the log has no kyoku")?;
+    let events = &events[range];
+    let mut wall: Vec<Option<Tile>> = vec![None; 136];
+    let (mut live, mut rinshan, mut dora, mut ura) = (52..122, 122..126, 126..131, 131..136);
+    let mut kan = false;
+    for event in events {
+        match event {
+            Event::StartKyoku {
+                tehais,
+                dora_marker,
+                oya,
+                ..
+            } => {
+                for (seat, tehai) in tehais.iter().enumerate() {
+                    let turn = (seat + 4 - *oya as usize) % 4;
+                    for (i, tile) in tehai.iter().enumerate() {
+                        let slot = match i {
+                            0..12 => i / 4 * 16 + turn * 4 + i % 4,
+                            _ => 48 + turn,
+                        };
+                        wall[slot] = Some(*tile);
+                    }
+                }
+                place(&mut wall, &mut dora, *dora_marker, "dora indicators")?;
+            }
+            Event::Tsumo { pai, .. } => match std::mem::take(&mut kan) {
+                true => place(&mut wall, &mut rinshan, *pai, "replacement draws")?,
+                false => place(&mut wall, &mut live, *pai, "draws")?,
+            },
+            Event::Daiminkan { .. } | Event::Ankan { .. } | Event::Kakan { .. } => kan = true,
+            Event::Dora { dora_marker } => place(&mut wall, &mut dora, *dora_marker, "dora indicators")?,
+            Event::Hora {
+                ura_markers: Some(markers),
+                ..
+            } if ura.start == 131 => {
+                for marker in markers {
+                    place(&mut wall, &mut ura, *marker, "ura indicators")?;
+                }
+            }
+            _ => {}
+        }
+    }
+    // a kan's replacement draw shortens the live wall by one
+    let kans = rinshan.start - 122;
+    ensure!(
+        live.start <= 122 - kans,
+        "the log draws {} tiles from the live wall, which has {} with {kans} kans",
+        live.start - 52,
+        70 - kans
+    );
+
+    // 34 kinds and the three red fives
+    let mut left = [4u8; 37];
+    if aka {
+        for (five, red) in [(tu8!(5m), tu8!(5mr)), (tu8!(5p), tu8!(5pr)), (tu8!(5s), tu8!(5sr))] {
+            left[five as usize] = 3;
+            left[red as usize] = 1;
+        }
+    } else {
+        left[34..].fill(0);
+    }
+    for tile in wall.iter().flatten() {
+        match left.get_mut(tile.as_usize()) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => bail!("the log has more {tile} than the set of tiles"),
+        }
+    }
+    let mut leftover = (0..37u8).flat_map(|id| vec![Tile::new_unchecked(id); left[id as usize] as usize]);
+    Ok(wall
+        .into_iter()
+        .map(|tile| tile.or_else(|| leftover.next()).unwrap_or_default())
+        .collect())
+}
@@ -0,0 +1,78 @@
+use riichi::convlog::generate::Board;
+use serde::Serialize;
+
+// Something a board allows that no real game gets to, keyed by the RawBoard field to fix.
+#[derive(Serialize)]
+pub struct BoardWarning {
+    pub field: String,
+    pub message: String,
+}
+
+fn warning(field: &str, message: String) -> BoardWarning {
+    BoardWarning {
+        field: field.to_owned(),
+        message,
+    }
+}
+
+// Score and stick bookkeeping of `board`, the parse of a RawBoard whose kyoku is `kyoku` and whose deposits
+// are `deposits`. The parse has already given the deposits back, so its scores and kyotaku are those before
+// this kyoku's riichis. `seats` are the parse's seat of each seat of the board, three of them for three
+// players, and the warnings name the board's seats.
+pub(crate) fn board_warnings(board: &Board, kyoku: &str, deposits: [bool; 4], seats: &[usize]) -> Vec<BoardWarning> {
+    let mut warnings = vec![];
+    let (expected, start) = if seats.len() == 3 { (105000, 35000) } else { (100000, 25000) };
+    let scores: Vec<i32> = seats.iter().map(|seat| board.scores[*seat]).collect();
+    let total: i32 = scores.iter().sum::<i32>() + 1000 * board.kyotaku as i32;
+    let riichi: Vec<usize> = (0..seats.len())
+        .filter(|seat| board.kawa[seats[*seat]].iter().any(|sutehai| sutehai.riichi))
+        .collect();
+    let undeposited: Vec<usize> = riichi.iter().copied().filter(|seat| !deposits[seats[*seat]]).collect();
+    if total != expected {
+        if total + 1000 * undeposited.len() as i32 == expected && !undeposited.is_empty() {
+            let seats: String = undeposited.iter().map(|seat| seat.to_string()).collect();
+            warnings.push(warning(
+                "deposits",
+                format!("the scores already paid the riichi sticks of seats {seats}: add them to deposits and their sticks to kyotaku, or the log takes them again"),
+            ));
+        } else {
+            warnings.push(warning(
+                "scores",
+                format!(
+                    "scores and kyotaku add up to {total}, not {expected}; scores are {scores:?} with {} sticks on the table",
+                    board.kyotaku
+                ),
+            ));
+        }
+    }
+    for (seat, score) in scores.iter().enumerate() {
+        if *score < 0 {
+            warnings.push(warning(
+                &format!("scores[{seat}]"),
+                format!("seat {seat} has {score} points, the game would have ended"),
+            ));
+        } else if *score < 1000 && riichi.contains(&seat) {
+            warnings.push(warning(
+                &format!("scores[{seat}]"),
+                format!("seat {seat} declares riichi with {score} points, which cannot pay the stick"),
+            ));
+        }
+    }
+    // sticks are only left over by a draw, which also adds a honba
+    if board.kyotaku > 0 && board.honba == 0 {
+        warnings.push(warning(
+            "kyotaku",
+            format!(
+                "{} sticks carried over with 0 honba; a draw leaves sticks and a honba, riichis of this kyoku go in deposits",
+                board.kyotaku
+            ),
+        ));
+    }
+    if kyoku == "E1" && board.honba == 0 && scores.iter().any(|score| *score != start) {
+        warnings.push(warning(
+            "scores",
+            format!("East 1 with 0 honba is the first kyoku of the game, every seat starts at {start}"),
+        ));
+    }
+    warnings
+}
@@ -0,0 +1,697 @@
+use std::cell::Cell;
+use std::fmt;
+
+use anyhow::{Context, Error};
+use riichi::mjai::Event;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_wasm_bindgen::from_value;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsValue;
+
+use crate::events::kyoku_range;
+use crate::{
+    analyze_danger, analyze_hand, analyze_riichi_safety, analyze_waits, analyze_walls, cache, camel_case_keys, check_board,
+    count_tiles, extract_board, extract_boards, extract_review_board, extract_snapshots, generate, generate_from_json,
+    generate_highlight, generate_quiz, generate_scenario, html, locale, localize_error, majsoul_events, mjai_events,
+    mjlog_events, notation, nuki, parse_board, reconcile_board, render_board_svg, riichi_city_events, score_win, tenhou,
+    tenhou_log_events, text, timeline, url, validate_mjai, BoardError, ExtractOptions, GenerateOptions, GenerateResult, RawBoard,
+    ScenarioOptions, WinOptions,
+};
+
+// The JavaScript bindings of the crate. Errors reach JavaScript as {stage, code, message} objects: `stage` is
+// "parse" for input that cannot be read, "generate" when no log can be made of it and "convert" for the
+// other conversions, `code` names what went wrong for callers to branch on. Board errors add `field` and
+// `offset`, see js_error.
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console)]
+    fn log(s: &str);
+}
+
+// A panic, e.g. inside the log generator, logs its message to the console before the module traps.
+#[wasm_bindgen(start)]
+pub fn start() {
+    console_error_panic_hook::set_once();
+}
+
+#[derive(Serialize)]
+struct JsError {
+    stage: &'static str,
+    code: &'static str,
+    message: String,
+    field: Option<String>,
+    offset: Option<usize>,
+}
+
+fn error_value(error: JsError) -> JsValue {
+    let error = localized(error);
+    serde_wasm_bindgen::to_value(&error).unwrap_or_else(|_| JsValue::from_str(&error.message))
+}
+
+// `error` with its message in the locale of set_locale_js
+fn localized(error: JsError) -> JsError {
+    let message = localize_error(locale::current(), error.code, error.field.as_deref(), &error.message);
+    JsError { message, ..error }
+}
+
+thread_local! {
+    static CAMEL_CASE: Cell<bool> = const { Cell::new(false) };
+}
+
+// Runs `f` with the locale and camel_case of `options`, the keys staying camelCase when set_camel_case_js
+// already turned it on.
+fn in_options<T>(options: &GenerateOptions, f: impl FnOnce() -> Result<T, JsValue>) -> Result<T, JsValue> {
+    let previous = CAMEL_CASE.get();
+    CAMEL_CASE.set(previous || options.camel_case);
+    let result = in_locale(&options.locale, f);
+    CAMEL_CASE.set(previous);
+    result
+}
+
+// Runs `f` in `locale`, e.g. the locale of some options, unless it is empty.
+fn in_locale<T>(locale: &str, f: impl FnOnce() -> Result<T, JsValue>) -> Result<T, JsValue> {
+    if locale.is_empty() {
+        return f();
+    }
+    let previous = locale::current();
+    locale::set_locale(locale.parse().map_err(|e| stage_error("parse", "InvalidOptions", e))?);
+    let result = f();
+    locale::set_locale(previous);
+    result
+}
+
+pub(crate) fn stage_error(stage: &'static str, code: &'static str, message: impl fmt::Display) -> JsValue {
+    error_value(JsError {
+        stage,
+        code,
+        message: format!("{message:#}"),
+        field: None,
+        offset: None,
+    })
+}
+
+// A board that could not be read has the kind of its error as the code, and the field and offset of the
+// character at fault for editors to highlight. Other errors get their stage from the context the library
+// gave them.
+fn js_error(e: Error) -> JsValue {
+    error_value(js_error_of(e))
+}
+
+fn js_error_of(e: Error) -> JsError {
+    if let Some(board_error) = e.downcast_ref::<BoardError>() {
+        return JsError {
+            stage: "parse",
+            code: board_error.kind,
+            message: board_error.message.clone(),
+            field: board_error.field.clone(),
+            offset: board_error.offset,
+        };
+    }
+    let context = e.chain().map(|cause| cause.to_string()).find_map(|cause| {
+        [
+            ("parse error", "parse", "Parse"),
+            ("deserialize error", "parse", "Deserialize"),
+            ("options deserialize error", "parse", "InvalidOptions"),
+            ("validation error", "parse", "Validation"),
+            ("log generation error", "generate", "Generation"),
+            ("scenario error", "generate", "Scenario"),
+            ("serialize result error", "convert", "Serialize"),
+        ]
+        .into_iter()
+        .find(|(prefix, ..)| cause.starts_with(prefix))
+    });
+    let (stage, code) = context.map_or(("convert", "Conversion"), |(_, stage, code)| (stage, code));
+    JsError {
+        stage,
+        code,
+        message: format!("{e:#}"),
+        field: None,
+        offset: None,
+    }
+}
+
+// What every binding returns, with camelCase keys while set_camel_case_js or the call's camel_case option is on.
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    if CAMEL_CASE.get() {
+        return to_js_camel(value);
+    }
+    serde_wasm_bindgen::to_value(value).map_err(|e| stage_error("convert", "Serialize", e))
+}
+
+// `result` with the mjai_log_string option applied. An empty mjai_log is left out.
+fn result_to_js(mut result: GenerateResult, options: &GenerateOptions) -> Result<JsValue, JsValue> {
+    if options.mjai_log_string && !result.mjai_log.is_empty() {
+        let events = std::mem::take(&mut result.mjai_log);
+        let events = nuki::mjai_values(&events).map_err(|e| stage_error("convert", "Serialize", e))?;
+        result.mjai_log_json = Some(serde_json::to_string(&events).map_err(|e| stage_error("convert", "Serialize", e))?);
+    }
+    to_js(&result)
+}
+
+// `value` with camelCase keys, as plain objects and numbers rather than Maps and BigInts.
+fn to_js_camel<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    let mut value = serde_json::to_value(value).map_err(|e| stage_error("convert", "Serialize", e))?;
+    camel_case_keys(&mut value);
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| stage_error("convert", "Serialize", e))
+}
+
+// undefined and null mean the default options
+fn options_of<T: DeserializeOwned + Default>(val: JsValue) -> Result<T, JsValue> {
+    if val.is_undefined() || val.is_null() {
+        return Ok(T::default());
+    }
+    from_value(val).map_err(|e| stage_error("parse", "InvalidOptions", e))
+}
+
+fn json_of(val: JsValue) -> Result<serde_json::Value, JsValue> {
+    from_value(val).map_err(|e| stage_error("parse", "Deserialize", e))
+}
+
+// A RawBoard object, or the same board in the labeled text format of `text::parse_board`.
+fn raw_board_of(val: JsValue) -> Result<RawBoard, JsValue> {
+    match val.as_string() {
+        Some(text) => text::parse_board(&text).map_err(|e| stage_error("parse", "Parse", e)),
+        None => from_value(val).map_err(|e| stage_error("parse", "Deserialize", e)),
+    }
+}
+
+// A tenhou/6 json log, or the mjlog xml as a string.
+fn tenhou_events(val: JsValue) -> Result<Vec<Event>, JsValue> {
+    match val.as_string().filter(|xml| xml.trim_start().starts_with('<')) {
+        Some(xml) => mjlog_events(&xml).map_err(js_error),
+        None => tenhou_log_events(&json_of(val)?).map_err(js_error),
+    }
+}
+
+// An mjai log as an array of events or a JSONL string.
+fn mjai_events_of(val: JsValue) -> Result<Vec<Event>, JsValue> {
+    match val.as_string() {
+        Some(log) => mjai_events(&log).map_err(js_error),
+        None => from_value(val).map_err(|e| stage_error("parse", "Deserialize", e)),
+    }
+}
+
+fn jikaze_of(jikaze: &JsValue) -> Result<String, JsValue> {
+    jikaze
+        .as_string()
+        .ok_or_else(|| stage_error("parse", "InvalidJikaze", "invalid jikaze"))
+}
+
+// A RawBoard JavaScript can build and edit field by field, e.g. `board.tehai = "123m"`, which is checked
+// and generated without converting a plain object on every call.
+#[wasm_bindgen(js_name = RawBoard)]
+#[derive(Default)]
+pub struct JsRawBoard {
+    raw: RawBoard,
+}
+
+#[wasm_bindgen(js_class = RawBoard)]
+impl JsRawBoard {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> JsRawBoard {
+        JsRawBoard::default()
+    }
+
+    // from a RawBoard object or the text format
+    pub fn from(val: JsValue) -> Result<JsRawBoard, JsValue> {
+        Ok(JsRawBoard { raw: raw_board_of(val)? })
+    }
+
+    pub fn to_object(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.raw)
+    }
+
+    // Throws the error generating the board would, without generating it.
+    pub fn validate(&self) -> Result<(), JsValue> {
+        parse_board(self.raw.clone(), true).map(|_| ()).map_err(js_error)
+    }
+
+    pub fn generate(&self, options: JsValue) -> Result<JsValue, JsValue> {
+        let options: GenerateOptions = options_of(options)?;
+        let result = generate(self.raw.clone(), &options).map_err(js_error)?;
+        result_to_js(result, &options)
+    }
+}
+
+macro_rules! raw_board_fields {
+    ($($field:ident $setter:ident: $ty:ty),* $(,)?) => {
+        #[wasm_bindgen(js_class = RawBoard)]
+        impl JsRawBoard {
+            $(
+                #[wasm_bindgen(getter)]
+                pub fn $field(&self) -> $ty {
+                    self.raw.$field.clone()
+                }
+
+                #[wasm_bindgen(setter)]
+                pub fn $setter(&mut self, value: $ty) {
+                    self.raw.$field = value;
+                }
+            )*
+        }
+    };
+}
+
+raw_board_fields! {
+    kyoku set_kyoku: String,
+    jikaze set_jikaze: String,
+    kyotaku set_kyotaku: String,
+    honba set_honba: String,
+    dora set_dora: String,
+    scores set_scores: Vec<String>,
+    tehai set_tehai: String,
+    kawa set_kawa: Vec<String>,
+    fuuro set_fuuro: Vec<String>,
+    junme set_junme: String,
+    chiicha set_chiicha: String,
+    claim set_claim: String,
+    fill_avoid set_fill_avoid: String,
+    fill_genbutsu set_fill_genbutsu: String,
+    bots set_bots: String,
+    players set_players: String,
+    nuki set_nuki: Vec<String>,
+    uradora set_uradora: String,
+    tsumo set_tsumo: String,
+    deposits set_deposits: String,
+    draws set_draws: Vec<String>,
+}
+
+#[wasm_bindgen]
+pub fn parse_board_text_js(text: String) -> Result<JsValue, JsValue> {
+    let board = text::parse_board(&text).map_err(|e| stage_error("parse", "Parse", e))?;
+    to_js(&board)
+}
+
+#[wasm_bindgen]
+pub fn parse_board_query_js(query: String) -> Result<JsValue, JsValue> {
+    let board = RawBoard::from_query_string(&query).map_err(|e| stage_error("parse", "Parse", e))?;
+    to_js(&board)
+}
+
+// `zero_aka` writes the red fives 0m however they were typed, see RawBoard::zero_aka; so does board_to_text_js.
+#[wasm_bindgen]
+pub fn board_to_query_js(val: JsValue, zero_aka: Option<bool>) -> Result<String, JsValue> {
+    let mut board = raw_board_of(val)?;
+    if zero_aka == Some(true) {
+        board.zero_aka();
+    }
+    Ok(board.to_query_string())
+}
+
+// Token spans of every board field and what each token was read as, for highlighting in an editor.
+#[wasm_bindgen]
+pub fn explain_parse_js(val: JsValue) -> Result<JsValue, JsValue> {
+    let raw_board = raw_board_of(val)?;
+    to_js(&notation::explain(&raw_board))
+}
+
+// The board as a small html snippet with inline css, for pasting into forum posts.
+#[wasm_bindgen]
+pub fn board_to_html_js(val: JsValue) -> Result<String, JsValue> {
+    let board = parse_board(raw_board_of(val)?, true).map_err(js_error)?;
+    Ok(html::board_to_html(&board))
+}
+
+// A standalone svg image of the board, e.g. for bots posting positions without a browser.
+#[wasm_bindgen]
+pub fn render_board_svg_js(val: JsValue) -> Result<String, JsValue> {
+    render_board_svg(raw_board_of(val)?).map_err(js_error)
+}
+
+#[wasm_bindgen]
+pub fn board_to_text_js(val: JsValue, zero_aka: Option<bool>) -> Result<String, JsValue> {
+    let mut board: RawBoard = from_value(val).map_err(|e| stage_error("parse", "Deserialize", e))?;
+    if zero_aka == Some(true) {
+        board.zero_aka();
+    }
+    text::board_to_text(&board).map_err(|e| stage_error("convert", "Export", e))
+}
+
+#[wasm_bindgen]
+pub fn generate_logs_js(val: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let options: GenerateOptions = options_of(options)?;
+    in_options(&options, || {
+        let raw_board = raw_board_of(val)?;
+        let result = generate(raw_board, &options).map_err(js_error)?;
+        result_to_js(result, &options)
+    })
+}
+
+// Generates like generate_logs_js and hands `callback` each mjai event as (json, index), the event as a
+// json string for JSON.parse, which is far cheaper on long logs than building every event object here.
+// Returning false from `callback` stops the walk. The result comes back without its mjai_log.
+#[wasm_bindgen]
+pub fn for_each_event_js(val: JsValue, options: JsValue, callback: &js_sys::Function) -> Result<JsValue, JsValue> {
+    let options: GenerateOptions = options_of(options)?;
+    in_options(&options, || {
+        let raw_board = raw_board_of(val)?;
+        let mut result = generate(raw_board, &options).map_err(js_error)?;
+        let events = nuki::mjai_values(&std::mem::take(&mut result.mjai_log));
+        for (index, event) in events.map_err(|e| stage_error("convert", "Serialize", e))?.iter().enumerate() {
+            let json = serde_json::to_string(event).map_err(|e| stage_error("convert", "Serialize", e))?;
+            let next = callback.call2(&JsValue::NULL, &JsValue::from_str(&json), &JsValue::from(index as u32))?;
+            if next.as_bool() == Some(false) {
+                break;
+            }
+        }
+        result_to_js(result, &options)
+    })
+}
+
+// Same as generate_logs_js with json strings on both ends, which skips the JsValue conversions that
+// dominate when generating in bulk. An empty `options_json` means the default options.
+#[wasm_bindgen]
+pub fn generate_logs_from_json(board_json: &str, options_json: &str) -> Result<String, JsValue> {
+    generate_from_json(board_json, options_json).map_err(js_error)
+}
+
+// utf-8 json in and out, for callers that keep their boards in Uint8Arrays
+#[wasm_bindgen]
+pub fn generate_logs_from_json_bytes(board_json: &[u8], options_json: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let board_json = std::str::from_utf8(board_json).map_err(|e| stage_error("parse", "Deserialize", e))?;
+    let options_json = std::str::from_utf8(options_json).map_err(|e| stage_error("parse", "InvalidOptions", e))?;
+    generate_logs_from_json(board_json, options_json).map(String::into_bytes)
+}
+
+// The result of generate_logs_js as MessagePack with named fields, so decoding it (e.g. with www/msgpack.js)
+// gives the same object generate_logs_js returns, at a fraction of the size to postMessage between workers.
+#[wasm_bindgen]
+pub fn generate_logs_msgpack_js(val: JsValue, options: JsValue) -> Result<Vec<u8>, JsValue> {
+    let options: GenerateOptions = options_of(options)?;
+    in_options(&options, || {
+        let raw_board = raw_board_of(val)?;
+        let result = generate(raw_board, &options).map_err(js_error)?;
+        if CAMEL_CASE.get() {
+            let mut value = serde_json::to_value(&result).map_err(|e| stage_error("convert", "Serialize", e))?;
+            camel_case_keys(&mut value);
+            return rmp_serde::to_vec_named(&value).map_err(|e| stage_error("convert", "Serialize", e));
+        }
+        rmp_serde::to_vec_named(&result).map_err(|e| stage_error("convert", "Serialize", e))
+    })
+}
+
+// Generates an array of boards (RawBoard objects or text notation) a chunk at a time, so a worker can report
+// progress between chunks and cancel by freeing it. A board that fails does not stop the others.
+#[wasm_bindgen]
+pub struct BatchGenerator {
+    boards: Vec<serde_json::Value>,
+    options: GenerateOptions,
+    next: usize,
+}
+
+#[derive(Serialize)]
+struct BatchItem {
+    index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<GenerateResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<JsError>,
+}
+
+#[wasm_bindgen]
+impl BatchGenerator {
+    #[wasm_bindgen(constructor)]
+    pub fn new(boards: JsValue, options: JsValue) -> Result<BatchGenerator, JsValue> {
+        Ok(BatchGenerator {
+            boards: from_value(boards).map_err(|e| stage_error("parse", "Deserialize", e))?,
+            options: options_of(options)?,
+            next: 0,
+        })
+    }
+
+    // The next `size` boards as [{index, result} or {index, error}], empty once every board is done.
+    pub fn next_chunk(&mut self, size: usize) -> Result<JsValue, JsValue> {
+        let options = std::mem::take(&mut self.options);
+        let chunk = in_options(&options, || self.chunk(size, &options));
+        self.options = options;
+        chunk
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn done(&self) -> usize {
+        self.next
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn total(&self) -> usize {
+        self.boards.len()
+    }
+}
+
+impl BatchGenerator {
+    fn chunk(&mut self, size: usize, options: &GenerateOptions) -> Result<JsValue, JsValue> {
+        let end = self.boards.len().min(self.next + size);
+        let items: Vec<BatchItem> = (self.next..end)
+            .map(|index| {
+                let board = &self.boards[index];
+                let raw_board = match board.as_str() {
+                    Some(text) => text::parse_board(text),
+                    None => RawBoard::deserialize(board).context("deserialize error"),
+                };
+                match raw_board.and_then(|raw_board| generate(raw_board, options)) {
+                    Ok(result) => BatchItem {
+                        index,
+                        result: Some(result),
+                        error: None,
+                    },
+                    Err(e) => BatchItem {
+                        index,
+                        result: None,
+                        error: Some(localized(js_error_of(e))),
+                    },
+                }
+            })
+            .collect();
+        self.next = end;
+        to_js(&items)
+    }
+}
+
+#[wasm_bindgen]
+pub fn generate_quiz_js(val: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let options: GenerateOptions = options_of(options)?;
+    in_options(&options, || {
+        let raw_board = raw_board_of(val)?;
+        to_js(&generate_quiz(raw_board, &options).map_err(js_error)?)
+    })
+}
+
+// See scenario::ScenarioOptions for `scenario`; `options` are the usual GenerateOptions.
+#[wasm_bindgen]
+pub fn generate_scenario_js(scenario: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let scenario: ScenarioOptions = options_of(scenario)?;
+    let options: GenerateOptions = options_of(options)?;
+    in_options(&options, || to_js(&generate_scenario(&scenario, &options).map_err(js_error)?))
+}
+
+#[wasm_bindgen]
+pub fn generate_highlight_from_tenhou_js(val: JsValue, jikaze: JsValue, kyoku: usize, turn: usize) -> Result<JsValue, JsValue> {
+    let events = tenhou_events(val)?;
+    let result = generate_highlight(&events, &jikaze_of(&jikaze)?, kyoku, turn).map_err(js_error)?;
+    to_js(&result)
+}
+
+#[wasm_bindgen]
+pub fn tenhou_log_id_js(val: JsValue) -> Result<String, JsValue> {
+    Ok(tenhou::log_id(&json_of(val)?))
+}
+
+// The tenhou viewer link of an imported log and the nodocchi.moe stats page of each player.
+#[wasm_bindgen]
+pub fn log_links_js(val: JsValue, seat: u8) -> Result<JsValue, JsValue> {
+    if seat > 3 {
+        return Err(stage_error(
+            "parse",
+            "InvalidSeat",
+            format!("seat {seat} is not one of 0 to 3"),
+        ));
+    }
+    let links = url::log_links(&json_of(val)?, seat).map_err(|e| stage_error("parse", "Parse", e))?;
+    to_js(&links)
+}
+
+#[wasm_bindgen]
+pub fn validate_tenhou_log_js(val: JsValue) -> Result<(), JsValue> {
+    tenhou::validate(&json_of(val)?).map_err(|e| stage_error("parse", "InvalidLog", e))
+}
+
+// The first event of an mjai log (a jsonl string or an array of events) that cannot happen, as an error
+// whose `offset` is the event's index.
+#[wasm_bindgen]
+pub fn validate_mjai_js(events: JsValue) -> Result<(), JsValue> {
+    validate_mjai(&mjai_events_of(events)?).map_err(|e| {
+        error_value(JsError {
+            stage: "parse",
+            code: "InvalidEvent",
+            message: e.reason,
+            field: None,
+            offset: Some(e.index),
+        })
+    })
+}
+
+// Per-seat discard timelines of the `kyoku`-th kyoku (0-based), indexed by absolute seat.
+#[wasm_bindgen]
+pub fn discard_timeline_from_tenhou_js(val: JsValue, kyoku: usize) -> Result<JsValue, JsValue> {
+    let events = tenhou_events(val)?;
+    let range =
+        kyoku_range(&events, kyoku).ok_or_else(|| stage_error("parse", "NoKyoku", format!("no kyoku at index {kyoku}")))?;
+    to_js(&timeline::discard_timelines(&events[range]))
+}
+
+// "en" or "ja", the language of the error messages from then on. Messages in other locales start with a
+// translation and keep the English message after it.
+#[wasm_bindgen]
+pub fn set_locale_js(locale: String) -> Result<(), JsValue> {
+    locale::set_locale(locale.parse().map_err(|e| stage_error("parse", "InvalidOptions", e))?);
+    Ok(())
+}
+
+// Whether every binding from then on returns camelCase keys, e.g. tenhouLog, as the camel_case option does
+// for the bindings that take GenerateOptions.
+#[wasm_bindgen]
+pub fn set_camel_case_js(camel_case: bool) {
+    CAMEL_CASE.set(camel_case);
+}
+
+// Keeps the mjai conversion of the last `capacity` tenhou logs, so switching perspective or turn on the
+// same game skips the conversion. 0, the default, turns the cache off.
+#[wasm_bindgen]
+pub fn set_conversion_cache_js(capacity: usize) {
+    cache::set_capacity(capacity);
+}
+
+// The single place long-lived pages can call to release whatever the module retains.
+#[wasm_bindgen]
+pub fn free_caches_js() {
+    cache::clear();
+}
+
+#[wasm_bindgen]
+pub fn reconcile_dora_js(val: JsValue, mode: String) -> Result<JsValue, JsValue> {
+    let raw_board: RawBoard = from_value(val).map_err(|e| stage_error("parse", "Deserialize", e))?;
+    to_js(&reconcile_board(raw_board, &mode).map_err(js_error)?)
+}
+
+#[wasm_bindgen]
+pub fn analyze_walls_js(val: JsValue) -> Result<JsValue, JsValue> {
+    to_js(&analyze_walls(raw_board_of(val)?).map_err(js_error)?)
+}
+
+#[wasm_bindgen]
+pub fn analyze_danger_js(val: JsValue) -> Result<JsValue, JsValue> {
+    to_js(&analyze_danger(raw_board_of(val)?).map_err(js_error)?)
+}
+
+#[wasm_bindgen]
+pub fn count_tiles_js(val: JsValue) -> Result<JsValue, JsValue> {
+    to_js(&count_tiles(raw_board_of(val)?).map_err(js_error)?)
+}
+
+#[wasm_bindgen]
+pub fn analyze_riichi_safety_js(val: JsValue) -> Result<JsValue, JsValue> {
+    to_js(&analyze_riichi_safety(raw_board_of(val)?).map_err(js_error)?)
+}
+
+#[wasm_bindgen]
+pub fn analyze_hand_js(val: JsValue) -> Result<JsValue, JsValue> {
+    to_js(&analyze_hand(raw_board_of(val)?).map_err(js_error)?)
+}
+
+// Warnings about the scores and sticks of a board that parses, see check_board.
+#[wasm_bindgen]
+pub fn check_board_js(val: JsValue) -> Result<JsValue, JsValue> {
+    to_js(&check_board(raw_board_of(val)?).map_err(js_error)?)
+}
+
+// The waits of a tenpai hand and what each scores, see analyze_waits.
+#[wasm_bindgen]
+pub fn analyze_waits_js(val: JsValue) -> Result<JsValue, JsValue> {
+    to_js(&analyze_waits(raw_board_of(val)?).map_err(js_error)?)
+}
+
+// See WinOptions for `options`.
+#[wasm_bindgen]
+pub fn score_win_js(val: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let options: WinOptions = options_of(options)?;
+    to_js(&score_win(raw_board_of(val)?, &options).map_err(js_error)?)
+}
+
+#[wasm_bindgen]
+pub fn generate_board_from_tenhou_js(val: JsValue, jikaze: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let options: ExtractOptions = options_of(options)?;
+    let events = tenhou_events(val)?;
+    to_js(&extract_board(events, &jikaze_of(&jikaze)?, &options).map_err(js_error)?)
+}
+
+// Every kyoku's board for the player at `jikaze` in the first one, see extract_boards.
+#[wasm_bindgen]
+pub fn generate_boards_from_tenhou_all_js(val: JsValue, jikaze: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let options: ExtractOptions = options_of(options)?;
+    let events = tenhou_events(val)?;
+    to_js(&extract_boards(events, &jikaze_of(&jikaze)?, &options).map_err(js_error)?)
+}
+
+// The boards after each event (or discard) of a tenhou log or mjlog's kyoku, see extract_snapshots.
+#[wasm_bindgen]
+pub fn generate_snapshots_from_tenhou_js(val: JsValue, jikaze: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let options: ExtractOptions = options_of(options)?;
+    let events = tenhou_events(val)?;
+    to_js(&extract_snapshots(events, &jikaze_of(&jikaze)?, &options).map_err(js_error)?)
+}
+
+// Like generate_snapshots_from_tenhou_js for an mjai log, given as generate_board_from_mjai_js takes it.
+#[wasm_bindgen]
+pub fn generate_snapshots_from_mjai_js(val: JsValue, jikaze: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let options: ExtractOptions = options_of(options)?;
+    let events = mjai_events_of(val)?;
+    to_js(&extract_snapshots(events, &jikaze_of(&jikaze)?, &options).map_err(js_error)?)
+}
+
+// Takes the decoded actions of a MahjongSoul record and extracts the board of a round, like
+// generate_board_from_tenhou_js.
+#[wasm_bindgen]
+pub fn generate_board_from_majsoul_js(val: JsValue, jikaze: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let options: ExtractOptions = options_of(options)?;
+    let events = majsoul_events(&json_of(val)?).map_err(js_error)?;
+    to_js(&extract_board(events, &jikaze_of(&jikaze)?, &options).map_err(js_error)?)
+}
+
+// Takes a Riichi City replay and extracts the board of a kyoku, like generate_board_from_tenhou_js.
+#[wasm_bindgen]
+pub fn generate_board_from_riichi_city_js(val: JsValue, jikaze: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let options: ExtractOptions = options_of(options)?;
+    let events = riichi_city_events(&json_of(val)?).map_err(js_error)?;
+    to_js(&extract_board(events, &jikaze_of(&jikaze)?, &options).map_err(js_error)?)
+}
+
+// An mjai log, e.g. a bot's, as an array of events or a JSONL string, to a board like
+// generate_board_from_tenhou_js.
+#[wasm_bindgen]
+pub fn generate_board_from_mjai_js(val: JsValue, jikaze: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let options: ExtractOptions = options_of(options)?;
+    let events = mjai_events_of(val)?;
+    to_js(&extract_board(events, &jikaze_of(&jikaze)?, &options).map_err(js_error)?)
+}
+
+// The board at a flagged decision of a Mortal review, the `entry`-th of kyoku `kyoku` in review.kyokus, as the
+// reviewed player saw it.
+#[wasm_bindgen]
+pub fn generate_board_from_mortal_js(val: JsValue, kyoku: usize, entry: usize, options: JsValue) -> Result<JsValue, JsValue> {
+    let options: ExtractOptions = options_of(options)?;
+    to_js(&extract_review_board(&json_of(val)?, kyoku, entry, &options).map_err(js_error)?)
+}
+
+#[wasm_bindgen]
+pub fn majsoul_to_mjai_js(val: JsValue) -> Result<JsValue, JsValue> {
+    to_js(&majsoul_events(&json_of(val)?).map_err(js_error)?)
+}
+
+#[wasm_bindgen]
+pub fn riichi_city_to_mjai_js(val: JsValue) -> Result<JsValue, JsValue> {
+    to_js(&riichi_city_events(&json_of(val)?).map_err(js_error)?)
+}
@@ -0,0 +1,23 @@
+use riichi::mjai::Event;
+
+// Ends the kyoku of a generated log with `actor` winning off `target`, the whole score being worked out by
+// the tenhou conversion. A log that already ends in a win is left alone.
+pub(crate) fn append_win(events: &mut Vec<Event>, actor: u8, target: u8) {
+    if events.iter().any(|event| matches!(event, Event::Hora { .. })) {
+        return;
+    }
+    let tail_start = events.len()
+        - events
+            .iter()
+            .rev()
+            .take_while(|event| matches!(event, Event::EndKyoku | Event::EndGame))
+            .count();
+    let tail = events.split_off(tail_start);
+    events.push(Event::Hora {
+        actor,
+        target,
+        deltas: None,
+        ura_markers: None,
+    });
+    events.extend(tail);
+}
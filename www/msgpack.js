@@ -0,0 +1,75 @@
+// Decoder for the MessagePack returned by generate_logs_msgpack_js. It covers the subset rmp-serde emits
+// for a GenerateResult: maps with string keys, arrays, strings, integers, floats, booleans and nil.
+//
+// The decoded value has the same shape as the object returned by generate_logs_js:
+//
+// /**
+//  * @typedef {Object} GenerateResult
+//  * @property {string} tenhou_log
+//  * @property {string} human_tenhou_log
+//  * @property {Object[]} mjai_log
+//  * @property {number} player_id
+//  * @property {string[]} [final_hands]
+//  * @property {Object} [result]
+//  */
+
+const textDecoder = new TextDecoder();
+
+export function decodeMsgpack(bytes) {
+    const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+    let offset = 0;
+
+    const str = (length) => {
+        const value = textDecoder.decode(bytes.subarray(offset, offset + length));
+        offset += length;
+        return value;
+    };
+    const array = (length) => {
+        const value = [];
+        for (let i = 0; i < length; i++) value.push(read());
+        return value;
+    };
+    const map = (length) => {
+        const value = {};
+        for (let i = 0; i < length; i++) {
+            const key = read();
+            value[key] = read();
+        }
+        return value;
+    };
+
+    function read() {
+        const byte = view.getUint8(offset++);
+        if (byte <= 0x7f) return byte;
+        if (byte >= 0xe0) return byte - 0x100;
+        if ((byte & 0xf0) === 0x80) return map(byte & 0x0f);
+        if ((byte & 0xf0) === 0x90) return array(byte & 0x0f);
+        if ((byte & 0xe0) === 0xa0) return str(byte & 0x1f);
+        let value;
+        switch (byte) {
+            case 0xc0: return null;
+            case 0xc2: return false;
+            case 0xc3: return true;
+            case 0xca: value = view.getFloat32(offset); offset += 4; return value;
+            case 0xcb: value = view.getFloat64(offset); offset += 8; return value;
+            case 0xcc: return view.getUint8(offset++);
+            case 0xcd: value = view.getUint16(offset); offset += 2; return value;
+            case 0xce: value = view.getUint32(offset); offset += 4; return value;
+            case 0xcf: value = Number(view.getBigUint64(offset)); offset += 8; return value;
+            case 0xd0: return view.getInt8(offset++);
+            case 0xd1: value = view.getInt16(offset); offset += 2; return value;
+            case 0xd2: value = view.getInt32(offset); offset += 4; return value;
+            case 0xd3: value = Number(view.getBigInt64(offset)); offset += 8; return value;
+            case 0xd9: return str(view.getUint8(offset++));
+            case 0xda: value = view.getUint16(offset); offset += 2; return str(value);
+            case 0xdb: value = view.getUint32(offset); offset += 4; return str(value);
+            case 0xdc: value = view.getUint16(offset); offset += 2; return array(value);
+            case 0xdd: value = view.getUint32(offset); offset += 4; return array(value);
+            case 0xde: value = view.getUint16(offset); offset += 2; return map(value);
+            case 0xdf: value = view.getUint32(offset); offset += 4; return map(value);
+            default: throw new Error(`unsupported msgpack type 0x${byte.toString(16)}`);
+        }
+    }
+
+    return read();
+}